@@ -0,0 +1,402 @@
+use std::future::Future;
+
+use iced::futures::StreamExt;
+use tokio::io::AsyncBufReadExt;
+
+use iced_dobot_controller::utils::{dobot::*, helpers::*, structs::*};
+
+const USAGE: &str = "Usage: iced_dobot_controller [COMMAND] [--simulate] [--no-wait]
+Starts the GUI when no command is given.
+
+Commands:
+  list             List the sequences in sequences_path
+  run <sequence>   Play a sequence, by name in sequences_path or by file path
+  home             Home the arm
+  test             Test the connection to the arm
+  replay <result>  Draw the shapes of a result file in the same order,
+                   by name in results_path or by file path
+  help             Show this text
+
+Options:
+  --simulate       Use the simulated arm, same as unconnected mode in the GUI
+  --no-wait        replay: draw the next shape right away instead of waiting for Enter
+
+Ctrl+C stops the arm and releases the relay.";
+
+/// Exit codes, so scripts can tell what went wrong.
+const EXIT_OK         : i32 = 0;
+const EXIT_FAILED     : i32 = 1;
+const EXIT_USAGE      : i32 = 2;
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Runs a headless subcommand and returns the process exit code.
+pub fn run(args: Vec<String>, config: Config) -> i32 {
+    let (flags, args): (Vec<String>, Vec<String>) =
+        args.into_iter().partition(|arg| arg.starts_with("--"));
+    let is_simulate = flags.iter().any(|flag| flag == "--simulate");
+    let is_no_wait = flags.iter().any(|flag| flag == "--no-wait");
+    if let Some(flag) = flags
+        .iter()
+        .find(|flag| !["--simulate", "--no-wait", "--help"].contains(&flag.as_str()))
+    {
+        eprintln!("Unknown option {}\n\n{}", flag, USAGE);
+        return EXIT_USAGE;
+    }
+    if flags.iter().any(|flag| flag == "--help") {
+        println!("{}", USAGE);
+        return EXIT_OK;
+    }
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(errmsg) => {
+            eprintln!("Cannot start the async runtime. {}", errmsg);
+            return EXIT_FAILED;
+        }
+    };
+    runtime.block_on(async move {
+        match args.iter().map(String::as_str).collect::<Vec<&str>>().as_slice() {
+            ["help"] => {
+                println!("{}", USAGE);
+                EXIT_OK
+            }
+            ["list"] => list_sequences(&config).await,
+            ["run", sequence] => {
+                let session = CliSession::connect(config, is_simulate);
+                session.until_interrupted(session.run_sequence(sequence)).await
+            }
+            ["home"] => {
+                let session = CliSession::connect(config, is_simulate);
+                session
+                    .until_interrupted(async {
+                        report("Homing", go_home(session.dobot.clone()).await)
+                    })
+                    .await
+            }
+            ["test"] => {
+                let session = CliSession::connect(config, is_simulate);
+                report("Connection test", test_connection(session.dobot.clone()).await)
+            }
+            ["replay", result_file] => {
+                let session = CliSession::connect(config, is_simulate);
+                session
+                    .until_interrupted(session.replay_results(result_file, is_no_wait))
+                    .await
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                EXIT_USAGE
+            }
+        }
+    })
+}
+
+/// Prints the outcome of a single dobot command.
+fn report(what: &str, erropt: Option<DobotError>) -> i32 {
+    match erropt {
+        Some(errmsg) => {
+            eprintln!("{} failed. {}", what, errmsg);
+            EXIT_FAILED
+        }
+        None => {
+            println!("{} ok.", what);
+            EXIT_OK
+        }
+    }
+}
+
+/// Name in the folder, or the path as given if a file exists there.
+fn resolve_path(folder: &String, name: &str) -> String {
+    if std::path::Path::new(name).is_file() {
+        name.to_string()
+    } else {
+        format!("{}/{}.json", folder, name)
+    }
+}
+
+async fn list_sequences(config: &Config) -> i32 {
+    let mut sequence_paths = match update_dir_lists(config.sequences_path.clone()).await {
+        (Some(sequence_paths), _) => sequence_paths,
+        (None, errmsg) => {
+            eprintln!("Cannot read {}. {}", config.sequences_path, errmsg.unwrap_or_default());
+            return EXIT_FAILED;
+        }
+    };
+    sequence_paths.sort();
+
+    let mut exit_code = EXIT_OK;
+    for sequence_path in sequence_paths.iter().filter(|path| path.ends_with(".json")) {
+        match load_file_content(sequence_path.clone()).await {
+            (Some(contents), _) => match serde_json::from_str::<NamedSequence>(&contents) {
+                Ok(named_sequence) => println!(
+                    "{}\t{} steps\t{}",
+                    named_sequence.name,
+                    named_sequence.steps.len(),
+                    if named_sequence.is_infinite_loop {
+                        "loop".to_string()
+                    } else {
+                        format!("x{}", named_sequence.repeat_count)
+                    }
+                ),
+                Err(errmsg) => {
+                    eprintln!("{}: {}", sequence_path, errmsg);
+                    exit_code = EXIT_FAILED;
+                }
+            },
+            (None, errmsg) => {
+                eprintln!("{}: {}", sequence_path, errmsg.unwrap_or_default());
+                exit_code = EXIT_FAILED;
+            }
+        }
+    }
+    exit_code
+}
+
+/// Connection to the arm for a single command, same as the GUI keeps for its whole run.
+struct CliSession {
+    dobot      : DobotConnection,
+    config     : Config,
+    is_simulate: bool,
+}
+
+impl CliSession {
+    /// Starts the connection actor. The port is opened by the first command.
+    fn connect(config: Config, is_simulate: bool) -> Self {
+        let (dobot, receivers) = DobotConnection::new();
+        tokio::spawn(dobot_connection_actor(receivers).for_each(|state| async move {
+            log::info!("Dobot {:?}", state);
+        }));
+        dobot.connect(if is_simulate {
+            BackendKind::Simulated
+        } else {
+            BackendKind::Serial(config.dobot_path.clone())
+        });
+        Self {
+            dobot: DobotConnection {
+                jump_height : config.jump_height,
+                jump_z_limit: config.safety_envelope.z_ceiling,
+                ..dobot
+            },
+            config,
+            is_simulate,
+        }
+    }
+
+    /// No relay is attached to the simulated arm.
+    fn relay(&self) -> Option<RelayPath> {
+        if self.is_simulate {
+            None
+        } else {
+            Some(RelayPath {
+                relaypath: self.config.relay_path.clone(),
+            })
+        }
+    }
+
+    /// Runs the command, or stops the arm and releases the relay on Ctrl+C.
+    async fn until_interrupted(&self, command: impl Future<Output = i32>) -> i32 {
+        tokio::select! {
+            exit_code = command => exit_code,
+            _ = tokio::signal::ctrl_c() => {
+                eprintln!("Interrupted, stopping the arm...");
+                if let Some(errmsg) = emergency_stop(self.dobot.clone(), self.relay()).await {
+                    eprintln!("Emergency stop incomplete! {}", errmsg);
+                }
+                EXIT_INTERRUPTED
+            }
+        }
+    }
+
+    /// Lists the offending steps of a refused run, like the GUI.
+    fn report_violations(&self, violations: &[(usize, String)]) {
+        for (idx, violation) in violations {
+            eprintln!("Step {} outside safety envelope, refusing: {}", idx, violation);
+        }
+    }
+
+    async fn run_sequence(&self, sequence: &str) -> i32 {
+        let sequence_path = resolve_path(&self.config.sequences_path, sequence);
+        let named_sequence = match load_file_content(sequence_path.clone()).await {
+            (Some(contents), _) => match serde_json::from_str::<NamedSequence>(&contents) {
+                Ok(named_sequence) => named_sequence,
+                Err(errmsg) => {
+                    eprintln!("{}: {}", sequence_path, errmsg);
+                    return EXIT_FAILED;
+                }
+            },
+            (None, errmsg) => {
+                eprintln!("{}: {}", sequence_path, errmsg.unwrap_or_default());
+                return EXIT_FAILED;
+            }
+        };
+        let violations = self
+            .config
+            .safety_envelope
+            .check_steps(&named_sequence.steps, self.config.jump_height);
+        if !violations.is_empty() {
+            self.report_violations(&violations);
+            return EXIT_FAILED;
+        }
+
+        println!("Playing {}, {} steps", named_sequence.name, named_sequence.steps.len());
+        // Nothing pauses a headless playback, the sender is only kept so the channel stays open.
+        let (_control_sender, control_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut events = Box::pin(play_sequence(
+            self.dobot.clone(),
+            self.relay(),
+            named_sequence,
+            control_receiver,
+        ));
+        let mut exit_code = EXIT_FAILED;
+        while let Some(event) = events.next().await {
+            match event {
+                PlaybackEvent::Iteration(iteration, repeat_total) => match repeat_total {
+                    Some(repeat_total) => println!("Iteration {}/{}", iteration, repeat_total),
+                    None => println!("Iteration {}", iteration),
+                },
+                PlaybackEvent::Moving(idx, total) => println!("Step {}/{}", idx + 1, total),
+                PlaybackEvent::Failed(idx, errmsg) => {
+                    eprintln!("Step {} failed. {}", idx, errmsg);
+                    exit_code = EXIT_FAILED;
+                }
+                PlaybackEvent::Finished => {
+                    println!("Finished.");
+                    exit_code = EXIT_OK;
+                }
+                PlaybackEvent::Stopped(idx) => {
+                    eprintln!("Stopped before step {}.", idx);
+                    exit_code = EXIT_FAILED;
+                }
+                PlaybackEvent::Reached(..) | PlaybackEvent::Paused(_) | PlaybackEvent::Resumed => {}
+            }
+        }
+        exit_code
+    }
+
+    /// Draws the true shapes of a result file in order, with the speed and acceleration it was run at,
+    /// per trial for results of a protocol.
+    /// Waits for Enter before every shape unless no_wait, like the experimenter does in the GUI.
+    async fn replay_results(&self, result_file: &str, is_no_wait: bool) -> i32 {
+        let result_path = resolve_path(&self.config.results_path, result_file);
+        let result_exports = match load_file_content(result_path.clone()).await {
+            (Some(contents), _) => match serde_json::from_str::<ResultExports>(&contents) {
+                Ok(result_exports) => result_exports,
+                Err(errmsg) => {
+                    eprintln!("{}: {}", result_path, errmsg);
+                    return EXIT_FAILED;
+                }
+            },
+            (None, errmsg) => {
+                eprintln!("{}: {}", result_path, errmsg.unwrap_or_default());
+                return EXIT_FAILED;
+            }
+        };
+
+        let shape_library = match load_shape_library(self.config.shapes_path.clone()).await {
+            (Some(shape_library), errmsg) => {
+                if let Some(errmsg) = errmsg {
+                    eprintln!("Some shapes failed to load. {}", errmsg);
+                }
+                shape_library
+            }
+            (None, errmsg) => {
+                eprintln!("Cannot load the shape library. {}", errmsg.unwrap_or_default());
+                return EXIT_FAILED;
+            }
+        };
+        // Everything is checked before the first shape, so a replay never stops halfway on a missing shape.
+        let mut trials = Vec::with_capacity(result_exports.results.len());
+        for result_item in &result_exports.results {
+            let parameters = result_item
+                .condition
+                .as_ref()
+                .and_then(|name| {
+                    result_exports
+                        .condition_analyses
+                        .iter()
+                        .find(|analysis| analysis.condition == *name)
+                })
+                .map_or(&result_exports.parameters, |analysis| &analysis.parameters);
+            let parameters = &match (&result_exports.staircase, result_item.level) {
+                (Some(result), Some(level)) => result.staircase.apply(parameters, level),
+                _ => parameters.clone(),
+            };
+            let (speed, acceleration) = match (
+                parameters.speed.value.parse::<f32>(),
+                parameters.acceleration.value.parse::<f32>(),
+            ) {
+                (Ok(speed), Ok(acceleration)) => (speed, acceleration),
+                _ => {
+                    eprintln!(
+                        "Speed {} or acceleration {} not parseable as f32.",
+                        parameters.speed.value, parameters.acceleration.value
+                    );
+                    return EXIT_FAILED;
+                }
+            };
+            let Some(shape) = shape_library
+                .iter()
+                .find(|shape| shape.name == result_item.true_shape)
+            else {
+                eprintln!("Shape {} is not in the library.", result_item.true_shape);
+                return EXIT_FAILED;
+            };
+            let drawing_steps = shape.drawing_steps(
+                speed,
+                acceleration,
+                self.config.calibration.as_ref(),
+                self.config.rest_pose,
+            );
+            let violations = self
+                .config
+                .safety_envelope
+                .check_steps(&drawing_steps, self.config.jump_height);
+            if !violations.is_empty() {
+                eprintln!("Shape {} leaves the safety envelope.", shape.name);
+                self.report_violations(&violations);
+                return EXIT_FAILED;
+            }
+            let label = match (&result_item.condition, result_item.level) {
+                (Some(condition), _) => format!("{} ({})", shape.name, condition),
+                (None, Some(level)) => format!("{} (level {})", shape.name, level),
+                (None, None) => shape.name.clone(),
+            };
+            trials.push((shape, label, drawing_steps));
+        }
+
+        let mut stdin = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+        let trial_count = trials.len();
+        for (trial_idx, (shape, label, drawing_steps)) in trials.into_iter().enumerate() {
+            if !is_no_wait {
+                println!(
+                    "Trial {}/{}: {}. Enter to draw, q to quit.",
+                    trial_idx + 1,
+                    trial_count,
+                    label
+                );
+                match stdin.next_line().await {
+                    Ok(Some(line)) if line.trim() != "q" => {}
+                    _ => {
+                        println!("Replay ended before trial {}.", trial_idx + 1);
+                        return EXIT_OK;
+                    }
+                }
+            } else {
+                println!("Trial {}/{}: {}", trial_idx + 1, trial_count, label);
+            }
+            if let Some(errmsg) = draw_shape(
+                self.dobot.clone(),
+                self.relay(),
+                shape.name.clone(),
+                drawing_steps,
+            )
+            .await
+            {
+                eprintln!("Drawing {} failed. {}", shape.name, errmsg);
+                return EXIT_FAILED;
+            }
+        }
+        println!("Replay finished.");
+        EXIT_OK
+    }
+}
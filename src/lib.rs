@@ -0,0 +1,4 @@
+//! Everything that runs without a window: the arm connection, the sequencer model,
+//! the experiment flow and results processing.
+//! main.rs is the iced shell around it, cli.rs the headless one.
+pub mod utils;
@@ -1,16 +1,19 @@
-use std::{num::ParseIntError, time::Instant};
+use std::time::Instant;
 use dragking::{DragEvent, DropPosition};
 use iced::{
     font,
     widget::{
-        button, checkbox, column, container, horizontal_rule, horizontal_space, pick_list,
+        button, canvas, checkbox, column, container, horizontal_rule, horizontal_space, pick_list,
         progress_bar, row, scrollable, text, text_input, Column, Container, Row, Space,
     },
-    Alignment, Color, Element, Font, Length, Padding, Task, Theme,
+    keyboard, Alignment, Color, Element, Font, Length, Padding, Subscription, Task, Theme,
 };
-mod utils;
-use utils::structs::LogType as LT;
-use utils::{dobot::*, experiment::*, helpers::*, structs::*, styling::*};
+use iced_dobot_controller::utils::structs::LogType as LT;
+use iced_dobot_controller::utils::{
+    dobot::*, experiment::*, helpers::*, journal::*, preview::*, sequencer::*, shapegen::*,
+    simulator::forward_kinematics, structs::*, styling::*,
+};
+mod cli;
 
 fn main() -> iced::Result {
     simple_logger::SimpleLogger::new()
@@ -25,7 +28,13 @@ fn main() -> iced::Result {
 
     log::info!("Running!");
 
-    configure_startup();
+    let configuration = configure_startup();
+
+    // Any argument runs a headless command instead of the GUI, see cli.rs.
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if !args.is_empty() {
+        std::process::exit(cli::run(args, configuration));
+    }
 
     iced::application(
         IcedDobotController::title(),
@@ -34,6 +43,7 @@ fn main() -> iced::Result {
     )
     .antialiasing(true)
     .theme(IcedDobotController::theme)
+    .subscription(IcedDobotController::subscription)
     .default_font(Font {
         family: font::Family::Name("PlemolJP"),
         ..Default::default()
@@ -41,6 +51,15 @@ fn main() -> iced::Result {
     .run_with(IcedDobotController::new)
 }
 
+/// Speed of cartesian jog steps, independent of the V and A inputs. [mm/s, mm/s²]
+const JOG_VELOCITY    : f32 = 50.0;
+const JOG_ACCELERATION: f32 = 50.0;
+/// How far a joint jog is kept inside the envelope, forward_kinematics is only close. [mm]
+const JOINT_JOG_MARGIN: f32 = 10.0;
+
+/// Points captured for DrawingCalibration::from_points, in order.
+const CALIBRATION_POINT_NAMES: [&str; 3] = ["origin", "+U", "+V"];
+
 #[derive(Clone)]
 struct IcedDobotController {
     is_title_font_ok: bool,
@@ -51,37 +70,62 @@ struct IcedDobotController {
     active_sequence_tab: SequencerTabpage,
 
     // Sequencer Tab Variable
-    active_sequencer_sequence : Position,
-    active_sequencer_sequences: Vec<Position>,
-    sequences_paths           : Vec<String>,
-    active_seq_path_input     : String,
-    active_sequences          : Vec<Position>,
+    sequencer       : SequencerModel,
+    sequences_paths : Vec<String>,
+    active_sequences: Option<NamedSequence>,
+    jog_space       : JogSpace,
+    jog_step        : f32,
 
     // Experiment Tab Variable
     active_experiment_parameters   : Parameters,
-    active_experiment_instance     : Option<ExperimentInstance>,
-    active_experiment_state        : ExperimentStage,
-    active_experiment_shapes_to_use: Vec<(Shapes, bool)>,
-    n_v                            : f32,
-    n_a                            : f32,
+    /// Running experiment, None while setting up.
+    experiment                     : Option<ExperimentSession>,
+    /// Unfinished session from the journal, offered in the setup view.
+    recovered_experiment           : Option<ExperimentSession>,
+    active_experiment_shapes_to_use: Vec<(ShapeDefinition, bool)>,
+    active_randomization           : Randomization,
+    plans_paths                    : Vec<String>,
+    /// Imported plan, the next session runs it instead of drawing a trial order.
+    active_plan                    : Option<ExperimentPlan>,
+    protocols_paths                : Vec<String>,
+    /// Loaded protocol, the next session sweeps its conditions.
+    active_protocol                : Option<ExperimentProtocol>,
+    shape_library                  : Vec<ShapeDefinition>,
+    shape_generator_inputs         : GeneratorInputs,
+    /// Library shape shown in the setup preview, None shows the generator's shape.
+    preview_shape                  : Option<String>,
+    preview_plane                  : ShapePlane,
     error_info                     : Option<String>,
     time_main                      : Instant,
-    is_time_counting               : bool,
-    time_start                     : u128,
-    time_end                       : u128,
-    active_max_idx                 : u32,
-    active_idx                     : u32,
 
     // Results Tab Variable
     results_paths     : Vec<String>,
     active_result_item: Option<ResultExports>,
 
     // Administration
+    dobot_connection: DobotConnection,
+    dobot_state     : ConnectionState,
+    live_pose       : Option<RobotPose>,
+    is_pose_polling : bool,
+    /// Every motion started since the last emergency stop, finished ones are harmless to abort.
+    motion_handles  : Vec<iced::task::Handle>,
+    /// Bumped by the emergency stop and when an experiment ends, see stamp.
+    epoch           : u64,
+
+    // Sequence playback
+    playback_control  : Option<tokio::sync::mpsc::UnboundedSender<PlaybackControl>>,
+    playback_index    : Option<usize>,
+    playback_progress : Option<(usize, usize)>,
+    playback_iteration: Option<(u32, Option<u32>)>,
+    is_playback_paused: bool,
     logs            : Vec<LogMessage>,
     active_config   : Config,
     is_debug_view   : bool,
     is_simulate_mode: bool,
     active_theme    : Theme,
+
+    // Calibration, captured origin, +U and +V points in arm coordinates.
+    calibration_points: [Option<[f32; 3]>; 3],
 }
 
 #[derive(Clone, Debug)]
@@ -92,34 +136,66 @@ enum Message {
     // Sequencer Messages
     SequenceReorder(DragEvent),
     SequencerInputUpdated(Coordinate, String),
+    SequencerModeSelected(MotionMode),
     SequencerMoveToPressed,
     SequencerAddToSequencesPressed,
+    SequencerAddStepPressed(SequenceStep),
+    SeqWaitInputUpdated(String),
     SequencerPerform,
-    SequencerPerformResult(Option<String>),
+    CapturePosePressed,
+    JogPressed(JogAxis, f32),
+    JogSpaceSelected(JogSpace),
+    JogStepSelected(f32),
+    JogResult(Option<RobotPose>, Option<DobotError>),
+    SequencerPerformResult(Option<DobotError>),
     RemoveASequencePressed(usize),
     PasteSequencePressed(usize),
     ClearSequences,
     ClearSeqInput,
     SeqFilenameInputUpdated(String),
+    SeqRepeatInputUpdated(String),
+    SeqDwellInputUpdated(String),
+    SeqLoopToggled(bool),
     SeqSaveFilePressed,
     SeqSaveFileResult(Option<String>),
     SeqLoadFilePressed(usize, bool),
     SeqLoadFileResult(Option<String>, Option<String>, bool),
-    PerformSequenceResult(Option<String>),
+    PlaybackProgress(PlaybackEvent),
+    PlaybackControlPressed(PlaybackControl),
     DobotGoHome,
     DobotTestConnection,
-    DobotResult(Option<String>),
+    DobotResult(Option<DobotError>),
 
     // Experiment Messages
     ParameterInputChanged(ParameterType, String),
-    SelectedShapesChanged(Shapes),
+    SelectedShapesChanged(String),
+    RandomizationStrategySelected(RandomizationStrategy),
+    LatinSquareRowChanged(String),
+    NoRepeatToggled(bool),
+    PlanExportPressed,
+    PlanExportResult(Option<String>),
+    PlanSelected(String),
+    PlanLoaded(Option<String>, Option<String>),
+    PlanClearPressed,
+    ProtocolSelected(String),
+    ProtocolLoaded(Option<String>, Option<String>),
+    ProtocolClearPressed,
     BeginExperimentPressed,
-    ShapeSelected(Shapes),
+    ShapeSelected(String),
     RetryButtonPressed,
+    ConditionReadyPressed,
     ForceAbortPressed,
+    EmergencyStopResult(Option<DobotError>),
     GoToNextStage,
-    DrawingResult(Option<String>),
+    DrawingResult(Option<DobotError>),
     ResultsProcessed(Option<ResultExports>, Option<String>),
+    JournalLoaded(Option<ExperimentSession>, Option<String>),
+    PlansListLoaded(Option<Vec<String>>, Option<String>),
+    ProtocolsListLoaded(Option<Vec<String>>, Option<String>),
+    JournalWritten(Option<String>),
+    RecoveryResumePressed,
+    RecoverySavePartialPressed,
+    RecoveryDiscardPressed,
 
     // Results
     UpdateResultsList,
@@ -128,12 +204,130 @@ enum Message {
 
     // Administration
     FontLoaded(Result<(), font::Error>),
+    DobotConnectionChanged(ConnectionState),
+    PollPose,
+    PoseUpdated(Option<RobotPose>, Option<DobotError>),
     ConfigFileLoaded(Option<String>, Option<String>),
     SequencesListsLoaded(Option<Vec<String>>, Option<String>),
+    ShapeLibraryLoaded(Option<Vec<ShapeDefinition>>, Option<String>),
+    GeneratorKindSelected(GeneratorKind),
+    GeneratorInputChanged(GeneratorField, String),
+    GeneratorSavePressed,
+    GeneratorSaveResult(Option<String>),
+    PreviewShapeSelected(Option<String>),
+    PreviewPlaneSelected(ShapePlane),
     ResultsListsUpdated(Option<Vec<String>>, Option<String>),
     DebugCheckboxPressed(bool),
     SimulateModeCheckboxPressed(bool),
     ThemeSelected(Theme),
+    CalibrationCapturePressed(usize),
+    RestPoseCapturePressed,
+    CalibrationSavePressed,
+    CalibrationClearPressed,
+    ConfigSaveResult(Option<String>),
+    /// Result of a task started in the given epoch, dropped when the epoch has moved on.
+    Stamped(u64, Box<Message>),
+}
+
+impl Message {
+    /// Page whose handler owns the message, whatever tab is on screen.
+    /// None for the global messages handled at the top of update.
+    fn page(&self) -> Option<Tabpage> {
+        match self {
+            Message::SequenceReorder(_)
+            | Message::SequencerInputUpdated(..)
+            | Message::SequencerModeSelected(_)
+            | Message::SequencerMoveToPressed
+            | Message::SequencerAddToSequencesPressed
+            | Message::SequencerAddStepPressed(_)
+            | Message::SeqWaitInputUpdated(_)
+            | Message::SequencerPerform
+            | Message::CapturePosePressed
+            | Message::JogPressed(..)
+            | Message::JogSpaceSelected(_)
+            | Message::JogStepSelected(_)
+            | Message::JogResult(..)
+            | Message::SequencerPerformResult(_)
+            | Message::RemoveASequencePressed(_)
+            | Message::PasteSequencePressed(_)
+            | Message::ClearSequences
+            | Message::ClearSeqInput
+            | Message::SeqFilenameInputUpdated(_)
+            | Message::SeqRepeatInputUpdated(_)
+            | Message::SeqDwellInputUpdated(_)
+            | Message::SeqLoopToggled(_)
+            | Message::SeqSaveFilePressed
+            | Message::SeqSaveFileResult(_)
+            | Message::SeqLoadFilePressed(..)
+            | Message::SeqLoadFileResult(..)
+            | Message::DobotGoHome
+            | Message::DobotTestConnection
+            | Message::DobotResult(_) => Some(Tabpage::Sequencer),
+
+            Message::ParameterInputChanged(..)
+            | Message::SelectedShapesChanged(_)
+            | Message::RandomizationStrategySelected(_)
+            | Message::LatinSquareRowChanged(_)
+            | Message::NoRepeatToggled(_)
+            | Message::PlanExportPressed
+            | Message::PlanExportResult(_)
+            | Message::PlanSelected(_)
+            | Message::PlanLoaded(..)
+            | Message::PlanClearPressed
+            | Message::ProtocolSelected(_)
+            | Message::ProtocolLoaded(..)
+            | Message::ProtocolClearPressed
+            | Message::BeginExperimentPressed
+            | Message::ShapeSelected(_)
+            | Message::RetryButtonPressed
+            | Message::ConditionReadyPressed
+            | Message::GoToNextStage
+            | Message::DrawingResult(_)
+            | Message::ResultsProcessed(..)
+            | Message::RecoveryResumePressed
+            | Message::RecoverySavePartialPressed
+            | Message::RecoveryDiscardPressed
+            | Message::GeneratorKindSelected(_)
+            | Message::GeneratorInputChanged(..)
+            | Message::GeneratorSavePressed
+            | Message::GeneratorSaveResult(_)
+            | Message::PreviewShapeSelected(_) => Some(Tabpage::Experiment),
+
+            Message::UpdateResultsList
+            | Message::ResultButtonPressed(_)
+            | Message::ResultButtonResult(..) => Some(Tabpage::Results),
+
+            Message::DebugCheckboxPressed(_)
+            | Message::SimulateModeCheckboxPressed(_)
+            | Message::ThemeSelected(_)
+            | Message::CalibrationCapturePressed(_)
+            | Message::RestPoseCapturePressed
+            | Message::CalibrationSavePressed
+            | Message::CalibrationClearPressed
+            | Message::ConfigSaveResult(_) => Some(Tabpage::Settings),
+
+            Message::TabSelected(_)
+            | Message::SeqTabSelected(_)
+            | Message::ForceAbortPressed
+            | Message::EmergencyStopResult(_)
+            | Message::PlaybackProgress(_)
+            | Message::PlaybackControlPressed(_)
+            | Message::FontLoaded(_)
+            | Message::DobotConnectionChanged(_)
+            | Message::PollPose
+            | Message::PoseUpdated(..)
+            | Message::ConfigFileLoaded(..)
+            | Message::SequencesListsLoaded(..)
+            | Message::ShapeLibraryLoaded(..)
+            | Message::PreviewPlaneSelected(_)
+            | Message::ResultsListsUpdated(..)
+            | Message::JournalLoaded(..)
+            | Message::PlansListLoaded(..)
+            | Message::ProtocolsListLoaded(..)
+            | Message::JournalWritten(_)
+            | Message::Stamped(..) => None,
+        }
+    }
 }
 
 impl IcedDobotController {
@@ -142,6 +336,7 @@ impl IcedDobotController {
     }
 
     fn new() -> (Self, Task<Message>) {
+        let (dobot_connection, dobot_receivers) = DobotConnection::new();
         (
             Self {
                 is_title_font_ok   : false,
@@ -149,44 +344,72 @@ impl IcedDobotController {
                 active_main_tab    : Tabpage::Sequencer,
                 active_sequence_tab: SequencerTabpage::Sequencer,
 
-                active_sequencer_sequence : Position::default(),
-                active_sequencer_sequences: Vec::new(),
-                sequences_paths           : Vec::new(),
-                active_seq_path_input     : "".to_string(),
-                active_sequences          : Vec::new(),
+                sequencer       : SequencerModel::new(),
+                sequences_paths : Vec::new(),
+                active_sequences: None,
+                jog_space       : JogSpace::default(),
+                jog_step        : JOG_STEPS[1],
 
                 active_experiment_parameters   : Parameters::new(),
-                active_experiment_instance     : None,
-                active_experiment_state        : ExperimentStage::NotInExperiment,
-                active_experiment_shapes_to_use: Shapes::create_vec_shape_bool(),
-                n_v                            : 0.0,
-                n_a                            : 0.0,
+                experiment                     : None,
+                recovered_experiment           : None,
+                active_experiment_shapes_to_use: Vec::new(),
+                active_randomization           : Randomization::default(),
+                plans_paths                    : Vec::new(),
+                active_plan                    : None,
+                protocols_paths                : Vec::new(),
+                active_protocol                : None,
+                shape_library                  : Vec::new(),
+                shape_generator_inputs         : GeneratorInputs::new(),
+                preview_shape                  : None,
+                preview_plane                  : ShapePlane::default(),
                 error_info                     : None,
                 time_main                      : Instant::now(),
-                is_time_counting               : false,
-                time_start                     : 0,
-                time_end                       : 0,
-                active_max_idx                 : 0,
-                active_idx                     : 0,
 
                 results_paths     : Vec::new(),
                 active_result_item: None,
 
+                dobot_connection,
+                dobot_state     : ConnectionState::Disconnected,
+                live_pose       : None,
+                is_pose_polling : false,
+                motion_handles  : Vec::new(),
+                epoch           : 0,
+
+                playback_control  : None,
+                playback_index    : None,
+                playback_progress : None,
+                playback_iteration: None,
+                is_playback_paused: false,
                 logs            : Vec::new(),
                 active_config   : Config::default(),
                 is_debug_view   : false,
                 is_simulate_mode: false,
                 active_theme    : Theme::KanagawaDragon,
+
+                calibration_points: [None; 3],
             },
             // Font is optional.
-            Task::batch([font::load(
-                include_bytes!("../fonts/porter-sans-inline-block.ttf").as_slice(),
-            )
-            .map(Message::FontLoaded)]),
+            Task::batch([
+                font::load(include_bytes!("../fonts/porter-sans-inline-block.ttf").as_slice())
+                    .map(Message::FontLoaded),
+                Task::run(
+                    dobot_connection_actor(dobot_receivers),
+                    Message::DobotConnectionChanged,
+                ),
+            ]),
         )
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
+        if let Message::Stamped(epoch, message) = message {
+            if epoch != self.epoch {
+                log::info!("Dropped stale {:?}", message);
+                return Task::none();
+            }
+            return self.update(*message);
+        }
+
         if let Message::FontLoaded(fontloaded_result) = message {
             if fontloaded_result.is_err() {
                 log::error!("Failed to load font!");
@@ -219,10 +442,107 @@ impl IcedDobotController {
                     }
                 }
             }
-            return Task::perform(
-                update_dir_lists(self.active_config.sequences_path.clone()),
-                |res| Message::SequencesListsLoaded(res.0, res.1),
-            );
+            self.dobot_connection.connect(self.backend_kind());
+            return Task::batch([
+                Task::perform(
+                    update_dir_lists(self.active_config.sequences_path.clone()),
+                    |res| Message::SequencesListsLoaded(res.0, res.1),
+                ),
+                Task::perform(
+                    load_shape_library(self.active_config.shapes_path.clone()),
+                    |res| Message::ShapeLibraryLoaded(res.0, res.1),
+                ),
+                Task::perform(
+                    load_journal(self.active_config.results_path.clone()),
+                    |res| Message::JournalLoaded(res.0, res.1),
+                ),
+                Task::perform(
+                    update_dir_lists(self.active_config.plans_path.clone()),
+                    |res| Message::PlansListLoaded(res.0, res.1),
+                ),
+                Task::perform(
+                    update_dir_lists(self.active_config.protocols_path.clone()),
+                    |res| Message::ProtocolsListLoaded(res.0, res.1),
+                ),
+            ]);
+        }
+
+        if let Message::DobotConnectionChanged(state) = message {
+            match &state {
+                ConnectionState::Faulted(errmsg) => {
+                    self.log(LT::E, format!("Dobot connection faulted: {}", errmsg))
+                }
+                ConnectionState::Connected => self.log(LT::I, "Dobot connected."),
+                _ => {}
+            }
+            if state != ConnectionState::Connected {
+                self.live_pose = None;
+            }
+            self.dobot_state = state;
+            return Task::none();
+        }
+
+        // Emergency stop works from every tab and stage, see also handle_hotkey.
+        if let Message::ForceAbortPressed = message {
+            self.log(LT::W, "Emergency stop!");
+            self.epoch += 1;
+            for motion_handle in self.motion_handles.drain(..) {
+                motion_handle.abort();
+            }
+            let partial_save = self.abort_experiment("Stopped with the emergency stop.".to_string());
+            self.reset_playback_variable();
+            self.is_busy = false;
+            return Task::batch([
+                Task::perform(
+                    emergency_stop(
+                        self.dobot(),
+                        self.relay(),
+                    ),
+                    Message::EmergencyStopResult,
+                ),
+                partial_save,
+            ]);
+        }
+
+        if let Message::EmergencyStopResult(erropt) = message {
+            if let Some(errmsg) = erropt {
+                self.log_dobot_error("Emergency stop incomplete!", errmsg);
+            } else {
+                self.log(LT::I, "Arm stopped, relay released.");
+            }
+            return Task::none();
+        }
+
+        if let Message::PlaybackProgress(event) = message {
+            return handle_playback_event(self, event);
+        }
+
+        if let Message::PlaybackControlPressed(request) = message {
+            if let Some(control) = &self.playback_control {
+                let _ = control.send(request);
+            }
+            return Task::none();
+        }
+
+        if let Message::PollPose = message {
+            // Skip while the previous request is still queued behind a move.
+            if self.is_pose_polling {
+                return Task::none();
+            }
+            self.is_pose_polling = true;
+            return Task::perform(get_pose(self.dobot()), |(_ok, _err)| {
+                Message::PoseUpdated(_ok, _err)
+            });
+        }
+
+        if let Message::PoseUpdated(pose_opt, erropt) = message {
+            self.is_pose_polling = false;
+            if let Some(errmsg) = erropt {
+                // Not added to the GUI log, a fault already shows up through the connection state.
+                log::warn!("Pose poll failed: {}", errmsg);
+            }
+            self.live_pose = pose_opt;
+            return Task::none();
         }
 
         if let Message::SequencesListsLoaded(seqlistsopt, erropt) = message {
@@ -235,6 +555,28 @@ impl IcedDobotController {
             return Task::none();
         }
 
+        if let Message::ShapeLibraryLoaded(shapesopt, erropt) = message {
+            if let Some(errmsg) = erropt {
+                self.log(LT::E, format!("Some shapes failed to load! {}", errmsg));
+            }
+            if let Some(shapes) = shapesopt {
+                self.log(LT::I, format!("Loaded {} shapes.", shapes.len()));
+                // Selection survives a reload, matched by name.
+                let selected = self
+                    .active_experiment_shapes_to_use
+                    .iter()
+                    .filter(|(_, is_use)| *is_use)
+                    .map(|(shape, _)| shape.name.clone())
+                    .collect::<Vec<String>>();
+                self.active_experiment_shapes_to_use = shapes
+                    .iter()
+                    .map(|shape| (shape.clone(), selected.contains(&shape.name)))
+                    .collect();
+                self.shape_library = shapes;
+            }
+            return Task::none();
+        }
+
         if let Message::ResultsListsUpdated(reslistopt, erropt) = message {
             self.is_busy = false;
             if let Some(errmsg) = erropt {
@@ -243,11 +585,67 @@ impl IcedDobotController {
                     format!("Result paths list failed to load! {}", errmsg),
                 );
             } else {
-                self.results_paths = reslistopt.unwrap()
+                self.results_paths = reslistopt
+                    .unwrap()
+                    .into_iter()
+                    .filter(|path| !path.ends_with(JOURNAL_FILE_NAME))
+                    .collect();
             }
             return Task::none();
         }
 
+        if let Message::JournalLoaded(session_opt, erropt) = message {
+            if let Some(errmsg) = erropt {
+                self.log(LT::E, format!("Session journal failed to load! {}", errmsg));
+            }
+            if let Some(session) = &session_opt {
+                self.log(
+                    LT::W,
+                    format!(
+                        "Unfinished session found, {} of {} trials answered.",
+                        session.answered_count(),
+                        session.trial_count()
+                    ),
+                );
+            }
+            self.recovered_experiment = session_opt;
+            return Task::none();
+        }
+
+        if let Message::PlansListLoaded(planlistopt, erropt) = message {
+            if let Some(errmsg) = erropt {
+                self.log(LT::E, format!("Plans list failed to load! {}", errmsg));
+            } else {
+                let mut plans_paths = planlistopt.unwrap_or_default();
+                plans_paths.sort();
+                self.plans_paths = plans_paths;
+            }
+            return Task::none();
+        }
+
+        if let Message::ProtocolsListLoaded(protocollistopt, erropt) = message {
+            if let Some(errmsg) = erropt {
+                self.log(LT::E, format!("Protocols list failed to load! {}", errmsg));
+            } else {
+                let mut protocols_paths = protocollistopt.unwrap_or_default();
+                protocols_paths.sort();
+                self.protocols_paths = protocols_paths;
+            }
+            return Task::none();
+        }
+
+        if let Message::JournalWritten(erropt) = message {
+            if let Some(errmsg) = erropt {
+                self.log(LT::E, format!("Session journal write failed! {}", errmsg));
+            }
+            return Task::none();
+        }
+
+        if let Message::PreviewPlaneSelected(plane) = message {
+            self.preview_plane = plane;
+            return Task::none();
+        }
+
         if let Message::TabSelected(selected_tab) = message {
             return handle_tabs(self, selected_tab);
         }
@@ -256,555 +654,762 @@ impl IcedDobotController {
             return handle_sequencer_tabs(self, selected_tab);
         }
 
-        match self.active_main_tab {
-            Tabpage::Sequencer => {
+        // Routed by message, results of tasks started on another tab still reach their handler.
+        match message.page() {
+            Some(Tabpage::Sequencer) => {
                 if let Message::DobotGoHome = message {
                     self.is_busy = true;
-                    return Task::perform(go_home(self.dobot()), Message::DobotResult);
+                    return self.track_motion(Task::perform(
+                        go_home(self.dobot()),
+                        Message::DobotResult,
+                    ));
                 } else if let Message::DobotTestConnection = message {
                     self.is_busy = true;
                     return Task::perform(test_connection(self.dobot()), Message::DobotResult);
                 } else if let Message::DobotResult(erropt) = message {
                     if let Some(errmsg) = erropt {
-                        self.log(LT::E, format!("Dobot error: {}", errmsg));
+                        self.log_dobot_error("Dobot error:", errmsg);
                     } else {
                         self.log(LT::I, "Dobot ok");
                     }
                     self.is_busy = false;
                     return Task::none();
-                } else {
                 }
-                match self.active_sequence_tab {
-                    SequencerTabpage::Sequencer => {
-                        match message {
-                            Message::SequenceReorder(event) => {
-                                match event {
-                                    DragEvent::Dropped {
-                                        index,
-                                        target_index,
-                                        drop_position,
-                                    } => match drop_position {
-                                        DropPosition::Swap => {
-                                            if target_index != index {
-                                                self.active_sequencer_sequences
-                                                    .swap(index, target_index);
-                                            }
-                                        }
-                                        DropPosition::Before | DropPosition::After => {
-                                            if target_index != index && target_index != index + 1 {
-                                                let item =
-                                                    self.active_sequencer_sequences.remove(index);
-                                                let insert_idx = if index < target_index {
-                                                    target_index - 1
-                                                } else {
-                                                    target_index
-                                                };
-                                                self.active_sequencer_sequences
-                                                    .insert(insert_idx, item);
-                                            }
-                                        }
-                                    },
-                                    _ => {}
-                                }
-                                Task::none()
-                            }
-                            Message::SequencerMoveToPressed => {
-                                log::info!("Moving to position...");
-
-                                Task::perform(
-                                    perform_moveto(
-                                        self.dobot(),
-                                        self.active_sequencer_sequence.clone(),
-                                    ),
-                                    Message::SequencerPerformResult,
-                                )
-                            }
-                            Message::SequencerAddToSequencesPressed => {
-                                match self
-                                    .active_sequencer_sequence
-                                    .clone()
-                                    .into_iter()
-                                    .any(|item: &PositionItem| item.in_string.is_empty())
-                                {
-                                    true => {
-                                        self.log(
-                                            LT::E,
-                                            "Cannot add to queue, some input(s) are empty",
-                                        );
-                                    }
-                                    false => {
-                                        self.active_sequencer_sequences
-                                            .push(self.active_sequencer_sequence.clone());
-                                    }
+                match message {
+                    Message::SequenceReorder(event) => {
+                        if let DragEvent::Dropped {
+                            index,
+                            target_index,
+                            drop_position,
+                        } = event
+                        {
+                            match drop_position {
+                                DropPosition::Swap => self.sequencer.swap_steps(index, target_index),
+                                DropPosition::Before | DropPosition::After => {
+                                    self.sequencer.move_step(index, target_index)
                                 }
-                                Task::none()
                             }
-                            Message::SequencerInputUpdated(coordtype, input_val) => {
-                                if input_val.is_empty() {
-                                    match coordtype {
-                                        Coordinate::X => self
-                                            .active_sequencer_sequence
-                                            .x
-                                            .update_s_f32(input_val, 0.0),
-                                        Coordinate::Y => self
-                                            .active_sequencer_sequence
-                                            .y
-                                            .update_s_f32(input_val, 0.0),
-                                        Coordinate::Z => self
-                                            .active_sequencer_sequence
-                                            .z
-                                            .update_s_f32(input_val, 0.0),
-                                        Coordinate::R => self
-                                            .active_sequencer_sequence
-                                            .r
-                                            .update_s_f32(input_val, 0.0),
-                                        Coordinate::V => self
-                                            .active_sequencer_sequence
-                                            .v
-                                            .update_s_f32(input_val, 0.0),
-                                        Coordinate::A => self
-                                            .active_sequencer_sequence
-                                            .a
-                                            .update_s_f32(input_val, 0.0),
-                                    }
-                                } else {
-                                    match input_val.parse::<f32>() {
-                                        Ok(value) => match coordtype {
-                                            Coordinate::X => self
-                                                .active_sequencer_sequence
-                                                .x
-                                                .update_s_f32(input_val, value),
-                                            Coordinate::Y => self
-                                                .active_sequencer_sequence
-                                                .y
-                                                .update_s_f32(input_val, value),
-                                            Coordinate::Z => self
-                                                .active_sequencer_sequence
-                                                .z
-                                                .update_s_f32(input_val, value),
-                                            Coordinate::R => self
-                                                .active_sequencer_sequence
-                                                .r
-                                                .update_s_f32(input_val, value),
-                                            Coordinate::V => self
-                                                .active_sequencer_sequence
-                                                .v
-                                                .update_s_f32(input_val, value),
-                                            Coordinate::A => self
-                                                .active_sequencer_sequence
-                                                .a
-                                                .update_s_f32(input_val, value),
-                                        },
-                                        Err(_) => {
-                                            self.log(
-                                                LT::W,
-                                                format!(
-                                                    "Input {} not parseable to f32",
-                                                    coordtype.to_string()
-                                                ),
-                                            );
-                                        }
-                                    }
+                        }
+                        Task::none()
+                    }
+                    Message::SequencerMoveToPressed => {
+                        let position = self.sequencer.input.clone();
+                        let violations = self
+                            .active_config
+                            .safety_envelope
+                            .check_sequence(&[position], self.active_config.jump_height);
+                        if self.is_outside_envelope(violations) {
+                            return Task::none();
+                        }
+                        log::info!("Moving to position...");
+
+                        self.is_busy = true;
+                        self.track_motion(Task::perform(
+                            perform_moveto(
+                                self.dobot(),
+                                self.sequencer.input.clone(),
+                            ),
+                            Message::SequencerPerformResult,
+                        ))
+                    }
+                    Message::CapturePosePressed => {
+                        if let Some(pose) = self.live_pose {
+                            self.sequencer.fill_input_from_pose(pose);
+                            self.log(LT::I, "Captured current pose into input.");
+                        } else {
+                            self.log(LT::W, "No live pose to capture.");
+                        }
+                        Task::none()
+                    }
+                    Message::JogPressed(axis, direction) => {
+                        // Keys keep firing while busy, only one step runs at a time.
+                        if self.is_busy {
+                            return Task::none();
+                        }
+                        let Some(pose) = self.live_pose else {
+                            self.log(LT::W, "No live pose to jog from.");
+                            return Task::none();
+                        };
+                        let delta = direction * self.jog_step;
+                        let target = match self.jog_space {
+                            JogSpace::Cartesian => {
+                                let mut xyzr = [pose.x, pose.y, pose.z, pose.r];
+                                xyzr[axis.index()] += delta;
+                                let [x, y, z, r] =
+                                    xyzr.map(|value| (value * 100.0).round() / 100.0);
+                                let mut position = Position::position(
+                                    x,
+                                    y,
+                                    z,
+                                    r,
+                                    JOG_VELOCITY,
+                                    JOG_ACCELERATION,
+                                );
+                                // Straight line, so a small step doesn't swing the arm around.
+                                position.mode = MotionMode::MovL;
+                                let violations = self
+                                    .active_config
+                                    .safety_envelope
+                                    .check_sequence(
+                                        &[position.clone()],
+                                        self.active_config.jump_height,
+                                    );
+                                if self.is_outside_envelope(violations) {
+                                    return Task::none();
                                 }
-                                Task::none()
-                            }
-                            Message::RemoveASequencePressed(index) => {
-                                let _ = self.active_sequencer_sequences.remove(index);
-                                Task::none()
-                            }
-                            Message::PasteSequencePressed(index) => {
-                                self.active_sequencer_sequence =
-                                    self.active_sequencer_sequences[index].clone();
-                                Task::none()
-                            }
-                            Message::ClearSequences => {
-                                self.active_sequencer_sequences = Vec::new();
-                                Task::none()
-                            }
-                            Message::ClearSeqInput => {
-                                self.active_sequencer_sequence = Position::default();
-                                Task::none()
-                            }
-                            Message::SeqFilenameInputUpdated(intext) => {
-                                self.active_seq_path_input = intext;
-                                Task::none()
+                                JogTarget::Pose(position)
                             }
-                            Message::SeqSaveFilePressed => {
-                                if self.active_sequencer_sequences.is_empty() {
-                                    self.log(LT::W, "Sequences empty, not saving...");
-                                    Task::none()
-                                } else {
-                                    self.is_busy = true;
-                                    log::info!("Saving as {}", self.active_seq_path_input);
-                                    match serde_json::to_string_pretty(&NamedSequence {
-                                        name: self.active_seq_path_input.clone(),
-                                        sequences: self.active_sequencer_sequences.clone(),
-                                    }) {
-                                        Ok(json_content) => Task::perform(
-                                            save_str_to_json(
-                                                self.active_config.sequences_path.clone(),
-                                                json_content.clone(),
-                                                self.active_seq_path_input.clone(),
-                                            ),
-                                            Message::SeqSaveFileResult,
-                                        ),
-                                        Err(errmsg) => {
-                                            self.log(
-                                                LT::E,
-                                                format!("Error while saving: {}", errmsg),
-                                            );
-                                            Task::none()
-                                        }
-                                    }
+                            JogSpace::Joint => {
+                                let Some(mut joints) = pose.joints else {
+                                    self.log(LT::W, "Arm does not report joint angles.");
+                                    return Task::none();
+                                };
+                                joints[axis.index()] += delta;
+                                // Checked at the pose the joints end up in,
+                                // with the model error taken off the envelope.
+                                let pose = forward_kinematics(joints);
+                                let position = Position::position(
+                                    pose.x,
+                                    pose.y,
+                                    pose.z,
+                                    pose.r,
+                                    JOG_VELOCITY,
+                                    JOG_ACCELERATION,
+                                );
+                                let violations = self
+                                    .active_config
+                                    .safety_envelope
+                                    .shrunk(JOINT_JOG_MARGIN)
+                                    .check_sequence(&[position], self.active_config.jump_height);
+                                if self.is_outside_envelope(violations) {
+                                    return Task::none();
                                 }
+                                JogTarget::Joints(joints)
                             }
-                            Message::SeqSaveFileResult(erroption) => {
-                                self.is_busy = false;
-                                if let Some(errmsg) = erroption {
+                        };
+                        self.is_busy = true;
+                        self.track_motion(Task::perform(
+                            jog_dobot(self.dobot(), target),
+                            |(pose, erropt)| Message::JogResult(pose, erropt),
+                        ))
+                    }
+                    Message::JogSpaceSelected(jog_space) => {
+                        self.jog_space = jog_space;
+                        Task::none()
+                    }
+                    Message::JogStepSelected(jog_step) => {
+                        self.jog_step = jog_step;
+                        Task::none()
+                    }
+                    Message::JogResult(pose_opt, erropt) => {
+                        self.is_busy = false;
+                        if let Some(errmsg) = erropt {
+                            self.log_dobot_error("Jog failed:", errmsg);
+                        }
+                        if let Some(pose) = pose_opt {
+                            self.live_pose = Some(pose);
+                            self.sequencer.fill_input_from_pose(pose);
+                        }
+                        Task::none()
+                    }
+                    Message::SequencerAddToSequencesPressed => {
+                        if let Err(errmsg) = self.sequencer.add_input_move() {
+                            self.log(LT::E, errmsg);
+                        }
+                        Task::none()
+                    }
+                    Message::SequencerAddStepPressed(step) => {
+                        self.sequencer.steps.push(step);
+                        Task::none()
+                    }
+                    Message::SeqWaitInputUpdated(intext) => {
+                        if let Err(errmsg) = self.sequencer.set_wait_input(intext) {
+                            self.log(LT::W, errmsg);
+                        }
+                        Task::none()
+                    }
+                    Message::SequencerInputUpdated(coordtype, input_val) => {
+                        if let Err(errmsg) = self.sequencer.set_coordinate(coordtype, input_val) {
+                            self.log(LT::W, errmsg);
+                        }
+                        Task::none()
+                    }
+                    Message::SequencerModeSelected(mode) => {
+                        self.sequencer.input.mode = mode;
+                        Task::none()
+                    }
+                    Message::RemoveASequencePressed(index) => {
+                        self.sequencer.remove_step(index);
+                        Task::none()
+                    }
+                    Message::PasteSequencePressed(index) => {
+                        self.sequencer.paste_step(index);
+                        Task::none()
+                    }
+                    Message::ClearSequences => {
+                        self.sequencer.steps = Vec::new();
+                        Task::none()
+                    }
+                    Message::ClearSeqInput => {
+                        self.sequencer.input = Position::default();
+                        Task::none()
+                    }
+                    Message::SeqFilenameInputUpdated(intext) => {
+                        self.sequencer.name_input = intext;
+                        Task::none()
+                    }
+                    Message::SeqRepeatInputUpdated(intext) => {
+                        if let Err(errmsg) = self.sequencer.set_repeat_input(intext) {
+                            self.log(LT::W, errmsg);
+                        }
+                        Task::none()
+                    }
+                    Message::SeqDwellInputUpdated(intext) => {
+                        if let Err(errmsg) = self.sequencer.set_dwell_input(intext) {
+                            self.log(LT::W, errmsg);
+                        }
+                        Task::none()
+                    }
+                    Message::SeqLoopToggled(is_looping) => {
+                        self.sequencer.is_looping = is_looping;
+                        Task::none()
+                    }
+                    Message::SeqSaveFilePressed => {
+                        if self.sequencer.steps.is_empty() {
+                            self.log(LT::W, "Sequences empty, not saving...");
+                            Task::none()
+                        } else {
+                            self.is_busy = true;
+                            log::info!("Saving as {}", self.sequencer.name_input);
+                            match serde_json::to_string_pretty(
+                                &self.sequencer.named_sequence(),
+                            ) {
+                                Ok(json_content) => Task::perform(
+                                    save_str_to_json(
+                                        self.active_config.sequences_path.clone(),
+                                        json_content.clone(),
+                                        self.sequencer.name_input.clone(),
+                                    ),
+                                    Message::SeqSaveFileResult,
+                                ),
+                                Err(errmsg) => {
                                     self.log(
                                         LT::E,
-                                        format!(
-                                            "Failed to save {} to file. {}",
-                                            self.active_seq_path_input, errmsg
-                                        ),
+                                        format!("Error while saving: {}", errmsg),
                                     );
                                     Task::none()
-                                } else {
-                                    self.log(LT::I, "Save successful.");
-                                    Task::perform(
-                                        update_dir_lists(self.active_config.sequences_path.clone()),
-                                        |(_ok, _err)| Message::SequencesListsLoaded(_ok, _err),
-                                    )
-                                }
-                            }
-                            Message::SequencesListsLoaded(ok, err) => {
-                                if let Some(errmsg) = err {
-                                    self.log(LT::E, errmsg);
-                                } else {
-                                    self.sequences_paths = ok.unwrap();
-                                }
-                                Task::none()
-                            }
-                            Message::SequencerPerform => {
-                                self.is_busy = true;
-                                Task::perform(
-                                    perform_sequences(
-                                        DobotPath {
-                                            dobotpath: self.active_config.dobot_path.clone(),
-                                        },
-                                        self.active_sequencer_sequences.clone(),
-                                    ),
-                                    Message::SequencerPerformResult,
-                                )
-                            }
-                            Message::SequencerPerformResult(erropt) => {
-                                if let Some(errmsg) = erropt {
-                                    self.log(LT::E, format!("Error running sequence: {}", errmsg));
                                 }
-                                self.is_busy = false;
-                                Task::none()
                             }
-                            _ => unimplemented!(),
                         }
                     }
-                    SequencerTabpage::Sequences => match message {
-                        Message::SeqLoadFilePressed(index, do_perform) => {
-                            self.is_busy = true;
+                    Message::SeqSaveFileResult(erroption) => {
+                        self.is_busy = false;
+                        if let Some(errmsg) = erroption {
+                            self.log(
+                                LT::E,
+                                format!(
+                                    "Failed to save {} to file. {}",
+                                    self.sequencer.name_input, errmsg
+                                ),
+                            );
+                            Task::none()
+                        } else {
+                            self.log(LT::I, "Save successful.");
                             Task::perform(
-                                load_file_content(self.sequences_paths[index].clone()),
-                                move |(_ok, _err)| {
-                                    Message::SeqLoadFileResult(_ok, _err, do_perform)
-                                },
+                                update_dir_lists(self.active_config.sequences_path.clone()),
+                                |(_ok, _err)| Message::SequencesListsLoaded(_ok, _err),
                             )
                         }
-                        Message::SeqLoadFileResult(ok, err, do_perform) => {
-                            if let Some(errmsg) = err {
-                                self.is_busy = false;
-                                self.log(LT::E, format!("Error loading sequence file: {}", errmsg));
-                                return Task::none();
-                            } else {
-                                match serde_json::from_str::<NamedSequence>(ok.unwrap().as_str()) {
-                                    Ok(some_json) => {
-                                        if do_perform {
-                                            self.active_sequences = some_json.sequences.clone();
-                                            self.log(LT::I, format!("Running {}", some_json.name));
-                                        } else {
-                                            self.active_sequencer_sequences =
-                                                some_json.sequences.clone();
-                                            self.log(
-                                                LT::I,
-                                                format!("Loaded {} into sequencer", some_json.name),
-                                            );
-                                        }
-                                    }
-                                    Err(some_err) => {
-                                        self.is_busy = false;
+                    }
+                    Message::SequencerPerform => {
+                        let named_sequence = self.sequencer.named_sequence();
+                        let violations = self
+                            .active_config
+                            .safety_envelope
+                            .check_steps(&named_sequence.steps, self.active_config.jump_height);
+                        if self.is_outside_envelope(violations) {
+                            return Task::none();
+                        }
+                        self.start_playback(named_sequence)
+                    }
+                    Message::SequencerPerformResult(erropt) => {
+                        if let Some(errmsg) = erropt {
+                            self.log_dobot_error("Error running sequence:", errmsg);
+                        }
+                        self.is_busy = false;
+                        Task::none()
+                    }
+                    Message::SeqLoadFilePressed(index, do_perform) => {
+                        self.is_busy = true;
+                        Task::perform(
+                            load_file_content(self.sequences_paths[index].clone()),
+                            move |(_ok, _err)| {
+                                Message::SeqLoadFileResult(_ok, _err, do_perform)
+                            },
+                        )
+                    }
+                    Message::SeqLoadFileResult(ok, err, do_perform) => {
+                        if let Some(errmsg) = err {
+                            self.is_busy = false;
+                            self.log(LT::E, format!("Error loading sequence file: {}", errmsg));
+                            return Task::none();
+                        } else {
+                            match serde_json::from_str::<NamedSequence>(ok.unwrap().as_str()) {
+                                Ok(some_json) => {
+                                    if do_perform {
+                                        self.log(
+                                            LT::I,
+                                            format!(
+                                                "Running {} ({})",
+                                                some_json.name,
+                                                if some_json.is_infinite_loop {
+                                                    "loop".to_string()
+                                                } else {
+                                                    format!("x{}", some_json.repeat_count)
+                                                }
+                                            ),
+                                        );
+                                        self.active_sequences = Some(some_json);
+                                    } else {
                                         self.log(
-                                            LT::E,
-                                            format!("Error parsing sequence data: {}", some_err),
+                                            LT::I,
+                                            format!("Loaded {} into sequencer", some_json.name),
                                         );
-                                        return Task::none();
+                                        self.sequencer.load(some_json);
                                     }
                                 }
+                                Err(some_err) => {
+                                    self.is_busy = false;
+                                    self.log(
+                                        LT::E,
+                                        format!("Error parsing sequence data: {}", some_err),
+                                    );
+                                    return Task::none();
+                                }
                             }
-                            if do_perform {
-                                Task::perform(
-                                    perform_sequences(
-                                        DobotPath {
-                                            dobotpath: self.active_config.dobot_path.clone(),
-                                        },
-                                        self.active_sequences.clone(),
-                                    ),
-                                    Message::PerformSequenceResult,
-                                )
-                            } else {
+                        }
+                        let named_sequence = self.active_sequences.clone();
+                        if let (true, Some(named_sequence)) = (do_perform, named_sequence) {
+                            let violations = self
+                                .active_config
+                                .safety_envelope
+                                .check_steps(&named_sequence.steps, self.active_config.jump_height);
+                            if self.is_outside_envelope(violations) {
                                 self.is_busy = false;
                                 Task::none()
+                            } else {
+                                self.start_playback(named_sequence)
                             }
-                        }
-                        Message::PerformSequenceResult(erropt) => {
+                        } else {
                             self.is_busy = false;
-                            if let Some(errmsg) = erropt {
-                                self.log(LT::E, format!("Performing sequences failed! {}", errmsg));
-                            }
                             Task::none()
                         }
-                        _ => unimplemented!(),
-                    },
+                    }
+                    message => self.ignore_message(message),
                 }
             }
-            Tabpage::Experiment => match self.active_experiment_state {
-                ExperimentStage::NotInExperiment => match message {
+            Some(Tabpage::Experiment) => match self.experiment {
+                None => match message {
                     Message::SelectedShapesChanged(selected_shape) => {
                         let _ = self
                             .active_experiment_shapes_to_use
                             .iter_mut()
-                            .filter(|(shape, _)| selected_shape == *shape)
+                            .filter(|(shape, _)| selected_shape == shape.name)
                             .map(|(_, is_use)| *is_use = !*is_use)
                             .collect::<Vec<_>>();
                         Task::none()
                     }
-                    Message::ParameterInputChanged(param_type, invalue) => {
+                    Message::RandomizationStrategySelected(strategy) => {
+                        self.active_randomization.strategy = strategy;
+                        Task::none()
+                    }
+                    Message::LatinSquareRowChanged(invalue) => {
                         if invalue.is_empty() {
-                            let _ = self
-                                .active_experiment_parameters
-                                .iter_mut()
-                                .filter(|param| param.parameter_type == param_type)
-                                .map(|item| {
-                                    item.value = invalue.clone();
-                                    ()
-                                })
-                                .collect::<Vec<()>>();
+                            self.active_randomization.latin_square_row = 0;
+                        } else if let Ok(row) = invalue.parse::<usize>() {
+                            self.active_randomization.latin_square_row = row;
                         } else {
-                            if let Some(_) = self
-                                .active_experiment_parameters
-                                .iter_mut()
-                                .filter(|param| param.parameter_type == param_type)
-                                .map(|item| {
-                                    let mut ret_opt = None;
-                                    match param_type {
-                                        ParameterType::SubjectName
-                                        | ParameterType::ModulationType
-                                        | ParameterType::CarrierType => {
-                                            item.value = invalue.clone();
-                                        }
-                                        _ => match invalue.parse::<u32>() {
-                                            Ok(_) => item.value = invalue.clone(),
-                                            Err(errmsg) => ret_opt = Some(errmsg),
-                                        },
-                                    }
-                                    ret_opt
-                                })
-                                .collect::<Vec<Option<ParseIntError>>>()
-                                .first()
-                                .unwrap()
-                            {
-                                self.log(LT::W, format!("Not parseable as u32."));
-                            };
+                            self.log(LT::W, "Latin square row not parseable as usize.");
                         }
                         Task::none()
                     }
-
-                    Message::BeginExperimentPressed => {
-                        let empty_check = self
-                            .active_experiment_parameters
-                            .into_iter()
-                            .filter(|item| item.value.is_empty())
-                            .map(|item| item.clone())
-                            .collect::<Vec<_>>()
-                            .clone();
-
-                        if empty_check.len() != 0 {
-                            for empty_params in empty_check {
+                    Message::NoRepeatToggled(no_immediate_repeat) => {
+                        self.active_randomization.no_immediate_repeat = no_immediate_repeat;
+                        Task::none()
+                    }
+                    Message::PlanExportPressed => match ExperimentPlan::generate(
+                        &self.active_experiment_parameters,
+                        &self.active_experiment_shapes_to_use,
+                        self.active_randomization,
+                        self.active_protocol.as_ref(),
+                        &mut rand::thread_rng(),
+                    ) {
+                        Ok(plan) => match serde_json::to_string_pretty(&plan) {
+                            Ok(json_content) => {
                                 self.log(
-                                    LT::W,
+                                    LT::I,
                                     format!(
-                                        "{} is empty",
-                                        empty_params.parameter_type.show_title(false)
+                                        "Exporting {} trials as {}.",
+                                        plan.list_of_shapes.len(),
+                                        plan.name
                                     ),
                                 );
-                                self.error_info = Some(format!(
-                                    "{} is empty",
-                                    empty_params.parameter_type.show_title(false)
-                                ));
+                                self.is_busy = true;
+                                Task::perform(
+                                    save_str_to_json(
+                                        self.active_config.plans_path.clone(),
+                                        json_content,
+                                        plan.name,
+                                    ),
+                                    Message::PlanExportResult,
+                                )
                             }
-                        } else if self
-                            .active_experiment_shapes_to_use
-                            .iter()
-                            .all(|(_, in_use)| *in_use == false)
-                        {
-                            self.log(LT::W, "No shape selection made!");
-                            self.error_info = Some("Please select some shapes!".to_string());
+                            Err(errmsg) => {
+                                self.log(LT::E, format!("Error while exporting plan: {}", errmsg));
+                                Task::none()
+                            }
+                        },
+                        Err(problems) => {
+                            for problem in problems {
+                                self.log(LT::W, problem.clone());
+                                self.error_info = Some(problem);
+                            }
+                            Task::none()
+                        }
+                    },
+                    Message::PlanExportResult(erroption) => {
+                        self.is_busy = false;
+                        if let Some(errmsg) = erroption {
+                            self.log(LT::E, format!("Failed to export plan. {}", errmsg));
+                            Task::none()
                         } else {
-                            self.log(LT::I, "Beginning experiment!");
-                            self.error_info = Some("".to_string());
-                            self.is_busy = true;
-                            self.n_v = self
-                                .active_experiment_parameters
-                                .speed
-                                .value
-                                .parse::<f32>()
-                                .unwrap();
-                          
-                            self.n_a = self
-                                .active_experiment_parameters
-                                .acceleration
-                                .value
-                                .parse::<f32>()
-                                .unwrap();
-                          
-                            self.active_experiment_instance =
-                                Some(create_experiment_instance(&self));
-
-                            self.active_max_idx = self
-                                .active_experiment_parameters
-                                .test_count
-                                .value
-                                .parse::<u32>()
-                                .unwrap() - 1;
-                          
-                            self.active_idx = 0;
-                            self.active_experiment_state = ExperimentStage::BeginTiming;
-                            return Task::perform(_wait_n_ms(50), |_| Message::GoToNextStage);
+                            self.log(LT::I, "Plan exported.");
+                            Task::perform(
+                                update_dir_lists(self.active_config.plans_path.clone()),
+                                |res| Message::PlansListLoaded(res.0, res.1),
+                            )
+                        }
+                    }
+                    Message::PlanSelected(plan_path) => {
+                        self.is_busy = true;
+                        Task::perform(load_file_content(plan_path), |(_ok, _err)| {
+                            Message::PlanLoaded(_ok, _err)
+                        })
+                    }
+                    Message::PlanLoaded(content_opt, erropt) => {
+                        self.is_busy = false;
+                        if let Some(errmsg) = erropt {
+                            self.log(LT::E, format!("Failed to load plan! {}", errmsg));
+                            return Task::none();
+                        }
+                        let content = content_opt.unwrap_or_default();
+                        match serde_json::from_str::<ExperimentPlan>(&content) {
+                            Ok(plan) => {
+                                self.log(
+                                    LT::I,
+                                    format!(
+                                        "Plan {} imported, {} trials.",
+                                        plan.name,
+                                        plan.list_of_shapes.len()
+                                    ),
+                                );
+                                // Shown in the inputs, the plan overrides them at the start.
+                                self.active_experiment_parameters.test_count.value =
+                                    plan.list_of_shapes.len().to_string();
+                                self.active_experiment_parameters.practice_count.value =
+                                    plan.practice_shapes.len().to_string();
+                                self.active_experiment_parameters.seed.value =
+                                    plan.seed.map(|seed| seed.to_string()).unwrap_or_default();
+                                self.active_plan = Some(plan);
+                            }
+                            Err(errmsg) => {
+                                self.log(LT::E, format!("Could not parse plan json! {}", errmsg))
+                            }
+                        }
+                        Task::none()
+                    }
+                    Message::PlanClearPressed => {
+                        self.active_plan = None;
+                        self.log(LT::I, "Plan cleared, trial order is drawn again.");
+                        Task::none()
+                    }
+                    Message::ProtocolSelected(protocol_path) => {
+                        self.is_busy = true;
+                        Task::perform(load_file_content(protocol_path), |(_ok, _err)| {
+                            Message::ProtocolLoaded(_ok, _err)
+                        })
+                    }
+                    Message::ProtocolLoaded(content_opt, erropt) => {
+                        self.is_busy = false;
+                        if let Some(errmsg) = erropt {
+                            self.log(LT::E, format!("Failed to load protocol! {}", errmsg));
+                            return Task::none();
+                        }
+                        let content = content_opt.unwrap_or_default();
+                        match serde_json::from_str::<ExperimentProtocol>(&content) {
+                            Ok(protocol) => {
+                                match &protocol.staircase {
+                                    Some(staircase) => self.log(
+                                        LT::I,
+                                        format!(
+                                            "Protocol {} loaded, staircase {} on {} from {}, {} reversals.",
+                                            protocol.name,
+                                            staircase.rule,
+                                            staircase.parameter.show_title_en(),
+                                            staircase.start,
+                                            staircase.reversals
+                                        ),
+                                    ),
+                                    None => self.log(
+                                        LT::I,
+                                        format!(
+                                            "Protocol {} loaded, {} conditions {}.",
+                                            protocol.name,
+                                            protocol.conditions.len(),
+                                            protocol.scheduling
+                                        ),
+                                    ),
+                                }
+                                for condition in &protocol.conditions {
+                                    self.log(LT::I, format!("Condition {}", condition));
+                                }
+                                self.active_protocol = Some(protocol);
+                            }
+                            Err(errmsg) => {
+                                self.log(LT::E, format!("Could not parse protocol json! {}", errmsg))
+                            }
+                        }
+                        Task::none()
+                    }
+                    Message::ProtocolClearPressed => {
+                        self.active_protocol = None;
+                        self.log(LT::I, "Protocol cleared, the entered parameters are used.");
+                        Task::none()
+                    }
+                    Message::ParameterInputChanged(param_type, invalue) => {
+                        if let Err(errmsg) =
+                            self.active_experiment_parameters.update(param_type, invalue)
+                        {
+                            self.log(LT::W, errmsg);
+                        }
+                        Task::none()
+                    }
+
+                    Message::BeginExperimentPressed => {
+                        if self.recovered_experiment.is_some() {
+                            self.log(LT::W, "Resume, save or discard the unfinished session first.");
+                            return Task::none();
+                        }
+                        let session = match self.active_plan.clone() {
+                            Some(plan) => ExperimentSession::from_plan(
+                                self.active_experiment_parameters.clone(),
+                                plan,
+                            ),
+                            None => ExperimentSession::begin(
+                                self.active_experiment_parameters.clone(),
+                                &self.active_experiment_shapes_to_use,
+                                self.active_randomization,
+                                self.active_protocol.as_ref(),
+                                &mut rand::thread_rng(),
+                            ),
+                        };
+                        match session {
+                            Err(problems) => {
+                                for problem in problems {
+                                    self.log(LT::W, problem.clone());
+                                    self.error_info = Some(problem);
+                                }
+                            }
+                            Ok(session)
+                                if self.are_shapes_outside_envelope(
+                                    &session.instance.shapes_selection,
+                                    &session.possible_motions(),
+                                ) =>
+                            {
+                                self.error_info =
+                                    Some("Shape motion is outside the safety envelope!".to_string());
+                            }
+                            Ok(session) => {
+                                self.log(LT::I, "Beginning experiment!");
+                                self.error_info = Some("".to_string());
+                                self.is_busy = true;
+                                let header = session.journal_header();
+                                self.experiment = Some(session);
+                                return Task::perform(
+                                    journal_begin(self.active_config.results_path.clone(), header),
+                                    Message::JournalWritten,
+                                )
+                                .chain(self.stamp(Task::perform(_wait_n_ms(50), |_| {
+                                    Message::GoToNextStage
+                                })));
+                            }
                         }
                         Task::none()
                     }
+                    Message::GeneratorKindSelected(kind) => {
+                        self.shape_generator_inputs.kind = kind;
+                        Task::none()
+                    }
+                    Message::GeneratorInputChanged(field, invalue) => {
+                        self.shape_generator_inputs.set(field, invalue);
+                        Task::none()
+                    }
+                    Message::GeneratorSavePressed => match self.shape_generator_inputs.build() {
+                        Ok(shape) => match serde_json::to_string_pretty(&shape) {
+                            Ok(json_content) => {
+                                self.is_busy = true;
+                                Task::perform(
+                                    save_str_to_json(
+                                        self.active_config.shapes_path.clone(),
+                                        json_content,
+                                        shape.name,
+                                    ),
+                                    Message::GeneratorSaveResult,
+                                )
+                            }
+                            Err(errmsg) => {
+                                self.log(LT::E, format!("Error while saving shape: {}", errmsg));
+                                Task::none()
+                            }
+                        },
+                        Err(errmsg) => {
+                            self.log(LT::W, format!("Cannot generate shape: {}", errmsg));
+                            Task::none()
+                        }
+                    },
+                    Message::PreviewShapeSelected(shape_name) => {
+                        self.preview_shape = shape_name;
+                        Task::none()
+                    }
+                    Message::GeneratorSaveResult(erroption) => {
+                        self.is_busy = false;
+                        if let Some(errmsg) = erroption {
+                            self.log(LT::E, format!("Failed to save shape. {}", errmsg));
+                            Task::none()
+                        } else {
+                            self.log(LT::I, "Shape saved to library.");
+                            Task::perform(
+                                load_shape_library(self.active_config.shapes_path.clone()),
+                                |res| Message::ShapeLibraryLoaded(res.0, res.1),
+                            )
+                        }
+                    }
                     Message::ResultsProcessed(result_option, erroption) => {
+                        let is_saved = erroption.is_none();
                         if let Some(errmsg) = erroption {
                             self.log(LT::E, format!("Error while saving results! {}", errmsg));
                         } else {
                             self.active_result_item = result_option;
                             self.log(LT::I, "Saved successfully.");
                         }
-                        Task::perform(
+                        let mut tasks = vec![Task::perform(
                             update_dir_lists(self.active_config.clone().results_path),
                             |(_ok, _err)| Message::ResultsListsUpdated(_ok, _err),
-                        )
+                        )];
+                        // The journal is only needed until the results are on disk.
+                        if is_saved {
+                            tasks.push(Task::perform(
+                                clear_journal(self.active_config.results_path.clone()),
+                                Message::JournalWritten,
+                            ));
+                        } else {
+                            tasks.push(self.offer_recovery());
+                        }
+                        Task::batch(tasks)
+                    }
+                    Message::RecoveryResumePressed => {
+                        let Some(session) = self.recovered_experiment.take() else {
+                            return Task::none();
+                        };
+                        if session.is_finished() {
+                            self.log(LT::W, "Every trial is answered, nothing left to resume.");
+                            self.recovered_experiment = Some(session);
+                            return Task::none();
+                        }
+                        if self.are_shapes_outside_envelope(
+                            &session.instance.shapes_selection,
+                            &session.possible_motions(),
+                        ) {
+                            self.error_info =
+                                Some("Shape motion is outside the safety envelope!".to_string());
+                            self.recovered_experiment = Some(session);
+                            return Task::none();
+                        }
+                        self.log(
+                            LT::I,
+                            format!("Resuming experiment at trial {}.", session.answered_count() + 1),
+                        );
+                        self.error_info = Some("".to_string());
+                        self.is_busy = true;
+                        self.experiment = Some(session);
+                        self.stamp(Task::perform(_wait_n_ms(50), |_| Message::GoToNextStage))
                     }
-                    _ => unimplemented!(),
-                },
-                ExperimentStage::BeginTiming => match message {
-                    Message::GoToNextStage => {
-                        self.is_time_counting = false;
-                        self.active_experiment_state = ExperimentStage::Preparation;
-                        Task::perform(_wait_n_ms(50), |_| Message::GoToNextStage)
+                    Message::RecoverySavePartialPressed => {
+                        let Some(session) = self.recovered_experiment.take() else {
+                            return Task::none();
+                        };
+                        self.save_partial_results(
+                            session,
+                            "Interrupted, recovered from the session journal.".to_string(),
+                        )
                     }
-                    Message::ForceAbortPressed => {
-                        self.log(LT::W, "Force abort button pressed.");
-                        self.reset_experiment_variable();
-                        Task::none()
+                    Message::RecoveryDiscardPressed => {
+                        self.recovered_experiment = None;
+                        self.log(LT::W, "Unfinished session discarded.");
+                        Task::perform(
+                            clear_journal(self.active_config.results_path.clone()),
+                            Message::JournalWritten,
+                        )
                     }
-                    _ => unimplemented!(),
+                    message => self.ignore_message(message),
                 },
-                ExperimentStage::Preparation => match message {
-                    Message::GoToNextStage => {
-                        let cur_exp_item = self.active_experiment_instance.clone().unwrap();
-
-                        if self.is_simulate_mode {
-                            Task::perform(simulate_drawing(), Message::DrawingResult)
-                        } else {
-                            Task::perform(
-                                draw_shape(
-                                    self.dobot(),
-                                    self.relay(),
-                                    cur_exp_item.list_of_shapes[cur_exp_item.experiment_index],
-                                    self.n_v,
-                                    self.n_a,
-                                ),
-                                Message::DrawingResult,
-                            )
-                        }
-                    }
+                Some(_) => match message {
+                    Message::GoToNextStage => self.step_experiment(|session| session.advance()),
                     Message::DrawingResult(erropt) => {
                         if let Some(errmsg) = erropt {
-                            self.log(
-                                LT::E,
-                                format!(
-                                    "Error while in experiment, aborting experiment... {}",
-                                    errmsg
-                                ),
+                            let reason = format!("Arm error: {}", errmsg);
+                            self.log_dobot_error(
+                                "Error while in experiment, aborting experiment...",
+                                errmsg,
                             );
-                            self.reset_experiment_variable();
+                            self.abort_experiment(reason)
                         } else {
-                            if !self.is_time_counting {
-                                self.time_start = self.time_main.elapsed().as_millis();
-                                self.is_time_counting = true;
-                            }
-                            self.active_experiment_state = ExperimentStage::Answering;
+                            let now = self.time_main.elapsed().as_millis();
+                            self.step_experiment(|session| session.drawing_finished(now))
                         }
-                        Task::none()
-                    }
-                    Message::ForceAbortPressed => {
-                        self.log(LT::W, "Force abort button pressed.");
-                        self.reset_experiment_variable();
-                        Task::none()
                     }
-                    _ => unimplemented!(),
-                },
-                ExperimentStage::Answering => match message {
                     Message::ShapeSelected(shape) => {
-                        self.time_end = self.time_main.elapsed().as_millis();
-                        let mut cur_exp_item = self.active_experiment_instance.clone().unwrap();
-
-                        cur_exp_item.list_of_guesses.push(shape);
-                        cur_exp_item
-                            .list_of_time
-                            .push(self.time_end - self.time_start);
-
-                        if cur_exp_item.experiment_index
-                            == cur_exp_item.list_of_shapes.capacity() - 1
-                        {
-                            self.is_busy = false;
-                            self.active_experiment_state = ExperimentStage::NotInExperiment;
-                            self.active_experiment_instance = Some(cur_exp_item);
-                            Task::perform(process_results(self.clone()), |(_ok, _err)| {
-                                Message::ResultsProcessed(_ok, _err)
-                            })
-                        } else {
-                            cur_exp_item.experiment_index += 1;
-                            self.active_idx += 1;
-                            self.active_experiment_instance = Some(cur_exp_item);
-                            self.active_experiment_state = ExperimentStage::BeginTiming;
-                            Task::perform(_wait_n_ms(50), |_| Message::GoToNextStage)
+                        let now = self.time_main.elapsed().as_millis();
+                        let mut trial_entry = None;
+                        let task = self.step_experiment(|session| {
+                            let action = session.answer(shape, now);
+                            if action != ExperimentAction::Ignored {
+                                trial_entry = session.last_trial_entry();
+                            }
+                            action
+                        });
+                        // Journaled before moving on, so a crash in the next drawing keeps the answer.
+                        match trial_entry {
+                            Some(entry) => Task::perform(
+                                journal_append(self.active_config.results_path.clone(), entry),
+                                Message::JournalWritten,
+                            )
+                            .chain(task),
+                            None => task,
                         }
                     }
-                    Message::ForceAbortPressed => {
-                        self.log(LT::W, "Force abort button pressed.");
-                        self.reset_experiment_variable();
-                        Task::none()
-                    }
                     Message::RetryButtonPressed => {
                         self.log(LT::I, "Retry button pressed.");
-                        let mut cur_exp_item = self.active_experiment_instance.clone().unwrap();
-                        cur_exp_item.list_of_retries[cur_exp_item.experiment_index] += 1;
-                        self.active_experiment_instance = Some(cur_exp_item);
-                        self.active_experiment_state = ExperimentStage::Preparation;
-                        Task::perform(_wait_n_ms(50), |_| Message::GoToNextStage)
+                        self.step_experiment(|session| session.retry())
                     }
-                    _ => unimplemented!(),
+                    Message::ConditionReadyPressed => {
+                        self.log(LT::I, "Condition set up, continuing.");
+                        self.step_experiment(|session| session.confirm_condition())
+                    }
+                    message => self.ignore_message(message),
                 },
             },
-            Tabpage::Results => match message {
+            Some(Tabpage::Results) => match message {
                 Message::UpdateResultsList => {
                     self.is_busy = true;
                     Task::perform(
@@ -832,29 +1437,104 @@ impl IcedDobotController {
                     }
                     Task::none()
                 }
-                _ => unimplemented!(),
+                message => self.ignore_message(message),
             },
-            Tabpage::Settings => match message {
+            Some(Tabpage::Settings) => match message {
                 Message::DebugCheckboxPressed(somebool) => {
                     self.is_debug_view = somebool;
                     Task::none()
                 }
                 Message::SimulateModeCheckboxPressed(somebool) => {
                     self.is_simulate_mode = somebool;
+                    self.dobot_connection.connect(self.backend_kind());
                     Task::none()
                 }
                 Message::ThemeSelected(theme) => {
                     self.active_theme = theme;
                     Task::none()
                 }
-                _ => unimplemented!(),
+                Message::CalibrationCapturePressed(point_idx) => {
+                    if let Some(pose) = self.live_pose {
+                        self.calibration_points[point_idx] = Some(
+                            [pose.x, pose.y, pose.z].map(|value| (value * 100.0).round() / 100.0),
+                        );
+                        self.log(
+                            LT::I,
+                            format!("Captured {}.", CALIBRATION_POINT_NAMES[point_idx]),
+                        );
+                    } else {
+                        self.log(LT::W, "No live pose to capture.");
+                    }
+                    Task::none()
+                }
+                Message::RestPoseCapturePressed => {
+                    let Some(pose) = self.live_pose else {
+                        self.log(LT::W, "No live pose to capture.");
+                        return Task::none();
+                    };
+                    // Stored in the default frame, so it moves along when the calibration changes.
+                    let [x, y, z] = match &self.active_config.calibration {
+                        Some(calibration) => calibration.unapply([pose.x, pose.y, pose.z]),
+                        None => [pose.x, pose.y, pose.z].map(|value| (value * 100.0).round() / 100.0),
+                    };
+                    self.active_config.rest_pose = [x, y, z, (pose.r * 100.0).round() / 100.0];
+                    self.log(LT::I, "Captured rest pose.");
+                    self.is_busy = true;
+                    Task::perform(save_config(self.active_config.clone()), Message::ConfigSaveResult)
+                }
+                Message::CalibrationSavePressed => match self.calibration_points {
+                    [Some(origin), Some(u_point), Some(v_point)] => {
+                        match DrawingCalibration::from_points(origin, u_point, v_point) {
+                            Ok(calibration) => {
+                                self.active_config.calibration = Some(calibration);
+                                self.log(LT::I, "Drawing plane calibrated.");
+                                self.is_busy = true;
+                                Task::perform(
+                                    save_config(self.active_config.clone()),
+                                    Message::ConfigSaveResult,
+                                )
+                            }
+                            Err(errmsg) => {
+                                self.log(LT::W, format!("Cannot calibrate: {}", errmsg));
+                                Task::none()
+                            }
+                        }
+                    }
+                    _ => {
+                        self.log(LT::W, "Capture all three points before calibrating.");
+                        Task::none()
+                    }
+                },
+                Message::CalibrationClearPressed => {
+                    self.active_config.calibration = None;
+                    self.calibration_points = [None; 3];
+                    self.log(LT::I, "Calibration cleared, drawing in the default plane.");
+                    self.is_busy = true;
+                    Task::perform(save_config(self.active_config.clone()), Message::ConfigSaveResult)
+                }
+                Message::ConfigSaveResult(erroption) => {
+                    self.is_busy = false;
+                    if let Some(errmsg) = erroption {
+                        self.log(LT::E, format!("Failed to save config. {}", errmsg));
+                    } else {
+                        self.log(LT::I, "Config saved.");
+                    }
+                    Task::none()
+                }
+                message => self.ignore_message(message),
             },
+            None => self.ignore_message(message),
         }
     }
 
     fn view(&self) -> Element<'_, Message> {
         let mut final_column = Column::new().spacing(10).padding(5);
-        final_column = final_column.push(generate_header(self.is_busy, self.is_title_font_ok));
+        final_column = final_column.push(generate_header(
+            self.is_busy,
+            self.is_title_font_ok,
+            self.is_simulate_mode,
+            &self.dobot_state,
+        ));
         final_column = final_column.push(horizontal_rule(10));
         final_column = final_column.push(match self.active_main_tab {
             Tabpage::Sequencer => generate_sequencer_tab(self),
@@ -892,30 +1572,277 @@ impl IcedDobotController {
         .into()
     }
 
+    /// Polls the live pose while connected, see Config::pose_poll_interval_ms.
+    /// Keyboard shortcuts are always on.
+    fn subscription(&self) -> Subscription<Message> {
+        let pose_polling = if self.dobot_state == ConnectionState::Connected
+            && self.active_config.pose_poll_interval_ms > 0
+        {
+            iced::time::every(std::time::Duration::from_millis(
+                self.active_config.pose_poll_interval_ms,
+            ))
+            .map(|_| Message::PollPose)
+        } else {
+            Subscription::none()
+        };
+        // Only where the jog controls are shown, other tabs don't handle JogPressed.
+        let jog_keys = if self.active_main_tab == Tabpage::Sequencer
+            && self.active_sequence_tab == SequencerTabpage::Sequencer
+        {
+            keyboard::on_key_press(handle_jog_key)
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch([pose_polling, keyboard::on_key_press(handle_hotkey), jog_keys])
+    }
+
     fn theme(&self) -> Theme {
         self.active_theme.clone()
     }
 
     fn reset_experiment_variable(&mut self) {
-        self.active_experiment_state = ExperimentStage::NotInExperiment;
-        self.active_experiment_instance = None;
+        self.experiment = None;
         self.is_busy = false;
-        self.n_v = 0.0;
-        self.n_a = 0.0;
-        self.time_start = 0;
-        self.time_end = 0;
-        self.is_time_counting = false;
+        self.epoch += 1;
+    }
+
+    /// Stops the running experiment and saves the trials answered so far with the reason.
+    fn abort_experiment(&mut self, reason: String) -> Task<Message> {
+        let Some(session) = self.experiment.take() else {
+            return Task::none();
+        };
+        self.reset_experiment_variable();
+        self.save_partial_results(session, reason)
+    }
+
+    /// Without any answer there is nothing to keep, the journal is dropped as well.
+    fn save_partial_results(&mut self, session: ExperimentSession, reason: String) -> Task<Message> {
+        match session.aborted_results(reason) {
+            Some(results) => {
+                self.log(
+                    LT::I,
+                    format!(
+                        "Saving {} of {} trials as partial results.",
+                        session.answered_count(),
+                        session.trial_count()
+                    ),
+                );
+                Task::perform(
+                    save_results(self.active_config.results_path.clone(), results),
+                    |(_ok, _err)| Message::ResultsProcessed(_ok, _err),
+                )
+            }
+            None => {
+                self.log(LT::I, "No trial answered, nothing to save.");
+                Task::perform(
+                    clear_journal(self.active_config.results_path.clone()),
+                    Message::JournalWritten,
+                )
+            }
+        }
+    }
+
+    /// Reads the journal back, so a session whose results could not be saved is offered again.
+    fn offer_recovery(&self) -> Task<Message> {
+        Task::perform(
+            load_journal(self.active_config.results_path.clone()),
+            |res| Message::JournalLoaded(res.0, res.1),
+        )
+    }
+
+    /// Applies a transition to the running experiment and carries out the action it returns.
+    fn step_experiment(
+        &mut self,
+        transition: impl FnOnce(&mut ExperimentSession) -> ExperimentAction,
+    ) -> Task<Message> {
+        let Some(session) = self.experiment.as_mut() else {
+            return Task::none();
+        };
+        let action = transition(session);
+        let (velocity, acceleration) = session.current_motion();
+        match action {
+            ExperimentAction::Advance => {
+                self.stamp(Task::perform(_wait_n_ms(50), |_| Message::GoToNextStage))
+            }
+            ExperimentAction::Draw(shape) => self.track_motion(Task::perform(
+                draw_shape(
+                    self.dobot(),
+                    self.relay(),
+                    shape.name.clone(),
+                    self.drawing_steps(&shape, velocity, acceleration),
+                ),
+                Message::DrawingResult,
+            )),
+            ExperimentAction::Finished => {
+                let results = session.results();
+                self.reset_experiment_variable();
+                Task::perform(
+                    save_results(self.active_config.results_path.clone(), results),
+                    |(_ok, _err)| Message::ResultsProcessed(_ok, _err),
+                )
+            }
+            // Long enough for the subject to read the feedback.
+            ExperimentAction::PracticeFeedback(is_correct) => {
+                self.log(
+                    LT::I,
+                    format!("Practice answer {}.", if is_correct { "correct" } else { "wrong" }),
+                );
+                self.stamp(Task::perform(_wait_n_ms(1500), |_| Message::GoToNextStage))
+            }
+            ExperimentAction::AwaitConditionSetup(condition) => {
+                self.log(LT::W, format!("Set up condition {}, then continue.", condition));
+                Task::none()
+            }
+            ExperimentAction::AwaitAnswer | ExperimentAction::Ignored => Task::none(),
+        }
+    }
+
+    fn dobot(&self) -> DobotConnection {
+        DobotConnection {
+            jump_height : self.active_config.jump_height,
+            jump_z_limit: self.active_config.safety_envelope.z_ceiling,
+            ..self.dobot_connection.clone()
+        }
+    }
+
+    /// Runs the sequence as a stream of PlaybackEvent, handled in handle_playback_event.
+    fn start_playback(&mut self, named_sequence: NamedSequence) -> Task<Message> {
+        let (control_sender, control_receiver) = tokio::sync::mpsc::unbounded_channel();
+        self.playback_control = Some(control_sender);
+        self.playback_progress = Some((0, named_sequence.steps.len()));
+        self.is_busy = true;
+        self.track_motion(Task::run(
+            play_sequence(self.dobot(), self.relay(), named_sequence, control_receiver),
+            Message::PlaybackProgress,
+        ))
+    }
+
+    fn reset_playback_variable(&mut self) {
+        self.playback_control = None;
+        self.playback_index = None;
+        self.playback_progress = None;
+        self.playback_iteration = None;
+        self.is_playback_paused = false;
+    }
+
+    /// Makes a motion task cancellable by the emergency stop.
+    fn track_motion(&mut self, task: Task<Message>) -> Task<Message> {
+        let (task, motion_handle) = self.stamp(task).abortable();
+        self.motion_handles.push(motion_handle);
+        task
+    }
+
+    /// Tags the task's messages with the current epoch, update drops them once it has moved on.
+    /// Keeps a late GoToNextStage or DrawingResult from acting on an aborted experiment.
+    fn stamp(&self, task: Task<Message>) -> Task<Message> {
+        let epoch = self.epoch;
+        task.map(move |message| Message::Stamped(epoch, Box::new(message)))
+    }
+
+    /// A message its handler has no use for in the current state, e.g. a click racing a stage change.
+    fn ignore_message(&self, message: Message) -> Task<Message> {
+        log::warn!("Ignored unexpected {:?}", message);
+        Task::none()
+    }
+
+    fn backend_kind(&self) -> BackendKind {
+        if self.is_simulate_mode {
+            BackendKind::Simulated
+        } else {
+            BackendKind::Serial(self.active_config.dobot_path.clone())
+        }
+    }
+
+    /// No relay is attached in unconnected mode.
+    fn relay(&self) -> Option<RelayPath> {
+        if self.is_simulate_mode {
+            None
+        } else {
+            Some(RelayPath {
+                relaypath: self.active_config.relay_path.clone(),
+            })
+        }
+    }
+
+    /// Logs every violation found by the safety envelope. Returns true when the move has to be refused.
+    fn is_outside_envelope(&mut self, violations: Vec<(usize, String)>) -> bool {
+        for (idx, reason) in violations.iter() {
+            self.log(
+                LT::E,
+                format!("Step {} outside safety envelope, refusing: {}", idx, reason),
+            );
+        }
+        !violations.is_empty()
     }
 
-    fn dobot(&self) -> DobotPath {
-        DobotPath {
-            dobotpath: self.active_config.dobot_path.clone(),
+    /// Same check for the shapes of an experiment, at the speed and acceleration of every condition.
+    fn are_shapes_outside_envelope(
+        &mut self,
+        shapes_to_check: &[ShapeDefinition],
+        motions: &[(f32, f32)],
+    ) -> bool {
+        let mut is_outside = false;
+        for (speed, acceleration) in motions {
+            for shape in shapes_to_check {
+                let violations = self.active_config.safety_envelope.check_steps(
+                    &self.drawing_steps(shape, *speed, *acceleration),
+                    self.active_config.jump_height,
+                );
+                if self.is_outside_envelope(violations) {
+                    self.log(
+                        LT::E,
+                        format!("{} is outside the safety envelope.", shape.name),
+                    );
+                    is_outside = true;
+                }
+            }
         }
+        is_outside
+    }
+
+    /// Shape drawing with the configured calibration and rest pose.
+    fn drawing_steps(
+        &self,
+        shape: &ShapeDefinition,
+        velocity: f32,
+        acceleration: f32,
+    ) -> Vec<SequenceStep> {
+        shape.drawing_steps(
+            velocity,
+            acceleration,
+            self.active_config.calibration.as_ref(),
+            self.active_config.rest_pose,
+        )
     }
 
-    fn relay(&self) -> RelayPath {
-        RelayPath {
-            relaypath: self.active_config.relay_path.clone(),
+    /// Logs a dobot error, with a hint on what to do for the kinds the user can fix.
+    fn log_dobot_error(&mut self, context: &str, dobot_error: DobotError) {
+        match dobot_error {
+            DobotError::PathNotSet => self.log(
+                LT::W,
+                format!(
+                    "{} {} Set dobot_path in .config.json or enable unconnected mode.",
+                    context, dobot_error
+                ),
+            ),
+            DobotError::Timeout(_) => self.log(
+                LT::W,
+                format!(
+                    "{} {} Check that the arm is powered and nothing blocks it.",
+                    context, dobot_error
+                ),
+            ),
+            DobotError::Alarm(_) => self.log(
+                LT::E,
+                format!(
+                    "{} {} Clear the alarm on the arm before moving again.",
+                    context, dobot_error
+                ),
+            ),
+            DobotError::Stopped => self.log(LT::W, format!("{} {}", context, dobot_error)),
+            DobotError::PortOpen(_) | DobotError::CommandRejected(_) => {
+                self.log(LT::E, format!("{} {}", context, dobot_error))
+            }
         }
     }
 
@@ -937,7 +1864,12 @@ impl IcedDobotController {
     }
 }
 
-fn generate_header<'a>(is_busy: bool, is_font_ok: bool) -> Element<'a, Message> {
+fn generate_header<'a>(
+    is_busy: bool,
+    is_font_ok: bool,
+    is_simulate_mode: bool,
+    dobot_state: &ConnectionState,
+) -> Element<'a, Message> {
     let tab_button_row = Tabpage::into_iter()
         .map(|page| {
             button(text_ccff_container(page.to_string()))
@@ -959,6 +1891,22 @@ fn generate_header<'a>(is_busy: bool, is_font_ok: bool) -> Element<'a, Message>
             .font(porter_sans_inline_font(is_font_ok))
             .size(26),
         horizontal_space(),
+        text(format!(
+            "Dobot{} {}",
+            if is_simulate_mode { " (Sim)" } else { "" },
+            dobot_state
+        ))
+            .shaping(text::Shaping::Advanced)
+            .style(match dobot_state {
+                ConnectionState::Connected  => text::success,
+                ConnectionState::Faulted(_) => text::danger,
+                _ => text::default,
+            }),
+        button(text_ccff_container("⏹ 停止 (Esc)"))
+            .width(120)
+            .height(40)
+            .on_press(Message::ForceAbortPressed)
+            .style(button::danger),
         tab_button_row.spacing(10)
     ]
     .align_y(Alignment::Center)
@@ -1013,17 +1961,118 @@ fn generate_sequencer_tab<'a>(appv: &IcedDobotController) -> Element<'a, Message
         row![
             dobot_buttonrow,
             horizontal_space(),
+            generate_pose_readout(appv.live_pose),
+            horizontal_space(),
             seqtab_button_row.spacing(10)
-        ],
+        ]
+        .align_y(Alignment::Center),
         seqtab_content
     ]
     .spacing(10)
     .into()
 }
 
+/// Steps other than moves. Params are taken from the V and A inputs above.
+fn generate_step_buttons<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
+    let add_step = |step: Option<SequenceStep>| {
+        if appv.is_busy {
+            None
+        } else {
+            step.map(Message::SequencerAddStepPressed)
+        }
+    };
+    let params = &appv.sequencer.input;
+    let params_step = if params.v.in_string.is_empty() || params.a.in_string.is_empty() {
+        None
+    } else {
+        Some(SequenceStep::SetParams(params.v.in_float, params.a.in_float))
+    };
+
+    row![
+        text("Steps"),
+        text(":"),
+        text_input("500", appv.sequencer.wait_input.as_str())
+            .width(70)
+            .on_input_maybe(if appv.is_busy {
+                None
+            } else {
+                Some(Message::SeqWaitInputUpdated)
+            }),
+        text("ms"),
+        button("Add Wait").on_press_maybe(add_step(
+            appv.sequencer.wait_input
+                .parse::<u64>()
+                .ok()
+                .map(SequenceStep::Wait)
+        )),
+        button("Relay ON").on_press_maybe(add_step(Some(SequenceStep::Relay(true)))),
+        button("Relay OFF").on_press_maybe(add_step(Some(SequenceStep::Relay(false)))),
+        button("Add Home").on_press_maybe(add_step(Some(SequenceStep::Home))),
+        button("Add Params").on_press_maybe(add_step(params_step)),
+    ]
+    .align_y(Alignment::Center)
+    .spacing(10)
+    .into()
+}
+
+/// Jog buttons for each axis, moved steps end up in the coordinate inputs.
+fn generate_jog_controls<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
+    let jog = |axis: JogAxis, direction: f32| {
+        if appv.is_busy || appv.live_pose.is_none() {
+            None
+        } else {
+            Some(Message::JogPressed(axis, direction))
+        }
+    };
+    let unit = match appv.jog_space {
+        JogSpace::Cartesian => "mm",
+        JogSpace::Joint => "deg",
+    };
+
+    JogAxis::ALL
+        .iter()
+        .fold(
+            row![
+                text("Jog"),
+                text(":"),
+                pick_list(JogSpace::ALL, Some(appv.jog_space), Message::JogSpaceSelected),
+                pick_list(JOG_STEPS, Some(appv.jog_step), Message::JogStepSelected).width(70),
+                text(unit),
+            ],
+            |accu, axis| {
+                let label = axis.label(appv.jog_space);
+                accu.push(button(text(format!("-{}", label))).on_press_maybe(jog(*axis, -1.0)))
+                    .push(button(text(format!("+{}", label))).on_press_maybe(jog(*axis, 1.0)))
+            },
+        )
+        .push(text("Keys: ↑↓ X, ←→ Y, PgUp/PgDn Z, Q/E R").shaping(text::Shaping::Advanced))
+        .align_y(Alignment::Center)
+        .spacing(10)
+        .into()
+}
+
+fn generate_pose_readout<'a>(live_pose: Option<RobotPose>) -> Element<'a, Message> {
+    match live_pose {
+        Some(pose) => {
+            let mut readout = format!(
+                "X: {:.2}, Y: {:.2}, Z: {:.2}, R: {:.2}",
+                pose.x, pose.y, pose.z, pose.r
+            );
+            if let Some([j1, j2, j3, j4]) = pose.joints {
+                readout.push_str(&format!(
+                    " | J1: {:.2}, J2: {:.2}, J3: {:.2}, J4: {:.2}",
+                    j1, j2, j3, j4
+                ));
+            }
+            text(readout).into()
+        }
+        None => text("Pose: -").into(),
+    }
+}
+
 fn generate_sequencer_content<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
     let coords_inputs = Coordinate::into_iter()
-        .zip(appv.active_sequencer_sequence.into_iter())
+        .zip(appv.sequencer.input.into_iter())
         .map(|(coord, active_coord)| {
             row![
                 row![text(coord.to_string()).width(10), text(":").width(10)]
@@ -1042,12 +2091,17 @@ fn generate_sequencer_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
         .fold(Row::new().spacing(10), |mut accu, coorditem| {
             accu = accu.push(coorditem);
             accu
-        });
+        })
+        .push(
+            pick_list(
+                MotionMode::ALL,
+                Some(appv.sequencer.input.mode),
+                Message::SequencerModeSelected,
+            )
+            .width(90),
+        );
 
-    let is_any_inputs_empty = appv
-        .active_sequencer_sequence
-        .into_iter()
-        .any(|item| item.in_string.is_empty());
+    let is_any_inputs_empty = appv.sequencer.is_input_incomplete();
     let input_section = column![
         text("Input coordinates, servo rotation, velocity, acceleration below."),
         row![
@@ -1064,6 +2118,13 @@ fn generate_sequencer_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
                 } else {
                     Some(Message::SequencerAddToSequencesPressed)
                 }),
+                button("Capture Pose").on_press_maybe(
+                    if appv.is_busy || appv.live_pose.is_none() {
+                        None
+                    } else {
+                        Some(Message::CapturePosePressed)
+                    }
+                ),
                 button("Empty")
                     .on_press_maybe(if appv.is_busy {
                         None
@@ -1074,49 +2135,72 @@ fn generate_sequencer_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
             ]
             .spacing(10)
         ],
+        generate_step_buttons(appv),
+        generate_jog_controls(appv),
     ]
     .spacing(10);
 
     let queue_section = scrollable(
         dragking::column(
-            appv.active_sequencer_sequences
+            appv.sequencer.steps
                 .iter()
                 .enumerate()
-                .map(|(idx, sequence)| {
+                .map(|(idx, step)| {
                     (
                         idx,
-                        sequence
-                            .into_iter()
-                            .zip(Coordinate::into_iter())
-                            .enumerate()
-                            .fold(Row::new(), |mut accu, (indx, (pos, posname))| {
-                                accu = accu.push(text(format!("{}: ", posname)));
-                                if indx == 5 {
-                                    accu = accu.push(text(format!("{}", pos.in_string)));
-                                } else {
-                                    accu = accu.push(text(format!("{}, ", pos.in_string)));
-                                }
-                                accu
-                            }),
+                        match step {
+                            SequenceStep::Move(sequence) => sequence
+                                .into_iter()
+                                .zip(Coordinate::into_iter())
+                                .enumerate()
+                                .fold(
+                                    Row::new().push(text(format!("{} ", sequence.mode))),
+                                    |mut accu, (indx, (pos, posname))| {
+                                        accu = accu.push(text(format!("{}: ", posname)));
+                                        if indx == 5 {
+                                            accu = accu.push(text(format!("{}", pos.in_string)));
+                                        } else {
+                                            accu =
+                                                accu.push(text(format!("{}, ", pos.in_string)));
+                                        }
+                                        accu
+                                    },
+                                ),
+                            _ => Row::new().push(text(step.to_string())),
+                        },
                     )
                 })
                 .map(|(idx, itemrow)| {
+                    let step = &appv.sequencer.steps[idx];
+                    let violations = appv
+                        .active_config
+                        .safety_envelope
+                        .check_step(step, appv.active_config.jump_height);
                     row![
                         container(
                             row![
                                 text(format!("{}:", idx)),
                                 itemrow,
+                                text(if violations.is_empty() {
+                                    String::new()
+                                } else {
+                                    format!("⚠ {}", violations.join(", "))
+                                })
+                                .shaping(text::Shaping::Advanced)
+                                .style(text::danger),
                                 horizontal_space(),
                                 button("Remove").on_press_maybe(if appv.is_busy {
                                     None
                                 } else {
                                     Some(Message::RemoveASequencePressed(idx))
                                 }),
-                                button("Paste to input").on_press_maybe(if appv.is_busy {
-                                    None
-                                } else {
-                                    Some(Message::PasteSequencePressed(idx))
-                                }),
+                                button("Paste to input").on_press_maybe(
+                                    if appv.is_busy || !matches!(step, SequenceStep::Move(_)) {
+                                        None
+                                    } else {
+                                        Some(Message::PasteSequencePressed(idx))
+                                    }
+                                ),
                             ]
                             .align_y(Alignment::Center)
                             .spacing(10)
@@ -1127,7 +2211,11 @@ fn generate_sequencer_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
                             bottom: 5.0,
                             left: 10.0
                         })
-                        .style(cont_gray_0_10),
+                        .style(if appv.playback_index == Some(idx) {
+                            cont_25510f_0_10
+                        } else {
+                            cont_gray_0_10
+                        }),
                         horizontal_space().width(10)
                     ]
                     .into()
@@ -1146,17 +2234,38 @@ fn generate_sequencer_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
         text(":"),
         text_input(
             "Enter save filename here",
-            appv.active_seq_path_input.as_str()
+            appv.sequencer.name_input.as_str()
         )
         .on_input_maybe(if appv.is_busy {
             None
         } else {
             Some(Message::SeqFilenameInputUpdated)
         }),
+        text("Repeat"),
+        text_input("1", appv.sequencer.repeat_input.as_str())
+            .width(50)
+            .on_input_maybe(if appv.is_busy || appv.sequencer.is_looping {
+                None
+            } else {
+                Some(Message::SeqRepeatInputUpdated)
+            }),
+        checkbox("Loop", appv.sequencer.is_looping).on_toggle_maybe(if appv.is_busy {
+            None
+        } else {
+            Some(Message::SeqLoopToggled)
+        }),
+        text("Dwell [ms]"),
+        text_input("0", appv.sequencer.dwell_input.as_str())
+            .width(70)
+            .on_input_maybe(if appv.is_busy {
+                None
+            } else {
+                Some(Message::SeqDwellInputUpdated)
+            }),
         button("Save").width(92).width(92).on_press_maybe(
             if appv.is_busy
-                || appv.active_seq_path_input.is_empty()
-                || appv.active_sequencer_sequences.is_empty()
+                || appv.sequencer.name_input.is_empty()
+                || appv.sequencer.steps.is_empty()
             {
                 None
             } else {
@@ -1164,7 +2273,7 @@ fn generate_sequencer_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
             }
         ),
         button("Perform").width(92).on_press_maybe(
-            if appv.is_busy || appv.active_sequencer_sequences.is_empty() {
+            if appv.is_busy || appv.sequencer.steps.is_empty() {
                 None
             } else {
                 Some(Message::SequencerPerform)
@@ -1173,13 +2282,14 @@ fn generate_sequencer_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
         button("Clear")
             .width(92)
             .on_press_maybe(
-                if appv.is_busy || appv.active_sequencer_sequences.is_empty() {
+                if appv.is_busy || appv.sequencer.steps.is_empty() {
                     None
                 } else {
                     Some(Message::ClearSequences)
                 }
             )
             .style(|_t, _s| button::secondary(_t, _s)),
+        generate_playback_controls(appv),
     ]
     .align_y(Alignment::Center)
     .height(30)
@@ -1187,13 +2297,92 @@ fn generate_sequencer_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
 
     let final_view = column![
         container(input_section).style(cont_w_2_10).padding(10),
-        container(queue_section).style(cont_w_2_10).padding(10),
+        row![
+            container(queue_section)
+                .style(cont_w_2_10)
+                .padding(10)
+                .width(Length::FillPortion(2)),
+            container(generate_trajectory_preview(
+                TrajectoryPreview::from_steps(
+                    &appv.sequencer.steps,
+                    appv.preview_plane,
+                    appv.active_config.safety_envelope.clone(),
+                ),
+                appv.preview_plane,
+            ))
+            .style(cont_w_2_10)
+            .padding(10)
+            .width(Length::FillPortion(1)),
+        ]
+        .spacing(10),
         container(save_section).style(cont_w_2_10).padding(10),
     ]
     .spacing(10);
     final_view.into()
 }
 
+/// Plane selector above the trajectory canvas.
+fn generate_trajectory_preview<'a>(
+    preview: TrajectoryPreview,
+    plane: ShapePlane,
+) -> Element<'a, Message> {
+    column![
+        row![
+            text("Preview"),
+            horizontal_space(),
+            pick_list(ShapePlane::ALL, Some(plane), Message::PreviewPlaneSelected),
+        ]
+        .align_y(Alignment::Center),
+        canvas(preview).width(Length::Fill).height(Length::Fill),
+    ]
+    .spacing(10)
+    .into()
+}
+
+/// Progress text plus pause/resume and stop, only enabled while a sequence plays.
+fn generate_playback_controls<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
+    let is_playing = appv.playback_control.is_some();
+    row![
+        text(match appv.playback_iteration {
+            Some((iteration, Some(repeat_total))) => format!("#{}/{}", iteration, repeat_total),
+            Some((iteration, None)) => format!("#{}/∞", iteration),
+            None => String::new(),
+        })
+        .shaping(text::Shaping::Advanced),
+        text(match appv.playback_progress {
+            Some((reached, total)) if appv.is_playback_paused => {
+                format!("Paused {}/{}", reached, total)
+            }
+            Some((reached, total)) => format!("{}/{} reached", reached, total),
+            None => String::new(),
+        }),
+        button(if appv.is_playback_paused {
+            "Resume"
+        } else {
+            "Pause"
+        })
+        .width(92)
+        .on_press_maybe(if !is_playing {
+            None
+        } else if appv.is_playback_paused {
+            Some(Message::PlaybackControlPressed(PlaybackControl::Resume))
+        } else {
+            Some(Message::PlaybackControlPressed(PlaybackControl::Pause))
+        }),
+        button("Stop")
+            .width(92)
+            .on_press_maybe(if is_playing {
+                Some(Message::PlaybackControlPressed(PlaybackControl::Stop))
+            } else {
+                None
+            })
+            .style(button::danger),
+    ]
+    .align_y(Alignment::Center)
+    .spacing(10)
+    .into()
+}
+
 fn generate_sequences_content<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
     if appv.sequences_paths.is_empty() {
         text(format!(
@@ -1202,7 +2391,7 @@ fn generate_sequences_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
         ))
         .into()
     } else {
-        scrollable(
+        let sequence_list = scrollable(
             container(
                 appv.sequences_paths
                     .iter()
@@ -1245,7 +2434,16 @@ fn generate_sequences_content<'a>(appv: &IcedDobotController) -> Element<'a, Mes
             .padding(10),
         )
         .width(Length::Fill)
-        .height(Length::Fill)
+        .height(Length::Fill);
+
+        column![
+            container(generate_playback_controls(appv))
+                .style(cont_w_2_10)
+                .padding(10)
+                .width(Length::Fill),
+            sequence_list
+        ]
+        .spacing(10)
         .into()
     }
 }
@@ -1299,103 +2497,170 @@ fn generate_error_log<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
 }
 
 fn generate_experiment_tab<'a>(appv: &'a IcedDobotController) -> Element<'a, Message> {
-    if appv.active_experiment_state == ExperimentStage::NotInExperiment {
-        ccff_container(container(
-            column![
-                appv.active_experiment_parameters
-                    .into_iter()
-                    .fold(Column::new(), |mut accu, parameter| {
-                        {
-                            accu = accu.push(make_exp_prep_input(
-                                parameter.parameter_type.show_title(true),
-                                &parameter.parameter_type.show_title_en(),
-                                &parameter.value,
-                                |input_val| {
-                                    Message::ParameterInputChanged(
-                                        parameter.parameter_type.clone(),
-                                        input_val,
-                                    )
-                                },
-                            ));
+    match &appv.experiment {
+        None => {
+            ccff_container(container(
+                column![
+                    appv.active_experiment_parameters
+                        .into_iter()
+                        .fold(Column::new(), |mut accu, parameter| {
+                            {
+                                accu = accu.push(make_exp_prep_input(
+                                    parameter.parameter_type.show_title(true),
+                                    &parameter.parameter_type.show_title_en(),
+                                    &parameter.value,
+                                    |input_val| {
+                                        Message::ParameterInputChanged(
+                                            parameter.parameter_type.clone(),
+                                            input_val,
+                                        )
+                                    },
+                                ));
+                                accu
+                            }
+                        })
+                        .align_x(Alignment::Center)
+                        .padding(10)
+                        .spacing(10),
+                    generate_recovery_panel(appv),
+                    button(text_ccff_container("Start"))
+                        .on_press_maybe(if appv.recovered_experiment.is_some() {
+                            None
+                        } else {
+                            Some(Message::BeginExperimentPressed)
+                        })
+                        .width(350)
+                        .height(30),
+                    text(if let Some(err_info) = &appv.error_info {
+                        err_info.to_string()
+                    } else {
+                        String::from("")
+                    }),
+                    appv.active_experiment_shapes_to_use.iter().fold(
+                        Row::new().spacing(10),
+                        |mut accu, (shape, is_in_use)| {
+                            accu = accu.push(
+                                checkbox(shape.name.clone(), *is_in_use)
+                                    .on_toggle(|_| Message::SelectedShapesChanged(shape.name.clone())),
+                            );
                             accu
                         }
-                    })
-                    .align_x(Alignment::Center)
-                    .padding(10)
-                    .spacing(10),
-                button(text_ccff_container("Start"))
-                    .on_press(Message::BeginExperimentPressed)
-                    .width(350)
-                    .height(30),
-                text(if let Some(err_info) = &appv.error_info {
-                    err_info.to_string()
-                } else {
-                    String::from("")
-                }),
-                appv.active_experiment_shapes_to_use.iter().fold(
-                    Row::new().spacing(10),
-                    |mut accu, (shape, is_in_use)| {
-                        accu = accu.push(
-                            checkbox(shape.show_name_en(), *is_in_use)
-                                .on_toggle(|_| Message::SelectedShapesChanged(shape.clone())),
-                        );
-                        accu
-                    }
-                ),
-            ]
-            .align_x(Alignment::Center),
-        ))
-        .into()
-    } else {
-        let enable_buttons = appv.active_experiment_state == ExperimentStage::Answering;
-        let shape_selection = appv
-            .active_experiment_shapes_to_use
-            .iter()
-            .filter(|(_, do_use)| *do_use)
-            .map(|(active_shape, _)| active_shape)
-            .fold(Row::new().spacing(10), |mut accu, shape| {
-                accu = accu.push(
-                    button(text_size_ccff_container(shape.to_string(), 25))
+                    ).wrap(),
+                    generate_randomization_controls(appv.active_randomization),
+                    generate_plan_controls(appv),
+                    generate_protocol_controls(appv),
+                    horizontal_rule(1),
+                    generate_shape_generator(appv),
+                ]
+                .spacing(10)
+                .align_x(Alignment::Center),
+            ))
+            .into()
+        }
+        Some(session) => {
+            let enable_buttons = session.stage == ExperimentStage::Answering;
+            // Answers come from the session, it may be a resumed one or an imported plan.
+            let shape_selection = session
+                .instance
+                .shapes_selection
+                .iter()
+                .fold(Row::new().spacing(10), |mut accu, shape| {
+                    accu = accu.push(
+                        button(text_size_ccff_container(shape.answer_label(), 25))
+                            .width(200)
+                            .height(50)
+                            .on_press_maybe(if !enable_buttons {
+                                None
+                            } else {
+                                Some(Message::ShapeSelected(shape.name.clone()))
+                            }),
+                    );
+                    accu
+                });
+
+            let (block_title, instance) = match &session.practice {
+                Some(practice) if session.is_practice() => ("練習・Practice", practice),
+                _ => ("実験", &session.instance),
+            };
+
+            ccff_container(
+                column![
+                    text(format!(
+                        "{} {} / {}",
+                        block_title,
+                        instance.experiment_index + 1,
+                        instance.list_of_shapes.len()
+                    ))
+                    .size(20),
+                    progress_bar(
+                        0.0..=(instance.list_of_shapes.len() - 1) as f32,
+                        instance.experiment_index as f32
+                    )
+                    .width(400),
+                    generate_condition_panel(session),
+                    generate_practice_feedback(session),
+                    shape_selection.wrap(),
+                    button(text_size_ccff_container(" ⟲ リトライ ⟲ ", 20))
                         .width(200)
                         .height(50)
                         .on_press_maybe(if !enable_buttons {
                             None
                         } else {
-                            Some(Message::ShapeSelected(shape.clone()))
-                        }),
-                );
-                accu
-            });
+                            Some(Message::RetryButtonPressed)
+                        })
+                        .style(button::secondary),
+                    button(text_size_ccff_container(" ⟁ 中断 ⟁ ", 20))
+                        .width(200)
+                        .height(50)
+                        .on_press(Message::ForceAbortPressed)
+                        .style(button::danger),
+                ]
+                .spacing(10)
+                .align_x(Alignment::Center),
+            )
+            .into()
+        }
+    }
+}
 
-        ccff_container(
-            column![
-                text(format!(
-                    "実験 {} / {}",
-                    appv.active_idx + 1, appv.active_max_idx + 1
-                ))
-                .size(20),
-                progress_bar(0.0..=appv.active_max_idx as f32, appv.active_idx as f32).width(400),
-                shape_selection.wrap(),
-                button(text_size_ccff_container(" ⟲ リトライ ⟲ ", 20))
-                    .width(200)
-                    .height(50)
-                    .on_press_maybe(if !enable_buttons {
-                        None
-                    } else {
-                        Some(Message::RetryButtonPressed)
-                    })
-                    .style(button::secondary),
-                button(text_size_ccff_container(" ⟁ 中断 ⟁ ", 20))
-                    .width(200)
-                    .height(50)
-                    .on_press(Message::ForceAbortPressed)
-                    .style(button::danger),
-            ]
-            .spacing(10)
-            .align_x(Alignment::Center),
-        )
+/// Whether the practice answer was right, empty outside the feedback.
+fn generate_practice_feedback<'a>(session: &ExperimentSession) -> Element<'a, Message> {
+    let Some((shape, is_correct)) = session.practice_feedback() else {
+        return Column::new().into();
+    };
+    let feedback = if is_correct {
+        "正解・Correct!".to_string()
+    } else {
+        format!("不正解・Wrong, it was {}", shape.answer_label().trim())
+    };
+    text(feedback).size(25).into()
+}
+
+/// Condition or staircase level of the current trial, with the button to go on once it is
+/// set up by hand.
+fn generate_condition_panel<'a>(session: &ExperimentSession) -> Element<'a, Message> {
+    let Some(condition) = session.current_setup() else {
+        return Column::new().into();
+    };
+    let reversals = session.instance.staircase.as_ref().map(|staircase| {
+        let found = session
+            .instance
+            .staircase_track()
+            .map_or(0, |track| track.reversal_levels.len());
+        text(format!("反転・Reversals: {} / {}", found, staircase.reversals))
+    });
+    Column::new()
+        .push(text(format!("条件・Condition: {}", condition)))
+        .push_maybe(reversals)
+        .push_maybe((session.stage == ExperimentStage::ConditionSetup).then(|| {
+            button(text_ccff_container("Condition set・条件設定完了"))
+                .width(350)
+                .height(30)
+                .on_press(Message::ConditionReadyPressed)
+        }))
+        .spacing(10)
+        .align_x(Alignment::Center)
         .into()
-    }
 }
 
 fn generate_result_tab<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
@@ -1432,7 +2697,8 @@ fn generate_result_tab<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
         .padding(10)
         .style(cont_w_2_10),
         container(column![generate_results_summary(
-            appv.active_result_item.clone()
+            appv.active_result_item.clone(),
+            &appv.shape_library
         )])
         .height(Length::Fill)
         .width(Length::FillPortion(1))
@@ -1443,7 +2709,112 @@ fn generate_result_tab<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
     .into()
 }
 
-fn generate_results_table<'a>(result_option: Option<ResultExports>) -> Element<'a, Message> {
+fn generate_shape_generator<'a>(appv: &'a IcedDobotController) -> Element<'a, Message> {
+    let inputs = &appv.shape_generator_inputs;
+    let field_inputs = [(GeneratorField::Name, "Name")]
+        .into_iter()
+        .chain(inputs.kind.fields())
+        .fold(Row::new().spacing(10), |accu, (field, label)| {
+            accu.push(
+                row![
+                    text(label),
+                    text_input("", inputs.get(field))
+                        .width(if field == GeneratorField::Name { 120 } else { 60 })
+                        .on_input_maybe(if appv.is_busy {
+                            None
+                        } else {
+                            Some(move |input_text| {
+                                Message::GeneratorInputChanged(field, input_text)
+                            })
+                        })
+                ]
+                .spacing(5)
+                .align_y(Alignment::Center),
+            )
+        });
+
+    let generated = inputs.build();
+    let previewed_shape = match &appv.preview_shape {
+        Some(shape_name) => appv
+            .shape_library
+            .iter()
+            .find(|shape| shape.name == *shape_name)
+            .cloned(),
+        None => generated.clone().ok(),
+    };
+    let shape_names = appv
+        .shape_library
+        .iter()
+        .map(|shape| shape.name.clone())
+        .collect::<Vec<String>>();
+    let vertex_list = match &generated {
+        Ok(shape) => text(format!(
+            "{} points: {}",
+            shape.vertices.len(),
+            shape
+                .vertices
+                .iter()
+                .map(|[u, v]| format!("({}, {})", u, v))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )),
+        Err(errmsg) => text(errmsg.clone()).style(text::danger),
+    };
+
+    column![
+        text("Shape generator・図形生成"),
+        row![
+            pick_list(
+                GeneratorKind::ALL,
+                Some(inputs.kind),
+                Message::GeneratorKindSelected
+            ),
+            field_inputs.wrap(),
+            button("Save shape").on_press_maybe(if appv.is_busy || generated.is_err() {
+                None
+            } else {
+                Some(Message::GeneratorSavePressed)
+            }),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center),
+        vertex_list,
+        row![
+            pick_list(shape_names, appv.preview_shape.clone(), |shape_name| {
+                Message::PreviewShapeSelected(Some(shape_name))
+            })
+            .placeholder("Generator"),
+            button("Generator").on_press_maybe(if appv.preview_shape.is_none() {
+                None
+            } else {
+                Some(Message::PreviewShapeSelected(None))
+            }),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center),
+        container(match previewed_shape {
+            Some(shape) => generate_trajectory_preview(
+                TrajectoryPreview::from_shape(
+                    &shape,
+                    appv.active_config.calibration.as_ref(),
+                    appv.preview_plane,
+                    appv.active_config.safety_envelope.clone(),
+                ),
+                appv.preview_plane,
+            ),
+            None => text("Nothing to preview.").into(),
+        })
+        .width(400)
+        .height(300),
+    ]
+    .spacing(10)
+    .into()
+}
+
+fn generate_results_table<'a>(
+    result_option: Option<ResultExports>,
+    shape_library: &[ShapeDefinition],
+) -> Element<'a, Message> {
     if let Some(result_content) = result_option {
         let mut table_column = Column::new();
 
@@ -1467,12 +2838,12 @@ fn generate_results_table<'a>(result_option: Option<ResultExports>) -> Element<'
             table_column = table_column.push(row![
                 //1 Shape
                 generate_table_container(
-                    text(result_content.results[idx].true_shape.show_name_symbol())
+                    text(shape_symbol(shape_library, &result_content.results[idx].true_shape))
                         .shaping(text::Shaping::Advanced)
                 ),
                 //2 Guess
                 generate_table_container(
-                    text(result_content.results[idx].guess_shape.show_name_symbol())
+                    text(shape_symbol(shape_library, &result_content.results[idx].guess_shape))
                         .shaping(text::Shaping::Advanced)
                 ),
                 //3 Result
@@ -1509,12 +2880,16 @@ fn generate_table_container<'a, T: Into<Element<'a, Message>>>(
         .into()
 }
 
-fn generate_results_summary<'a>(result_option: Option<ResultExports>) -> Element<'a, Message> {
+fn generate_results_summary<'a>(
+    result_option: Option<ResultExports>,
+    shape_library: &[ShapeDefinition],
+) -> Element<'a, Message> {
     if let Some(result) = result_option.clone() {
         column![
             scrollable(make_analysis_view(result)).height(Length::FillPortion(1)),
             horizontal_rule(1),
-            scrollable(generate_results_table(result_option)).height(Length::FillPortion(1)),
+            scrollable(generate_results_table(result_option, shape_library))
+                .height(Length::FillPortion(1)),
         ]
         .spacing(10)
         .into()
@@ -1556,14 +2931,14 @@ fn make_analysis_label<
 
 fn make_shape_analysis<'a>(analysed_shape: &mut ShapeAnalysis, total: u32) -> Element<'a, Message> {
     row![
-        text(analysed_shape.main_shape.show_name_en()).width(165),
+        text(analysed_shape.main_shape.clone()).width(165),
         text(" : ").width(20),
         {
             analysed_shape.wrong_shapes.iter().fold(
                 {
                     let mut in_col = Column::new();
                     in_col = in_col.push(make_analysis_label(
-                        analysed_shape.main_shape.show_name_en(),
+                        analysed_shape.main_shape.clone(),
                         analysed_shape.main_shape_count.to_string(),
                         total.to_string(),
                     ));
@@ -1571,7 +2946,7 @@ fn make_shape_analysis<'a>(analysed_shape: &mut ShapeAnalysis, total: u32) -> El
                 },
                 |mut accu, (shape_item, shape_count)| {
                     accu = accu.push(make_analysis_label(
-                        shape_item.show_name_en(),
+                        shape_item.clone(),
                         shape_count.to_string(),
                         total.to_string(),
                     ));
@@ -1612,12 +2987,23 @@ fn make_analysis_view<'a>(result: ResultExports) -> Element<'a, Message> {
                 accu
             });
 
+    if let CompletionStatus::Aborted(reason) = &result.status {
+        move_column = move_column.push(make_summary_label(
+            "状態・Status".to_string(),
+            format!(
+                "Aborted・中断 ({} / {}): {}",
+                result.results.len(),
+                result.parameters.test_count.value,
+                reason
+            ),
+            None,
+        ));
+    }
+
+    // Out of the answered trials, which is every trial unless the session is incomplete.
     move_column = move_column.push(make_summary_label(
         "結果・Results".to_string(),
-        format!(
-            "{} / {}",
-            result.avg_correct_answers, result.parameters.test_count.value
-        ),
+        format!("{} / {}", result.avg_correct_answers, result.results.len()),
         None,
     ));
 
@@ -1625,13 +3011,64 @@ fn make_analysis_view<'a>(result: ResultExports) -> Element<'a, Message> {
         "正解率・Results Rate".to_string(),
         format!(
             "{}",
-            result.avg_correct_answers as f32
-                / result.parameters.test_count.value.parse::<f32>().unwrap()
-                * 100.0
+            result.avg_correct_answers as f32 / result.results.len().max(1) as f32 * 100.0
         ),
         Some(String::from(" [%]")),
     ));
 
+    move_column = move_column.push(make_summary_label(
+        "ランダム化・Randomization".to_string(),
+        result.randomization.to_string(),
+        None,
+    ));
+
+    if let Some(plan) = &result.plan {
+        move_column = move_column.push(make_summary_label(
+            "計画・Plan".to_string(),
+            plan.clone(),
+            None,
+        ));
+    }
+
+    if let Some(protocol) = &result.protocol {
+        move_column = move_column.push(make_summary_label(
+            "プロトコル・Protocol".to_string(),
+            protocol.clone(),
+            None,
+        ));
+    }
+
+    if let Some(staircase_result) = &result.staircase {
+        let staircase = &staircase_result.staircase;
+        move_column = move_column.push(make_summary_label(
+            "階段法・Staircase".to_string(),
+            format!(
+                "{}, {}, {} / {} reversals",
+                staircase.rule,
+                staircase.parameter.show_title_en(),
+                staircase_result.reversal_levels.len(),
+                staircase.reversals
+            ),
+            None,
+        ));
+        move_column = move_column.push(match staircase_result.threshold {
+            Some(threshold) => make_summary_label(
+                "閾値・Threshold".to_string(),
+                if staircase_result.is_converged {
+                    format!("{}", threshold)
+                } else {
+                    format!("{} (not converged・未収束)", threshold)
+                },
+                staircase.parameter.show_unit(),
+            ),
+            None => make_summary_label(
+                "閾値・Threshold".to_string(),
+                "Too few reversals・反転不足".to_string(),
+                None,
+            ),
+        });
+    }
+
     move_column = move_column.push(make_summary_label(
         "平均時間・Avg. Time".to_string(),
         format!("{}", result.avg_time),
@@ -1652,9 +3089,100 @@ fn make_analysis_view<'a>(result: ResultExports) -> Element<'a, Message> {
             accu
         });
 
+    let mut move_column = result
+        .condition_analyses
+        .into_iter()
+        .fold(move_column, |accu, condition_analysis| {
+            accu.push(make_condition_analysis(&result.parameters, condition_analysis))
+        });
+
+    // Only for reference, none of the scores above count them.
+    if !result.practice.is_empty() {
+        move_column = move_column.push(make_summary_label(
+            "練習・Practice".to_string(),
+            format!(
+                "{} / {}",
+                result.practice.iter().filter(|item| item.is_correct).count(),
+                result.practice.len()
+            ),
+            None,
+        ));
+        move_column = move_column.push(row_container_space(result.practice.iter().fold(
+            Column::new(),
+            |accu, item| {
+                accu.push(text(format!(
+                    "{} → {} ({} ms){}",
+                    item.true_shape,
+                    item.guess_shape,
+                    item.time,
+                    if item.is_correct { "" } else { " ✗" }
+                )))
+            },
+        )));
+    }
+
     move_column.into()
 }
 
+/// Same summary as the whole session for the trials of one condition, with the parameters it overrides.
+fn make_condition_analysis<'a>(
+    parameters: &Parameters,
+    mut condition_analysis: ConditionAnalysis,
+) -> Element<'a, Message> {
+    let condition_column = condition_analysis
+        .parameters
+        .into_iter()
+        .zip(parameters.into_iter())
+        .filter(|(parameter, entered)| parameter.value != entered.value)
+        .fold(
+            column![text(format!("条件・Condition: {}", condition_analysis.condition))],
+            |accu, (parameter, _)| {
+                accu.push(make_summary_label(
+                    parameter.parameter_type.show_title(false).to_string(),
+                    parameter.value.clone(),
+                    parameter.parameter_type.show_unit(),
+                ))
+            },
+        );
+    let condition_column = condition_column
+        .push(make_summary_label(
+            "結果・Results".to_string(),
+            format!(
+                "{} / {}",
+                condition_analysis.avg_correct_answers, condition_analysis.trial_count
+            ),
+            None,
+        ))
+        .push(make_summary_label(
+            "正解率・Results Rate".to_string(),
+            format!(
+                "{}",
+                condition_analysis.avg_correct_answers as f32
+                    / condition_analysis.trial_count.max(1) as f32
+                    * 100.0
+            ),
+            Some(String::from(" [%]")),
+        ))
+        .push(make_summary_label(
+            "平均時間・Avg. Time".to_string(),
+            format!("{}", condition_analysis.avg_time),
+            Some(String::from(" [ms]")),
+        ))
+        .spacing(10);
+
+    condition_analysis
+        .analyses
+        .iter_mut()
+        .map(|shape_analysis| {
+            let totals = shape_analysis.calc_total_self_shape();
+            make_shape_analysis(shape_analysis, totals)
+        })
+        .fold(condition_column, |accu, analysis_elem| {
+            accu.push(row_container_space(analysis_elem))
+        })
+        .into()
+}
+
 fn generate_settings_tab<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
     column![
         checkbox("Toggle Debug View", appv.is_debug_view).on_toggle(Message::DebugCheckboxPressed),
@@ -1663,6 +3191,266 @@ fn generate_settings_tab<'a>(appv: &IcedDobotController) -> Element<'a, Message>
         pick_list(Theme::ALL, Some(appv.active_theme.clone()), |selection| {
             Message::ThemeSelected(selection)
         }),
+        horizontal_rule(1),
+        generate_calibration_panel(appv),
+    ]
+    .spacing(10)
+    .into()
+}
+
+/// Jog the pen to the new shape centre, a point along +U and a point on the +V side, then compute.
+fn generate_randomization_controls<'a>(randomization: Randomization) -> Element<'a, Message> {
+    let is_latin_square = randomization.strategy == RandomizationStrategy::LatinSquare;
+    row![
+        text("Trial order・試行順"),
+        pick_list(
+            RandomizationStrategy::ALL,
+            Some(randomization.strategy),
+            Message::RandomizationStrategySelected,
+        ),
+        text("Row・行"),
+        text_input("0", &randomization.latin_square_row.to_string())
+            .on_input_maybe(if is_latin_square {
+                Some(Message::LatinSquareRowChanged)
+            } else {
+                None
+            })
+            .width(60),
+        checkbox("No immediate repeat・連続なし", randomization.no_immediate_repeat)
+            .on_toggle(Message::NoRepeatToggled),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center)
+    .into()
+}
+
+/// Export of the drawn trial order and import of a plan file instead of drawing one.
+fn generate_plan_controls<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
+    let plan_text = match &appv.active_plan {
+        Some(ExperimentPlan { name, list_of_shapes, conditions: Some(schedule), .. }) => format!(
+            "Plan・計画: {} ({} trials, protocol {})",
+            name,
+            list_of_shapes.len(),
+            schedule.protocol
+        ),
+        Some(plan) => format!("Plan・計画: {} ({} trials)", plan.name, plan.list_of_shapes.len()),
+        None => "No plan, trial order drawn at start・計画なし".to_string(),
+    };
+    column![
+        row![
+            button(text_ccff_container("Export plan・計画を書き出す"))
+                .width(200)
+                .height(30)
+                .on_press_maybe(if appv.is_busy || appv.active_plan.is_some() {
+                    None
+                } else {
+                    Some(Message::PlanExportPressed)
+                }),
+            pick_list(
+                appv.plans_paths.clone(),
+                None::<String>,
+                Message::PlanSelected,
+            )
+            .placeholder("Import plan・計画を読み込む")
+            .width(250),
+            button(text_ccff_container("Clear plan・計画を解除"))
+                .width(200)
+                .height(30)
+                .on_press_maybe(if appv.active_plan.is_some() {
+                    Some(Message::PlanClearPressed)
+                } else {
+                    None
+                }),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center),
+        text(plan_text),
+    ]
+    .spacing(5)
+    .align_x(Alignment::Center)
+    .into()
+}
+
+/// An imported plan brings its own conditions, the protocol is only used to draw new ones.
+fn generate_protocol_controls<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
+    let protocol_text = match (&appv.active_plan, &appv.active_protocol) {
+        (Some(_), _) => "Conditions from the plan・計画の条件".to_string(),
+        (
+            None,
+            Some(ExperimentProtocol {
+                name,
+                staircase: Some(staircase),
+                ..
+            }),
+        ) => format!(
+            "Protocol・プロトコル: {} (staircase {}, {})",
+            name,
+            staircase.rule,
+            staircase.parameter.show_title_en()
+        ),
+        (None, Some(protocol)) => format!(
+            "Protocol・プロトコル: {} ({} conditions, {})",
+            protocol.name,
+            protocol.conditions.len(),
+            protocol.scheduling
+        ),
+        (None, None) => "No protocol, one condition・プロトコルなし".to_string(),
+    };
+    column![
+        row![
+            pick_list(
+                appv.protocols_paths.clone(),
+                None::<String>,
+                Message::ProtocolSelected,
+            )
+            .placeholder("Load protocol・プロトコルを読み込む")
+            .width(250),
+            button(text_ccff_container("Clear protocol・プロトコルを解除"))
+                .width(250)
+                .height(30)
+                .on_press_maybe(if appv.active_protocol.is_some() {
+                    Some(Message::ProtocolClearPressed)
+                } else {
+                    None
+                }),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center),
+        text(protocol_text),
+    ]
+    .spacing(5)
+    .align_x(Alignment::Center)
+    .into()
+}
+
+/// Offer for the unfinished session from the journal, empty when there is none.
+fn generate_recovery_panel<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
+    let Some(session) = &appv.recovered_experiment else {
+        return Column::new().into();
+    };
+    column![
+        text(format!(
+            "Unfinished session of {}, {} / {} trials answered.・未完了のセッション",
+            session.parameters.subject_name.value,
+            session.answered_count(),
+            session.trial_count()
+        )),
+        row![
+            button(text_ccff_container("Resume・再開"))
+                .width(150)
+                .height(30)
+                .on_press_maybe(if appv.is_busy || session.is_finished() {
+                    None
+                } else {
+                    Some(Message::RecoveryResumePressed)
+                }),
+            button(text_ccff_container("Save partial・部分保存"))
+                .width(150)
+                .height(30)
+                .on_press_maybe(if appv.is_busy {
+                    None
+                } else {
+                    Some(Message::RecoverySavePartialPressed)
+                }),
+            button(text_ccff_container("Discard・破棄"))
+                .width(150)
+                .height(30)
+                .on_press_maybe(if appv.is_busy {
+                    None
+                } else {
+                    Some(Message::RecoveryDiscardPressed)
+                })
+                .style(button::danger),
+        ]
+        .spacing(10),
+    ]
+    .spacing(5)
+    .align_x(Alignment::Center)
+    .into()
+}
+
+fn generate_calibration_panel<'a>(appv: &IcedDobotController) -> Element<'a, Message> {
+    let can_capture = appv.live_pose.is_some() && !appv.is_busy;
+    let format_point = |point: &Option<[f32; 3]>| match point {
+        Some([x, y, z]) => format!("X: {:.2}, Y: {:.2}, Z: {:.2}", x, y, z),
+        None => "-".to_string(),
+    };
+
+    let mut point_rows = Column::new().spacing(5);
+    for (point_idx, point) in appv.calibration_points.iter().enumerate() {
+        point_rows = point_rows.push(
+            row![
+                button(text_ccff_container(format!(
+                    "Capture {}",
+                    CALIBRATION_POINT_NAMES[point_idx]
+                )))
+                .width(160)
+                .height(35)
+                .on_press_maybe(if can_capture {
+                    Some(Message::CalibrationCapturePressed(point_idx))
+                } else {
+                    None
+                }),
+                text(format_point(point)),
+            ]
+            .align_y(Alignment::Center)
+            .spacing(10),
+        );
+    }
+
+    let [rest_x, rest_y, rest_z, rest_r] = appv.active_config.rest_pose;
+    let calibration_text = match &appv.active_config.calibration {
+        Some(calibration) => format!(
+            "Origin: {} | U: {:.3?} | V: {:.3?}",
+            format_point(&Some(calibration.origin)),
+            calibration.u_axis,
+            calibration.v_axis
+        ),
+        None => "Not calibrated, drawing in the default plane.・未較正".to_string(),
+    };
+
+    column![
+        text("Drawing Plane Calibration・描画平面の較正").size(20),
+        generate_pose_readout(appv.live_pose),
+        point_rows,
+        row![
+            button(text_ccff_container("Compute & save・計算と保存"))
+                .width(200)
+                .height(35)
+                .on_press_maybe(
+                    if !appv.is_busy && appv.calibration_points.iter().all(Option::is_some) {
+                        Some(Message::CalibrationSavePressed)
+                    } else {
+                        None
+                    }
+                ),
+            button(text_ccff_container("Clear calibration・較正を解除"))
+                .width(200)
+                .height(35)
+                .on_press_maybe(if appv.is_busy {
+                    None
+                } else {
+                    Some(Message::CalibrationClearPressed)
+                }),
+        ]
+        .spacing(10),
+        text(calibration_text),
+        row![
+            button(text_ccff_container("Capture rest pose・休止位置"))
+                .width(200)
+                .height(35)
+                .on_press_maybe(if can_capture {
+                    Some(Message::RestPoseCapturePressed)
+                } else {
+                    None
+                }),
+            text(format!(
+                "Rest pose: X: {:.2}, Y: {:.2}, Z: {:.2}, R: {:.2}",
+                rest_x, rest_y, rest_z, rest_r
+            )),
+        ]
+        .align_y(Alignment::Center)
+        .spacing(10),
     ]
     .spacing(10)
     .into()
@@ -1704,6 +3492,70 @@ fn text_size_ccff_container<'a, T: Into<String> + iced::widget::text::IntoFragme
         .into()
 }
 
+fn handle_hotkey(key: keyboard::Key, _modifiers: keyboard::Modifiers) -> Option<Message> {
+    match key {
+        keyboard::Key::Named(keyboard::key::Named::Escape) => Some(Message::ForceAbortPressed),
+        _ => None,
+    }
+}
+
+/// Arrows jog X and Y, PageUp/PageDown Z, Q/E R, or J1 to J4 in joint space.
+/// Keys typed into a focused text input never get here.
+fn handle_jog_key(key: keyboard::Key, _modifiers: keyboard::Modifiers) -> Option<Message> {
+    let (axis, direction) = match key.as_ref() {
+        keyboard::Key::Named(keyboard::key::Named::ArrowUp)    => (JogAxis::X, 1.0),
+        keyboard::Key::Named(keyboard::key::Named::ArrowDown)  => (JogAxis::X, -1.0),
+        keyboard::Key::Named(keyboard::key::Named::ArrowLeft)  => (JogAxis::Y, 1.0),
+        keyboard::Key::Named(keyboard::key::Named::ArrowRight) => (JogAxis::Y, -1.0),
+        keyboard::Key::Named(keyboard::key::Named::PageUp)     => (JogAxis::Z, 1.0),
+        keyboard::Key::Named(keyboard::key::Named::PageDown)   => (JogAxis::Z, -1.0),
+        keyboard::Key::Character("q") | keyboard::Key::Character("Q") => (JogAxis::R, 1.0),
+        keyboard::Key::Character("e") | keyboard::Key::Character("E") => (JogAxis::R, -1.0),
+        _ => return None,
+    };
+    Some(Message::JogPressed(axis, direction))
+}
+
+fn handle_playback_event(appv: &mut IcedDobotController, event: PlaybackEvent) -> Task<Message> {
+    match event {
+        PlaybackEvent::Iteration(iteration, repeat_total) => {
+            appv.playback_iteration = Some((iteration, repeat_total));
+        }
+        PlaybackEvent::Moving(idx, total) => {
+            appv.playback_index = Some(idx);
+            appv.playback_progress = Some((idx, total));
+        }
+        PlaybackEvent::Reached(idx, total) => {
+            appv.playback_progress = Some((idx + 1, total));
+            log::info!("{}/{} reached", idx + 1, total);
+        }
+        PlaybackEvent::Paused(idx) => {
+            appv.is_playback_paused = true;
+            appv.log(LT::I, format!("Playback paused before waypoint {}.", idx));
+        }
+        PlaybackEvent::Resumed => {
+            appv.is_playback_paused = false;
+            appv.log(LT::I, "Playback resumed.");
+        }
+        PlaybackEvent::Finished => {
+            appv.log(LT::I, "Sequence finished.");
+            appv.reset_playback_variable();
+            appv.is_busy = false;
+        }
+        PlaybackEvent::Stopped(idx) => {
+            appv.log(LT::W, format!("Playback stopped before waypoint {}.", idx));
+            appv.reset_playback_variable();
+            appv.is_busy = false;
+        }
+        PlaybackEvent::Failed(idx, errmsg) => {
+            appv.log_dobot_error(&format!("Error running sequence at waypoint {}:", idx), errmsg);
+            appv.reset_playback_variable();
+            appv.is_busy = false;
+        }
+    }
+    Task::none()
+}
+
 fn handle_tabs(appv: &mut IcedDobotController, selected_tab: Tabpage) -> Task<Message> {
     if appv.active_main_tab != selected_tab {
         if selected_tab == Tabpage::Results {
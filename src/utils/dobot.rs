@@ -1,9 +1,24 @@
-use dobot_fx24::{error, Dobot};
+use dobot_fx24::{Dobot, Mode};
+use iced::futures::{channel::mpsc as futures_mpsc, future::BoxFuture, SinkExt, Stream};
 use serialport::SerialPort;
+use tokio::sync::{mpsc, oneshot};
 
-use crate::{Shapes, _wait_n_ms};
+use super::helpers::_wait_n_ms;
+use super::simulator::SimulatedDobot;
+use super::structs::{
+    BackendKind, ConnectionState, DobotCommand, DobotConnection, DobotError, DobotOp,
+    DobotReceivers, DobotReply, JogTarget, MotionMode, NamedSequence, PlaybackControl,
+    PlaybackEvent, Position, RelayPath, RobotPose, SequenceStep, StopReply,
+};
 
-use super::structs::{DobotPath, Position, RelayPath};
+/// Time between reconnect attempts while the connection is faulted.
+const RECONNECT_INTERVAL_MS: u64 = 2000;
+/// How many times a failed command is retried on a freshly reopened port.
+const RECONNECT_RETRIES: u32 = 1;
+/// Upper bound for a single command, including waiting for the move to finish.
+const COMMAND_TIMEOUT_MS: u64 = 60000;
+/// Attempts at opening the relay port during an emergency stop, 50 ms apart.
+const RELAY_RELEASE_RETRIES: u32 = 10;
 
 /// In this repository, a relay connected to arduino is used.
 /// therefore, a default connection to arduino at /dev/ttyACM0, baud rate 9600 is used.
@@ -32,182 +47,710 @@ pub fn toggle_relay<'a>(relay: &'a mut Box<dyn SerialPort>, signal: bool) -> Opt
     }
 }
 
+/// Everything the connection actor needs from an arm.
+/// Each call resolves once the arm has finished the command, not when it was queued.
+/// Implemented by the real dobot below and by SimulatedDobot in simulator.rs.
+pub trait RobotBackend: Send {
+    fn move_to(&mut self, x: f32, y: f32, z: f32, r: f32, mode: MotionMode)
+        -> BoxFuture<'_, Result<(), DobotError>>;
+    /// Joint interpolated move to J1 to J4. [deg]
+    fn move_joints(&mut self, joints: [f32; 4]) -> BoxFuture<'_, Result<(), DobotError>>;
+    fn set_home(&mut self) -> BoxFuture<'_, Result<(), DobotError>>;
+    fn get_pose(&mut self) -> BoxFuture<'_, Result<RobotPose, DobotError>>;
+    fn set_ptp_coordinate_params(
+        &mut self,
+        v: f32,
+        a: f32,
+    ) -> BoxFuture<'_, Result<(), DobotError>>;
+    fn set_ptp_jump_params(&mut self, jump_height: f32, z_limit: f32)
+        -> BoxFuture<'_, Result<(), DobotError>>;
+    /// Halts the current motion and drops everything still queued.
+    fn stop(&mut self) -> BoxFuture<'_, Result<(), DobotError>>;
+}
+
+/// Driver errors after the port is open are reported as rejected commands,
+/// the driver does not tell a refused command and a broken link apart.
+impl RobotBackend for Dobot {
+    fn move_to(&mut self, x: f32, y: f32, z: f32, r: f32, mode: MotionMode)
+        -> BoxFuture<'_, Result<(), DobotError>> {
+        let driver_mode = match mode {
+            MotionMode::MovJ => Mode::MovJXyz,
+            MotionMode::MovL => Mode::MovLXyz,
+            MotionMode::Jump => Mode::JumpXyz,
+        };
+        Box::pin(async move {
+            match Dobot::set_ptp_cmd(self, x, y, z, r, driver_mode).await {
+                Ok(handle) => handle.wait().await.map_err(rejected),
+                Err(errmsg) => Err(rejected(errmsg)),
+            }
+        })
+    }
+
+    fn move_joints(&mut self, joints: [f32; 4]) -> BoxFuture<'_, Result<(), DobotError>> {
+        let [j1, j2, j3, j4] = joints;
+        Box::pin(async move {
+            match Dobot::set_ptp_cmd(self, j1, j2, j3, j4, Mode::MovJAngle).await {
+                Ok(handle) => handle.wait().await.map_err(rejected),
+                Err(errmsg) => Err(rejected(errmsg)),
+            }
+        })
+    }
+
+    fn set_home(&mut self) -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(async move {
+            match Dobot::set_home(self).await {
+                Ok(handle) => handle.wait().await.map_err(rejected),
+                Err(errmsg) => Err(rejected(errmsg)),
+            }
+        })
+    }
+
+    fn get_pose(&mut self) -> BoxFuture<'_, Result<RobotPose, DobotError>> {
+        Box::pin(async move {
+            match Dobot::get_pose(self).await {
+                Ok(pose) => Ok(RobotPose {
+                    x     : pose.x,
+                    y     : pose.y,
+                    z     : pose.z,
+                    r     : pose.r,
+                    joints: Some([pose.j1, pose.j2, pose.j3, pose.j4]),
+                }),
+                Err(errmsg) => Err(rejected(errmsg)),
+            }
+        })
+    }
+
+    fn set_ptp_coordinate_params(
+        &mut self,
+        v: f32,
+        a: f32,
+    ) -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(async move {
+            match Dobot::set_ptp_coordinate_params(self, v, a).await {
+                Ok(handle) => handle.wait().await.map_err(rejected),
+                Err(errmsg) => Err(rejected(errmsg)),
+            }
+        })
+    }
+
+    /// The driver's second argument is the absolute z a jump may lift to at most,
+    /// the lift is cut short there rather than rising jump_height above the start.
+    fn set_ptp_jump_params(&mut self, jump_height: f32, z_limit: f32)
+        -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(async move {
+            match Dobot::set_ptp_jump_params(self, jump_height, z_limit).await {
+                Ok(handle) => handle.wait().await.map_err(rejected),
+                Err(errmsg) => Err(rejected(errmsg)),
+            }
+        })
+    }
+
+    fn stop(&mut self) -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(async move {
+            Dobot::set_queued_cmd_stop_exec(self).await.map_err(rejected)?;
+            Dobot::set_queued_cmd_clear(self).await.map_err(rejected)?;
+            // Queue execution has to run again, or the next command would never start.
+            Dobot::set_queued_cmd_start_exec(self).await.map_err(rejected)?;
+            Ok(())
+        })
+    }
+}
+
+fn rejected(errmsg: dobot_fx24::error::Error) -> DobotError {
+    DobotError::CommandRejected(format!("{}", errmsg))
+}
+
+/// Opens the backend for the given kind. The simulator never fails to open.
+async fn open_backend(backend_kind: &BackendKind) -> Result<Box<dyn RobotBackend>, DobotError> {
+    match backend_kind {
+        BackendKind::Serial(dobot_path) => match Dobot::open(dobot_path).await {
+            Ok(dobot) => Ok(Box::new(dobot)),
+            Err(errmsg) => Err(DobotError::PortOpen(format!("{}", errmsg))),
+        },
+        BackendKind::Simulated => Ok(Box::new(SimulatedDobot::new())),
+    }
+}
+
 /// Same as relay, path defined in config.json or structs.rs, Config::default.
-impl DobotPath {
-    pub async fn go_home(&self) -> Option<String> {
-        match Dobot::open(&self.dobotpath).await {
-            Ok(mut dobot) => match dobot.set_home().await {
-                Ok(_handle) => {
-                    let _ = _handle.wait().await;
-                    None
+/// The port is opened once by the connection actor and kept open between commands.
+/// Any failed command drops the port, which is then reopened and the command retried.
+/// Stop requests come in on their own channel so they can interrupt a command in progress.
+pub fn dobot_connection_actor(
+    receivers: DobotReceivers,
+) -> impl Stream<Item = ConnectionState> {
+    iced::stream::channel(16, move |output| async move {
+        let mut actor = ConnectionActor {
+            backend_kind: BackendKind::Serial(String::new()),
+            backend     : None,
+            state       : ConnectionState::Disconnected,
+            commands    : receivers.commands,
+            stops       : receivers.stops,
+            output,
+        };
+        actor.run().await;
+    })
+}
+
+struct ConnectionActor {
+    backend_kind: BackendKind,
+    backend     : Option<Box<dyn RobotBackend>>,
+    state       : ConnectionState,
+    commands    : mpsc::UnboundedReceiver<DobotCommand>,
+    stops       : mpsc::UnboundedReceiver<StopReply>,
+    output      : futures_mpsc::Sender<ConnectionState>,
+}
+
+impl ConnectionActor {
+    async fn run(&mut self) {
+        let mut reconnect_timer =
+            tokio::time::interval(tokio::time::Duration::from_millis(RECONNECT_INTERVAL_MS));
+        loop {
+            tokio::select! {
+                command = self.commands.recv() => match command {
+                    Some(DobotCommand::Connect(backend_kind)) => self.connect(backend_kind).await,
+                    // Nobody waits for the reply any more, e.g. the task was aborted.
+                    Some(DobotCommand::Execute(_, reply)) if reply.is_closed() => {}
+                    Some(DobotCommand::Execute(operation, reply)) => {
+                        let _ = reply.send(self.execute(operation).await);
+                    }
+                    // Every handle was dropped, the app is closing.
+                    None => break,
+                },
+                Some(stop_reply) = self.stops.recv() => {
+                    self.handle_stop(stop_reply).await;
                 }
-                Err(errmsg) => Some(format!("{}", errmsg)),
-            },
-            Err(errmsg) => Some(format!("{}", errmsg)),
-        }
-    }
-
-    async fn move_dobot_to(&self, position: Position) -> Option<String> {
-        match Dobot::open(&self.dobotpath).await {
-            Ok(mut dobot) => {
-                set_dobot_params(&mut dobot, position.v.in_float, position.a.in_float)
-                    .await
-                    .unwrap();
-                match dobot
-                    .move_to(
-                        position.x.in_float,
-                        position.y.in_float,
-                        position.z.in_float,
-                        position.r.in_float,
-                    )
-                    .await
-                    .unwrap()
-                    .wait()
-                    .await
-                {
-                    Ok(_) => None,
-                    Err(errmsg) => Some(format!("{}", errmsg)),
+                _ = reconnect_timer.tick() => {
+                    if let ConnectionState::Faulted(_) = self.state {
+                        self.try_reconnect().await;
+                    }
                 }
             }
-            Err(errmsg) => Some(format!("{}", errmsg)),
-        }
-    }
-
-    async fn move_dobot_sequence(&self, sequence: Vec<Position>) -> Option<String> {
-        match Dobot::open(&self.dobotpath).await {
-            Ok(mut dobot) => {
-                for position in sequence {
-                    set_dobot_params(&mut dobot, position.v.in_float, position.a.in_float)
-                        .await
-                        .unwrap();
-                    match dobot
-                        .move_to(
-                            position.x.in_float,
-                            position.y.in_float,
-                            position.z.in_float,
-                            position.r.in_float,
-                        )
-                        .await
-                        .unwrap()
-                        .wait()
-                        .await
-                    {
-                        Ok(_) => {}
-                        Err(errmsg) => return Some(format!("{}", errmsg)),
+        }
+    }
+
+    async fn connect(&mut self, backend_kind: BackendKind) {
+        self.backend = None;
+        self.backend_kind = backend_kind;
+        if self.is_path_empty() {
+            self.set_state(ConnectionState::Disconnected).await;
+        } else {
+            let _ = self.ensure_connected().await;
+        }
+    }
+
+    async fn set_state(&mut self, state: ConnectionState) {
+        if self.state != state {
+            self.state = state.clone();
+            let _ = self.output.send(state).await;
+        }
+    }
+
+    fn is_path_empty(&self) -> bool {
+        match &self.backend_kind {
+            BackendKind::Serial(dobot_path) => dobot_path.is_empty(),
+            BackendKind::Simulated => false,
+        }
+    }
+
+    fn is_link_fault(&self, error: &DobotError) -> bool {
+        match self.backend_kind {
+            BackendKind::Serial(_) => matches!(
+                error,
+                DobotError::PortOpen(_) | DobotError::CommandRejected(_) | DobotError::Timeout(_)
+            ),
+            BackendKind::Simulated => false,
+        }
+    }
+
+    async fn ensure_connected(&mut self) -> Result<(), DobotError> {
+        if self.is_path_empty() {
+            return Err(DobotError::PathNotSet);
+        }
+        if self.backend.is_none() {
+            self.set_state(ConnectionState::Connecting).await;
+            match open_backend(&self.backend_kind).await {
+                Ok(backend) => {
+                    self.backend = Some(backend);
+                    self.set_state(ConnectionState::Connected).await;
+                }
+                Err(errmsg) => {
+                    self.set_state(ConnectionState::Faulted(errmsg.to_string())).await;
+                    return Err(errmsg);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Background reconnect while faulted. Does not report Connecting, to keep the header from flickering.
+    async fn try_reconnect(&mut self) {
+        if self.is_path_empty() {
+            return;
+        }
+        if let Ok(backend) = open_backend(&self.backend_kind).await {
+            log::info!("Reconnected to dobot {:?}", self.backend_kind);
+            self.backend = Some(backend);
+            self.set_state(ConnectionState::Connected).await;
+        }
+    }
+
+    /// Stops the arm if it is connected. A failed stop counts as a link fault, the port is reopened.
+    /// Operations queued behind the stopped one are answered with Stopped instead of being run.
+    async fn handle_stop(&mut self, stop_reply: StopReply) {
+        let stop_result = match self.backend.as_mut() {
+            Some(backend) => backend.stop().await.err(),
+            None => None,
+        };
+        if let Some(errmsg) = &stop_result {
+            self.backend = None;
+            self.set_state(ConnectionState::Faulted(errmsg.to_string())).await;
+        }
+        while let Ok(command) = self.commands.try_recv() {
+            match command {
+                DobotCommand::Execute(_, reply) => {
+                    let _ = reply.send(Err(DobotError::Stopped));
+                }
+                DobotCommand::Connect(backend_kind) => self.connect(backend_kind).await,
+            }
+        }
+        let _ = stop_reply.send(stop_result);
+    }
+
+    async fn execute(&mut self, operation: DobotOp) -> Result<DobotReply, DobotError> {
+        let mut attempt = 0;
+        loop {
+            self.ensure_connected().await?;
+            let backend = self.backend.as_mut().unwrap();
+            let outcome = tokio::select! {
+                outcome = run_operation(backend.as_mut(), operation) => outcome,
+                Some(stop_reply) = self.stops.recv() => {
+                    self.handle_stop(stop_reply).await;
+                    return Err(DobotError::Stopped);
+                }
+            };
+            match outcome {
+                Ok(reply) => return Ok(reply),
+                // Alarms and simulator refusals are answers from a working arm, reconnecting won't help.
+                Err(errmsg) if !self.is_link_fault(&errmsg) => return Err(errmsg),
+                Err(errmsg) => {
+                    self.backend = None;
+                    self.set_state(ConnectionState::Faulted(errmsg.to_string())).await;
+                    if attempt >= RECONNECT_RETRIES {
+                        return Err(errmsg);
                     }
+                    log::warn!("Dobot {:?} failed, reconnecting... {}", operation, errmsg);
+                    attempt += 1;
                 }
-                None
             }
-            Err(errmsg) => Some(format!("{}", errmsg)),
         }
     }
+}
+
+async fn run_operation(
+    backend: &mut dyn RobotBackend,
+    operation: DobotOp,
+) -> Result<DobotReply, DobotError> {
+    let timeout = tokio::time::Duration::from_millis(COMMAND_TIMEOUT_MS);
+    let operation_future = async {
+        match operation {
+            DobotOp::SetHome => backend.set_home().await.map(|_| DobotReply::Done),
+            DobotOp::SetParams(v, a) => backend
+                .set_ptp_coordinate_params(v, a)
+                .await
+                .map(|_| DobotReply::Done),
+            DobotOp::MoveTo(x, y, z, r, mode) => backend
+                .move_to(x, y, z, r, mode)
+                .await
+                .map(|_| DobotReply::Done),
+            DobotOp::SetJumpParams(jump_height, z_limit) => backend
+                .set_ptp_jump_params(jump_height, z_limit)
+                .await
+                .map(|_| DobotReply::Done),
+            DobotOp::MoveJoints(joints) => backend
+                .move_joints(joints)
+                .await
+                .map(|_| DobotReply::Done),
+            DobotOp::GetPose => backend.get_pose().await.map(DobotReply::Pose),
+        }
+    };
+    match tokio::time::timeout(timeout, operation_future).await {
+        Ok(reply) => reply,
+        Err(_) => Err(DobotError::Timeout(format!("{:?}", operation))),
+    }
+}
 
-    pub async fn _get_cur_pos(&self) -> (Option<dobot_fx24::Pose>, Option<String>) {
-        match Dobot::open(&self.dobotpath).await {
-            Ok(mut dobot) => match dobot.get_pose().await {
-                Ok(pose) => (Some(pose), None),
-                Err(errmsg) => (None, Some(format!("{}", errmsg))),
+impl DobotConnection {
+    /// Handle for the app plus the receiving ends, which go to dobot_connection_actor.
+    pub fn new() -> (Self, DobotReceivers) {
+        let (sender, commands) = mpsc::unbounded_channel();
+        let (stop_sender, stops) = mpsc::unbounded_channel();
+        (
+            Self {
+                sender,
+                stop_sender,
+                jump_height : 0.0,
+                jump_z_limit: 0.0,
             },
-            Err(errmsg) => (None, Some(format!("{}", errmsg))),
+            DobotReceivers { commands, stops },
+        )
+    }
+
+    /// Tells the actor which backend to use. Does not wait for it to open, watch ConnectionState instead.
+    pub fn connect(&self, backend_kind: BackendKind) {
+        let _ = self.sender.send(DobotCommand::Connect(backend_kind));
+    }
+
+    async fn request(&self, operation: DobotOp) -> Result<DobotReply, DobotError> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        if self
+            .sender
+            .send(DobotCommand::Execute(operation, reply_sender))
+            .is_err()
+        {
+            return Err(DobotError::PortOpen("Dobot connection closed.".to_string()));
+        }
+        match reply_receiver.await {
+            Ok(reply) => reply,
+            Err(_) => Err(DobotError::PortOpen("Dobot connection closed.".to_string())),
         }
     }
 
-    pub async fn test_connection(&self) -> Option<String> {
-        match Dobot::open(&self.dobotpath).await {
-            Ok(_) => None,
-            Err(errmsg) => Some(format!("{}", errmsg)),
+    /// Interrupts whatever the actor is doing and halts the arm.
+    pub async fn stop(&self) -> Option<DobotError> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        if self.stop_sender.send(reply_sender).is_err() {
+            return Some(DobotError::PortOpen("Dobot connection closed.".to_string()));
+        }
+        match reply_receiver.await {
+            Ok(stop_result) => stop_result,
+            Err(_) => Some(DobotError::PortOpen("Dobot connection closed.".to_string())),
         }
     }
-}
 
-async fn set_dobot_params<'a>(
-    internal_dobot: &'a mut Dobot,
-    v: f32,
-    a: f32,
-) -> Result<(), error::Error> {
-    internal_dobot
-        .set_ptp_coordinate_params(v, a)
-        .await
-        .unwrap()
-        .wait()
+    pub async fn go_home(&self) -> Option<DobotError> {
+        self.request(DobotOp::SetHome).await.err()
+    }
+
+    async fn move_dobot_to(&self, position: Position) -> Option<DobotError> {
+        if let Err(errmsg) = self
+            .request(DobotOp::SetParams(position.v.in_float, position.a.in_float))
+            .await
+        {
+            return Some(errmsg);
+        }
+        if position.mode == MotionMode::Jump {
+            if let Err(errmsg) = self
+                .request(DobotOp::SetJumpParams(self.jump_height, self.jump_z_limit))
+                .await
+            {
+                return Some(errmsg);
+            }
+        }
+        self.request(DobotOp::MoveTo(
+            position.x.in_float,
+            position.y.in_float,
+            position.z.in_float,
+            position.r.in_float,
+            position.mode,
+        ))
         .await
+        .err()
+    }
+
+    async fn set_params(&self, velocity: f32, acceleration: f32) -> Option<DobotError> {
+        self.request(DobotOp::SetParams(velocity, acceleration))
+            .await
+            .err()
+    }
+
+    pub async fn get_cur_pos(&self) -> (Option<RobotPose>, Option<DobotError>) {
+        match self.request(DobotOp::GetPose).await {
+            Ok(DobotReply::Pose(pose)) => (Some(pose), None),
+            Ok(DobotReply::Done) => (
+                None,
+                Some(DobotError::CommandRejected("Dobot did not return a pose.".to_string())),
+            ),
+            Err(errmsg) => (None, Some(errmsg)),
+        }
+    }
+
+    /// Asks for the pose, so a stale but still open port is detected too.
+    pub async fn test_connection(&self) -> Option<DobotError> {
+        self.request(DobotOp::GetPose).await.err()
+    }
 }
 
-pub async fn perform_sequences<'a>(
-    dobot_path: DobotPath,
-    sequences: Vec<Position>,
-) -> Option<String> {
-    if dobot_path.dobotpath.is_empty() {
-        return Some("Dobot path not set.".to_string());
+/// Plays the sequence one step at a time and reports progress as it goes.
+/// Repeats it as set in the NamedSequence, with its dwell between iterations.
+/// Pause/resume/stop requests sent through control are checked before every step and during waits,
+/// a move in progress is only interrupted by the emergency stop.
+/// The relay is only opened when the sequence has relay steps, and is released once playback ends.
+/// An empty sequence finishes right away, looping it would never get to a control check.
+pub fn play_sequence(
+    dobot: DobotConnection,
+    relay_path: Option<RelayPath>,
+    named_sequence: NamedSequence,
+    mut control: mpsc::UnboundedReceiver<PlaybackControl>,
+) -> impl Stream<Item = PlaybackEvent> {
+    iced::stream::channel(16, move |mut output| async move {
+        if named_sequence.steps.is_empty() {
+            let _ = output.send(PlaybackEvent::Finished).await;
+            return;
+        }
+        let has_relay_steps = named_sequence
+            .steps
+            .iter()
+            .any(|step| matches!(step, SequenceStep::Relay(_)));
+        let mut relay = match open_relay(relay_path.filter(|_| has_relay_steps)) {
+            Ok(relay) => relay,
+            Err(errmsg) => {
+                let _ = output.send(PlaybackEvent::Failed(0, errmsg)).await;
+                return;
+            }
+        };
+        play_steps(&dobot, &mut relay, &named_sequence, &mut control, &mut output).await;
+        release_held_relay(&mut relay);
+    })
+}
+
+async fn play_steps(
+    dobot: &DobotConnection,
+    relay: &mut Option<Box<dyn SerialPort>>,
+    named_sequence: &NamedSequence,
+    control: &mut mpsc::UnboundedReceiver<PlaybackControl>,
+    output: &mut futures_mpsc::Sender<PlaybackEvent>,
+) {
+    let total = named_sequence.steps.len();
+    let repeat_total = if named_sequence.is_infinite_loop {
+        None
+    } else {
+        Some(named_sequence.repeat_count)
+    };
+    let mut iteration = 0;
+    while repeat_total.is_none_or(|repeat_total| iteration < repeat_total) {
+        iteration += 1;
+        let _ = output
+            .send(PlaybackEvent::Iteration(iteration, repeat_total))
+            .await;
+        for (idx, step) in named_sequence.steps.iter().enumerate() {
+            let dwell_ms = if idx == 0 && iteration > 1 {
+                named_sequence.dwell_ms
+            } else {
+                0
+            };
+            if !wait_for_go(control, output, idx, dwell_ms).await {
+                return;
+            }
+
+            let _ = output.send(PlaybackEvent::Moving(idx, total)).await;
+            // Waits go through wait_for_go too, so they can be paused and stopped.
+            let step_result = match step {
+                SequenceStep::Wait(wait_ms) => {
+                    if !wait_for_go(control, output, idx, *wait_ms).await {
+                        return;
+                    }
+                    None
+                }
+                _ => run_step(dobot, relay, step).await,
+            };
+            if let Some(errmsg) = step_result {
+                let _ = output.send(PlaybackEvent::Failed(idx, errmsg)).await;
+                return;
+            }
+            let _ = output.send(PlaybackEvent::Reached(idx, total)).await;
+        }
     }
-    dobot_path.move_dobot_sequence(sequences).await
+    let _ = output.send(PlaybackEvent::Finished).await;
 }
 
-pub async fn perform_moveto<'a>(dobot_path: DobotPath, sequences: Position) -> Option<String> {
-    if dobot_path.dobotpath.is_empty() {
-        return Some("Dobot path not set.".to_string());
+/// Runs a single step to completion.
+/// Relay steps are skipped when no relay is open, e.g. in unconnected mode.
+async fn run_step(
+    dobot: &DobotConnection,
+    relay: &mut Option<Box<dyn SerialPort>>,
+    step: &SequenceStep,
+) -> Option<DobotError> {
+    match step {
+        SequenceStep::Move(position) => dobot.move_dobot_to(position.clone()).await,
+        SequenceStep::Wait(wait_ms) => {
+            _wait_n_ms(*wait_ms).await;
+            None
+        }
+        SequenceStep::Relay(signal) => match relay.as_mut() {
+            Some(relay) => toggle_relay(relay, *signal)
+                .map(|errmsg| DobotError::CommandRejected(format!("Relay: {}", errmsg))),
+            None => {
+                log::info!("No relay attached, skipping relay {}.", signal);
+                None
+            }
+        },
+        SequenceStep::Home => dobot.go_home().await,
+        SequenceStep::SetParams(velocity, acceleration) => {
+            dobot.set_params(*velocity, *acceleration).await
+        }
     }
-    dobot_path.move_dobot_to(sequences).await
 }
 
-pub async fn go_home<'a>(dobot_path: DobotPath) -> Option<String> {
-    if dobot_path.dobotpath.is_empty() {
-        return Some("Dobot path not set.".to_string());
+fn open_relay(relay_path: Option<RelayPath>) -> Result<Option<Box<dyn SerialPort>>, DobotError> {
+    relay_path
+        .map(|relay_path| relay_path.new())
+        .transpose()
+        .map_err(|errmsg| DobotError::PortOpen(format!("Relay: {}", errmsg)))
+}
+
+/// Leaves the relay off whichever way the steps ended.
+fn release_held_relay(relay: &mut Option<Box<dyn SerialPort>>) {
+    if let Some(relay) = relay.as_mut() {
+        if let Some(errmsg) = toggle_relay(relay, false) {
+            log::error!("Failed to release relay: {}", errmsg);
+        }
     }
-    dobot_path.go_home().await
 }
 
-pub async fn test_connection<'a>(dobot_path: DobotPath) -> Option<String> {
-    if dobot_path.dobotpath.is_empty() {
-        return Some("Dobot path not set.".to_string());
+/// Waits out the dwell while handling pause/resume/stop. Returns false when playback has to stop.
+/// Being paused can outlast the dwell, playback continues right after resuming in that case.
+async fn wait_for_go(
+    control: &mut mpsc::UnboundedReceiver<PlaybackControl>,
+    output: &mut futures_mpsc::Sender<PlaybackEvent>,
+    idx: usize,
+    dwell_ms: u64,
+) -> bool {
+    let dwell_end = tokio::time::Instant::now() + tokio::time::Duration::from_millis(dwell_ms);
+    let mut is_paused = false;
+    loop {
+        let request = if is_paused {
+            // A closed channel while paused means nobody can resume anymore.
+            control.recv().await.or(Some(PlaybackControl::Stop))
+        } else {
+            match tokio::time::timeout_at(dwell_end, control.recv()).await {
+                Ok(Some(request)) => Some(request),
+                Ok(None) => {
+                    tokio::time::sleep_until(dwell_end).await;
+                    None
+                }
+                Err(_) => None,
+            }
+        };
+        match request {
+            Some(PlaybackControl::Pause) if !is_paused => {
+                is_paused = true;
+                let _ = output.send(PlaybackEvent::Paused(idx)).await;
+            }
+            Some(PlaybackControl::Resume) if is_paused => {
+                is_paused = false;
+                let _ = output.send(PlaybackEvent::Resumed).await;
+            }
+            Some(PlaybackControl::Stop) => {
+                let _ = output.send(PlaybackEvent::Stopped(idx)).await;
+                return false;
+            }
+            Some(_) => {}
+            None => return true,
+        }
     }
-    dobot_path.test_connection().await
 }
 
+pub async fn perform_moveto<'a>(dobot: DobotConnection, sequences: Position) -> Option<DobotError> {
+    dobot.move_dobot_to(sequences).await
+}
+
+/// Moves by one jog step and reads back where the arm ended up.
+pub async fn jog_dobot(
+    dobot: DobotConnection,
+    target: JogTarget,
+) -> (Option<RobotPose>, Option<DobotError>) {
+    let erropt = match target {
+        JogTarget::Pose(position) => dobot.move_dobot_to(position).await,
+        JogTarget::Joints(joints) => dobot.request(DobotOp::MoveJoints(joints)).await.err(),
+    };
+    if let Some(errmsg) = erropt {
+        return (None, Some(errmsg));
+    }
+    dobot.get_cur_pos().await
+}
+
+pub async fn go_home<'a>(dobot: DobotConnection) -> Option<DobotError> {
+    dobot.go_home().await
+}
+
+pub async fn get_pose<'a>(dobot: DobotConnection) -> (Option<RobotPose>, Option<DobotError>) {
+    dobot.get_cur_pos().await
+}
+
+/// Stops the arm and forces the relay off at the same time.
+/// Relay is None in unconnected mode, same as draw_shape.
+pub async fn emergency_stop(
+    dobot: DobotConnection,
+    relay_path: Option<RelayPath>,
+) -> Option<DobotError> {
+    let (dobot_result, relay_result) = tokio::join!(dobot.stop(), async move {
+        match relay_path {
+            Some(relay_path) => release_relay(relay_path).await,
+            None => None,
+        }
+    });
+    if let (Some(_), Some(relay_errmsg)) = (&dobot_result, &relay_result) {
+        log::error!("Emergency stop, relay: {}", relay_errmsg);
+    }
+    dobot_result.or(relay_result)
+}
+
+/// The aborted drawing may hold the relay port for a moment after being cancelled, so opening is retried.
+async fn release_relay(relay_path: RelayPath) -> Option<DobotError> {
+    let mut last_errmsg = String::new();
+    for _ in 0..RELAY_RELEASE_RETRIES {
+        match relay_path.new() {
+            Ok(mut relay) => {
+                return toggle_relay(&mut relay, false)
+                    .map(|errmsg| DobotError::PortOpen(format!("Relay: {}", errmsg)))
+            }
+            Err(errmsg) => last_errmsg = format!("{}", errmsg),
+        }
+        _wait_n_ms(50).await;
+    }
+    Some(DobotError::PortOpen(format!("Relay: {}", last_errmsg)))
+}
+
+pub async fn test_connection<'a>(dobot: DobotConnection) -> Option<DobotError> {
+    dobot.test_connection().await
+}
+
+/// Runs the steps from ShapeDefinition::drawing_steps.
+/// Relay is optional so the same drawing runs against the simulator, where no relay is attached.
 pub async fn draw_shape(
-    dobot_path: DobotPath,
-    relay_path: RelayPath,
-    shape_to_draw: Shapes,
-    arm_speed: f32,
-    arm_accel: f32,
-) -> Option<String> {
-    log::info!(
-        "Drawing shape {:#?}, speed {}, acceleration {}",
-        shape_to_draw,
-        arm_speed,
-        arm_accel
-    );
-    let shape_motion_data = shape_to_draw.motion_data(arm_speed, arm_accel);
-    match relay_path.new() {
-        Ok(mut relay) => {
-            _wait_n_ms(2000).await;
-            if let Some(errmsg) = dobot_path
-                .move_dobot_to(shape_motion_data.last().unwrap().clone())
-                .await
-            {
-                return Some(format!("{}", errmsg));
-            };
-            _wait_n_ms(500).await;
-            toggle_relay(&mut relay, true);
-            if let Some(errmsg) = dobot_path.move_dobot_sequence(shape_motion_data).await {
-                return Some(format!("{}", errmsg));
-            };
-            _wait_n_ms(500).await;
-            toggle_relay(&mut relay, false);
-            _wait_n_ms(500).await;
-            if let Some(errmsg) = dobot_path
-                .move_dobot_to(Position::position(200.0, 0.0, 0.0, 0.0, 200.0, 200.0))
-                .await
-            {
-                return Some(format!("{}", errmsg));
-            };
+    dobot: DobotConnection,
+    relay_path: Option<RelayPath>,
+    shape_name: String,
+    drawing_steps: Vec<SequenceStep>,
+) -> Option<DobotError> {
+    log::info!("Drawing shape {}, {} steps", shape_name, drawing_steps.len());
+    let mut relay = match open_relay(relay_path) {
+        Ok(relay) => relay,
+        Err(errmsg) => return Some(errmsg),
+    };
+    for step in drawing_steps {
+        if let Some(errmsg) = run_step(&dobot, &mut relay, &step).await {
+            release_held_relay(&mut relay);
+            return Some(errmsg);
         }
-        Err(errmsg) => return Some(format!("{}", errmsg)),
     }
     None
 }
 
-pub async fn simulate_drawing<'a>() -> Option<String> {
-    _wait_n_ms(1500).await;
-    None
+#[cfg(test)]
+mod tests {
+    use iced::futures::{executor::block_on, StreamExt};
+
+    use super::*;
+
+    #[test]
+    fn empty_loop_finishes() {
+        let named_sequence: NamedSequence =
+            serde_json::from_str(r#"{ "name": "empty", "steps": [], "is_infinite_loop": true }"#)
+                .unwrap();
+        let (_control_sender, control) = mpsc::unbounded_channel();
+        let playback = play_sequence(DobotConnection::new().0, None, named_sequence, control);
+        let events = block_on(playback.collect::<Vec<_>>());
+        assert_eq!(events, [PlaybackEvent::Finished]);
+    }
 }
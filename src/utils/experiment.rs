@@ -1,38 +1,44 @@
-use rand::{distributions::Uniform, prelude::Distribution};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 
-use crate::{
-    ExperimentInstance, IcedDobotController, ResultExports, ResultItem, ShapeAnalysis, Shapes,
+use super::structs::{
+    CompletionStatus, Condition, ConditionAnalysis, ConditionSchedule, ConditionScheduling,
+    ExperimentAction, ExperimentInstance, ExperimentPlan, ExperimentProtocol, ExperimentSession,
+    ExperimentStage, ParameterItem, ParameterType,
+    Parameters, Randomization, RandomizationStrategy, ResultExports, ResultItem, ShapeAnalysis,
+    ShapeDefinition, Staircase, StaircaseResult, StaircaseRule, StaircaseTrack,
 };
 
-pub fn create_experiment_instance(appv: &IcedDobotController) -> ExperimentInstance {
-    let n_tests = appv
-        .active_experiment_parameters
-        .test_count
-        .value
-        .parse::<usize>()
-        .unwrap();
-
-    let shapes_to_use = appv
-        .active_experiment_shapes_to_use
-        .clone()
-        .iter()
-        .filter(|(_shape, is_use)| *is_use)
-        .map(|(shape, _)| shape.clone())
-        .collect::<Vec<Shapes>>();
-
-    let n_used_shapes = shapes_to_use.len();
-
-    let mut shapes_buffer = Vec::with_capacity(n_tests);
-    let between = Uniform::from(0..n_used_shapes);
-    let mut rng = rand::thread_rng();
-
-    for _ in 0..n_tests {
-        shapes_buffer.push(shapes_to_use[between.sample(&mut rng)])
-    }
+/// n_tests trials drawn from shapes_to_use as the randomization asks.
+pub fn create_experiment_instance(
+    shapes_to_use: Vec<ShapeDefinition>,
+    n_tests: usize,
+    randomization: Randomization,
+    rng: &mut impl Rng,
+) -> ExperimentInstance {
+    let n_shapes = shapes_to_use.len();
+    let no_repeat = randomization.no_immediate_repeat && n_shapes > 1;
+    let order = match randomization.strategy {
+        RandomizationStrategy::Uniform => uniform_order(n_shapes, n_tests, no_repeat, rng),
+        RandomizationStrategy::BalancedBlocks => {
+            balanced_block_order(n_shapes, n_tests, no_repeat, rng)
+        }
+        // Consecutive entries of a row always differ, also across the wrap around.
+        RandomizationStrategy::LatinSquare => {
+            latin_square_row(n_shapes, randomization.latin_square_row)
+                .into_iter()
+                .cycle()
+                .take(n_tests)
+                .collect()
+        }
+    };
 
     ExperimentInstance {
-        shapes_selection: shapes_to_use.clone(),
-        list_of_shapes: shapes_buffer,
+        randomization,
+        plan: None,
+        conditions: None,
+        staircase: None,
+        list_of_shapes: order.iter().map(|idx| shapes_to_use[*idx].clone()).collect(),
+        shapes_selection: shapes_to_use,
         list_of_guesses: Vec::with_capacity(n_tests),
         list_of_retries: vec![0; n_tests],
         list_of_time: Vec::with_capacity(n_tests),
@@ -40,73 +46,1128 @@ pub fn create_experiment_instance(appv: &IcedDobotController) -> ExperimentInsta
     }
 }
 
-pub async fn process_results<'a>(
-    appv: IcedDobotController,
-) -> (Option<ResultExports>, Option<String>) {
-    let result = appv.active_experiment_instance.clone().unwrap();
-    let counts = result.list_of_guesses.capacity();
+impl ExperimentInstance {
+    /// Practice block answered with the same shapes, None without practice trials.
+    pub fn practice(
+        shapes_selection: Vec<ShapeDefinition>,
+        practice_shapes: Vec<ShapeDefinition>,
+    ) -> Option<Self> {
+        if practice_shapes.is_empty() {
+            return None;
+        }
+        let n_practice = practice_shapes.len();
+        Some(Self {
+            randomization   : Randomization {
+                strategy: RandomizationStrategy::BalancedBlocks,
+                ..Randomization::default()
+            },
+            plan            : None,
+            conditions      : None,
+            staircase       : None,
+            shapes_selection,
+            list_of_shapes  : practice_shapes,
+            list_of_guesses : Vec::with_capacity(n_practice),
+            list_of_retries : vec![0; n_practice],
+            list_of_time    : Vec::with_capacity(n_practice),
+            experiment_index: 0,
+        })
+    }
+}
 
-    let mut total_time = 0;
-    let mut total_correct = 0;
+impl ExperimentInstance {
+    /// Staircase levels of the answered trials, None without a staircase.
+    pub fn staircase_track(&self) -> Option<StaircaseTrack> {
+        let staircase = self.staircase.as_ref()?;
+        let answers = self
+            .list_of_shapes
+            .iter()
+            .zip(self.list_of_guesses.iter())
+            .map(|(shape, guess)| shape.name == *guess)
+            .collect::<Vec<bool>>();
+        Some(staircase.track(&answers))
+    }
 
-    let mut analysis_vec = result
-        .shapes_selection
+    fn is_staircase_done(&self) -> bool {
+        match (&self.staircase, self.staircase_track()) {
+            (Some(staircase), Some(track)) => {
+                track.reversal_levels.len() >= staircase.reversals as usize
+            }
+            _ => false,
+        }
+    }
+
+    /// Drops the trials left, once a staircase has its reversals.
+    fn drop_unanswered(&mut self) {
+        let answered = self.list_of_guesses.len();
+        self.list_of_shapes.truncate(answered);
+        self.list_of_retries.truncate(answered);
+        if let Some(schedule) = self.conditions.as_mut() {
+            schedule.list_of_conditions.truncate(answered);
+        }
+    }
+}
+
+impl Staircase {
+    /// Every problem with the settings, empty when it can run.
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if matches!(
+            self.parameter,
+            ParameterType::SubjectName
+                | ParameterType::TestCount
+                | ParameterType::PracticeCount
+                | ParameterType::Seed
+                | ParameterType::ModulationType
+                | ParameterType::CarrierType
+        ) {
+            problems.push(format!(
+                "Staircase cannot vary {}.",
+                self.parameter.show_title_en()
+            ));
+        }
+        if self.min > self.start || self.start > self.max {
+            problems.push(format!(
+                "Staircase start {} not in {}..{}.",
+                self.start, self.min, self.max
+            ));
+        }
+        if self.step == 0 {
+            problems.push("Staircase step must not be 0.".to_string());
+        }
+        if self.discard_reversals >= self.reversals {
+            problems.push(format!(
+                "Staircase needs more than the {} discarded reversals.",
+                self.discard_reversals
+            ));
+        }
+        problems
+    }
+
+    /// The parameters with the varied one at the level.
+    pub fn apply(&self, parameters: &Parameters, level: u32) -> Parameters {
+        let mut varied = parameters.clone();
+        for item in varied
+            .iter_mut()
+            .filter(|item| item.parameter_type == self.parameter)
+        {
+            item.value = level.to_string();
+        }
+        varied
+    }
+
+    /// Replays the answers in order. Every wrong answer takes a step up, the rule's number of
+    /// correct ones in a row a step down, within min..max. A reversal is a step against the
+    /// direction of the one before.
+    pub fn track(&self, answers: &[bool]) -> StaircaseTrack {
+        let down_after = match self.rule {
+            StaircaseRule::OneUpTwoDown => 2,
+            StaircaseRule::OneUpThreeDown => 3,
+        };
+        let mut level = self.start.clamp(self.min, self.max) as i64;
+        let mut levels = Vec::with_capacity(answers.len() + 1);
+        levels.push(level as u32);
+        let mut reversal_levels = Vec::new();
+        let mut correct_run = 0;
+        let mut last_direction = None;
+        for is_correct in answers {
+            let direction = if *is_correct {
+                correct_run += 1;
+                if correct_run < down_after {
+                    None
+                } else {
+                    correct_run = 0;
+                    Some(-1)
+                }
+            } else {
+                correct_run = 0;
+                Some(1)
+            };
+            if let Some(direction) = direction {
+                if last_direction.is_some_and(|last| last != direction) {
+                    reversal_levels.push(level as u32);
+                }
+                last_direction = Some(direction);
+                level = (level + direction * self.step as i64).clamp(self.min as i64, self.max as i64);
+            }
+            levels.push(level as u32);
+        }
+        StaircaseTrack {
+            levels,
+            reversal_levels,
+        }
+    }
+
+    /// Mean of the reversal levels after the discarded ones, None without any.
+    pub fn threshold(&self, track: &StaircaseTrack) -> Option<f32> {
+        let counted = track
+            .reversal_levels
+            .iter()
+            .skip(self.discard_reversals as usize)
+            .collect::<Vec<&u32>>();
+        if counted.is_empty() {
+            return None;
+        }
+        Some(counted.iter().map(|level| **level as f32).sum::<f32>() / counted.len() as f32)
+    }
+}
+
+fn uniform_order(n_shapes: usize, n_tests: usize, no_repeat: bool, rng: &mut impl Rng) -> Vec<usize> {
+    let mut order: Vec<usize> = Vec::with_capacity(n_tests);
+    for _ in 0..n_tests {
+        let idx = match order.last() {
+            // Picks from the others, indices from the previous one on shift up by one.
+            Some(&previous) if no_repeat => {
+                let idx = rng.gen_range(0..n_shapes - 1);
+                if idx >= previous {
+                    idx + 1
+                } else {
+                    idx
+                }
+            }
+            _ => rng.gen_range(0..n_shapes),
+        };
+        order.push(idx);
+    }
+    order
+}
+
+fn balanced_block_order(
+    n_shapes: usize,
+    n_tests: usize,
+    no_repeat: bool,
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    let mut order: Vec<usize> = Vec::with_capacity(n_tests);
+    while order.len() < n_tests {
+        let mut block = (0..n_shapes).collect::<Vec<usize>>();
+        block.shuffle(rng);
+        // A block has no repeats inside, only the seam to the previous block can.
+        if no_repeat && order.last() == block.first() {
+            let swap_idx = rng.gen_range(1..block.len());
+            block.swap(0, swap_idx);
+        }
+        block.truncate(n_tests - order.len());
+        order.extend(block);
+    }
+    order
+}
+
+/// Row of a Williams design: across the rows every shape comes first equally often and
+/// follows every other shape equally often. Odd shape counts need 2 n rows instead of n,
+/// the second half mirrors the first.
+pub fn latin_square_row(n_shapes: usize, row: usize) -> Vec<usize> {
+    let n_rows = if n_shapes.is_multiple_of(2) { n_shapes } else { 2 * n_shapes };
+    let row = row % n_rows.max(1);
+    // 0, 1, n-1, 2, n-2, ... shifted by the row.
+    let mut sequence = (0..n_shapes)
+        .map(|j| match j {
+            0 => 0,
+            j if j % 2 == 1 => j.div_ceil(2),
+            j => n_shapes - j / 2,
+        })
+        .map(|base| (base + row) % n_shapes)
+        .collect::<Vec<usize>>();
+    if row >= n_shapes {
+        sequence.reverse();
+    }
+    sequence
+}
+
+impl Parameters {
+    /// Text parameters take anything, the seed must parse as u64 and the others as u32.
+    /// Empty input is always kept.
+    pub fn update(&mut self, param_type: ParameterType, invalue: String) -> Result<(), String> {
+        let is_text = matches!(
+            param_type,
+            ParameterType::SubjectName | ParameterType::ModulationType | ParameterType::CarrierType
+        );
+        if !invalue.is_empty() && param_type == ParameterType::Seed {
+            if invalue.parse::<u64>().is_err() {
+                return Err("Not parseable as u64.".to_string());
+            }
+        } else if !invalue.is_empty() && !is_text && invalue.parse::<u32>().is_err() {
+            return Err("Not parseable as u32.".to_string());
+        }
+        for item in self.iter_mut().filter(|param| param.parameter_type == param_type) {
+            item.value = invalue.clone();
+        }
+        Ok(())
+    }
+}
+
+impl Condition {
+    /// The entered parameters with the overrides, and a problem for every override not applied.
+    pub fn apply(&self, parameters: &Parameters) -> (Parameters, Vec<String>) {
+        let mut overridden = parameters.clone();
+        let mut problems = Vec::new();
+        for item in &self.overrides {
+            let result = match item.parameter_type {
+                ParameterType::SubjectName
+                | ParameterType::TestCount
+                | ParameterType::PracticeCount
+                | ParameterType::Seed => {
+                    Err("Set per session, not per condition.".to_string())
+                }
+                _ => overridden.update(item.parameter_type.clone(), item.value.clone()),
+            };
+            if let Err(errmsg) = result {
+                problems.push(format!(
+                    "Condition {}, {}: {}",
+                    self.name,
+                    item.parameter_type.show_title_en(),
+                    errmsg
+                ));
+            }
+        }
+        (overridden, problems)
+    }
+}
+
+/// Parameters of every condition, or only the entered ones without conditions.
+/// On error returns every problem found, missing parameters only once.
+pub fn condition_parameters(
+    parameters: &Parameters,
+    conditions: &[Condition],
+) -> Result<Vec<Parameters>, Vec<String>> {
+    let mut problems = Vec::new();
+    let mut parameter_sets = Vec::with_capacity(conditions.len().max(1));
+    if conditions.is_empty() {
+        problems = missing_parameters(parameters);
+        parameter_sets.push(parameters.clone());
+    }
+    for (idx, condition) in conditions.iter().enumerate() {
+        if conditions[..idx].iter().any(|other| other.name == condition.name) {
+            problems.push(format!("Condition name {} is used twice.", condition.name));
+        }
+        let (overridden, override_problems) = condition.apply(parameters);
+        problems.extend(override_problems);
+        for problem in missing_parameters(&overridden) {
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+        parameter_sets.push(overridden);
+    }
+    if problems.is_empty() {
+        Ok(parameter_sets)
+    } else {
+        Err(problems)
+    }
+}
+
+/// Parameters of every condition with their speed and acceleration.
+type ConditionMotions = (Vec<Parameters>, Vec<(f32, f32)>);
+
+/// Parameters and motion of every condition, checked against the trials.
+fn session_conditions(
+    parameters: &Parameters,
+    schedule: Option<&ConditionSchedule>,
+    n_tests: usize,
+) -> Result<ConditionMotions, Vec<String>> {
+    let Some(schedule) = schedule else {
+        let parameter_sets = condition_parameters(parameters, &[])?;
+        let motions = parameter_sets
+            .iter()
+            .map(motion_parameters)
+            .collect::<Result<Vec<(f32, f32)>, String>>()
+            .map_err(|e| vec![e])?;
+        return Ok((parameter_sets, motions));
+    };
+
+    let mut problems = Vec::new();
+    if schedule.conditions.is_empty() {
+        problems.push(format!("Protocol {} has no conditions.", schedule.protocol));
+    }
+    if schedule.list_of_conditions.len() != n_tests {
+        problems.push(format!(
+            "{} trial conditions for {} trials.",
+            schedule.list_of_conditions.len(),
+            n_tests
+        ));
+    }
+    for (idx, condition_idx) in schedule.list_of_conditions.iter().enumerate() {
+        if *condition_idx >= schedule.conditions.len() {
+            problems.push(format!("Trial {} has no condition {}.", idx + 1, condition_idx));
+        }
+    }
+    let parameter_sets = match condition_parameters(parameters, &schedule.conditions) {
+        Ok(parameter_sets) => parameter_sets,
+        Err(found) => {
+            problems.extend(found);
+            Vec::new()
+        }
+    };
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    let motions = parameter_sets
         .iter()
-        .map(|shape| ShapeAnalysis::new(shape.clone(), result.shapes_selection.clone()))
-        .collect::<Vec<ShapeAnalysis>>();
+        .map(motion_parameters)
+        .collect::<Result<Vec<(f32, f32)>, String>>()
+        .map_err(|e| vec![e])?;
+    Ok((parameter_sets, motions))
+}
 
-    let result_items: Vec<ResultItem> = result
-        .list_of_shapes
+/// Draws the trials of every condition on its own, each with its share of the test count and
+/// the randomization asked for, then orders the conditions as the protocol schedules them.
+/// No immediate repeat only holds within a condition when interleaved.
+fn schedule_conditions(
+    shapes_to_use: &[ShapeDefinition],
+    n_tests: usize,
+    randomization: Randomization,
+    protocol: &ExperimentProtocol,
+    rng: &mut impl Rng,
+) -> (Vec<ShapeDefinition>, Vec<usize>) {
+    let n_conditions = protocol.conditions.len();
+    let mut per_condition = (0..n_conditions)
+        .map(|idx| {
+            let n_condition_tests =
+                n_tests / n_conditions + usize::from(idx < n_tests % n_conditions);
+            create_experiment_instance(
+                shapes_to_use.to_vec(),
+                n_condition_tests,
+                randomization,
+                &mut *rng,
+            )
+            .list_of_shapes
+            .into_iter()
+        })
+        .collect::<Vec<_>>();
+    let mut list_of_conditions = per_condition
         .iter()
-        .zip(result.list_of_guesses.iter())
-        .zip(result.list_of_time.iter())
-        .zip(result.list_of_retries.iter())
-        .map(|(((real, guess), r_time), retries)| {
-            let _ = analysis_vec
-                .iter_mut()
-                .filter(|shape_analysis| shape_analysis.main_shape == *real)
-                .map(|shape_analysis| {
-                    classify_answer(*guess, shape_analysis, *r_time);
-                })
-                .collect::<Vec<_>>();
-            total_time += r_time;
-            (real, guess, r_time, retries)
+        .enumerate()
+        .flat_map(|(idx, shapes)| std::iter::repeat_n(idx, shapes.len()))
+        .collect::<Vec<usize>>();
+    if protocol.scheduling == ConditionScheduling::Interleaved {
+        list_of_conditions.shuffle(rng);
+    }
+    let list_of_shapes = list_of_conditions
+        .iter()
+        .filter_map(|idx| per_condition[*idx].next())
+        .collect();
+    (list_of_shapes, list_of_conditions)
+}
+
+/// A protocol runs either conditions or a staircase.
+fn protocol_problems(protocol: &ExperimentProtocol) -> Vec<String> {
+    match &protocol.staircase {
+        None if protocol.conditions.is_empty() => {
+            vec![format!("Protocol {} has no conditions.", protocol.name)]
+        }
+        None => Vec::new(),
+        Some(_) if !protocol.conditions.is_empty() => vec![format!(
+            "Protocol {} has both conditions and a staircase.",
+            protocol.name
+        )],
+        Some(staircase) => staircase.check(),
+    }
+}
+
+impl ExperimentPlan {
+    /// Checks the setup and draws the trial order. Without a seed in the parameters one is
+    /// drawn from rng, so the order can always be drawn again from the recorded seed.
+    /// With a protocol the test count is split across its conditions, see schedule_conditions,
+    /// or is the most trials its staircase may run.
+    /// Practice trials are drawn last in balanced blocks, so the subject sees every shape.
+    /// On error returns every problem found, so they can all be logged at once.
+    pub fn generate(
+        parameters: &Parameters,
+        shapes_to_use: &[(ShapeDefinition, bool)],
+        randomization: Randomization,
+        protocol: Option<&ExperimentProtocol>,
+        rng: &mut impl Rng,
+    ) -> Result<Self, Vec<String>> {
+        let staircase = protocol.and_then(|protocol| protocol.staircase.as_ref());
+        // The varied parameter may be left empty, the staircase sets it.
+        let parameters = &match staircase {
+            Some(staircase) => staircase.apply(parameters, staircase.start),
+            None => parameters.clone(),
+        };
+        let conditions = protocol.map_or(&[][..], |protocol| &protocol.conditions[..]);
+        let mut problems = condition_parameters(parameters, conditions)
+            .err()
+            .unwrap_or_default();
+        if let Some(protocol) = protocol {
+            problems.extend(protocol_problems(protocol));
+        }
+        let selection = shapes_to_use
+            .iter()
+            .filter(|(_, is_use)| *is_use)
+            .map(|(shape, _)| shape.clone())
+            .collect::<Vec<ShapeDefinition>>();
+        if selection.is_empty() {
+            problems.push("Please select some shapes!".to_string());
+        }
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        let n_tests =
+            parse_parameter(&parameters.test_count.value, "Test count").map_err(|e| vec![e])?;
+        if n_tests == 0 {
+            return Err(vec!["Test count must be at least 1.".to_string()]);
+        }
+        if (n_tests as usize) < conditions.len() {
+            return Err(vec![format!(
+                "Test count must be at least {}, one trial per condition.",
+                conditions.len()
+            )]);
+        }
+        let n_practice = match parameters.practice_count.value.is_empty() {
+            true => 0,
+            false => parse_parameter(&parameters.practice_count.value, "Practice count")
+                .map_err(|e| vec![e])?,
+        };
+        let seed = match parameters.seed.value.is_empty() {
+            true => rng.gen::<u64>(),
+            false => parameters
+                .seed
+                .value
+                .parse::<u64>()
+                .map_err(|_| vec![format!("Seed {} not parseable as u64.", parameters.seed.value)])?,
+        };
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (name, list_of_shapes, schedule) = match protocol {
+            Some(protocol) if !protocol.conditions.is_empty() => {
+                let (list_of_shapes, list_of_conditions) = schedule_conditions(
+                    &selection,
+                    n_tests as usize,
+                    randomization,
+                    protocol,
+                    &mut rng,
+                );
+                let schedule = ConditionSchedule {
+                    protocol          : protocol.name.clone(),
+                    scheduling        : protocol.scheduling,
+                    conditions        : protocol.conditions.clone(),
+                    list_of_conditions,
+                };
+                (
+                    format!("plan_{}_S{}_T{}", protocol.name, seed, n_tests),
+                    list_of_shapes,
+                    Some(schedule),
+                )
+            }
+            _ => (
+                match protocol {
+                    Some(protocol) => format!("plan_{}_S{}_T{}", protocol.name, seed, n_tests),
+                    None => format!("plan_S{}_T{}", seed, n_tests),
+                },
+                create_experiment_instance(
+                    selection.clone(),
+                    n_tests as usize,
+                    randomization,
+                    &mut rng,
+                )
+                .list_of_shapes,
+                None,
+            ),
+        };
+        let practice_randomization = Randomization {
+            strategy: RandomizationStrategy::BalancedBlocks,
+            ..randomization
+        };
+        let practice_shapes = create_experiment_instance(
+            selection.clone(),
+            n_practice as usize,
+            practice_randomization,
+            &mut rng,
+        )
+        .list_of_shapes;
+        Ok(Self {
+            name,
+            seed            : Some(seed),
+            randomization,
+            shapes_selection: selection,
+            list_of_shapes,
+            conditions      : schedule,
+            staircase       : staircase.cloned(),
+            practice_shapes,
+        })
+    }
+}
+
+impl ExperimentSession {
+    /// Draws a plan, see ExperimentPlan::generate, and starts it.
+    pub fn begin(
+        parameters: Parameters,
+        shapes_to_use: &[(ShapeDefinition, bool)],
+        randomization: Randomization,
+        protocol: Option<&ExperimentProtocol>,
+        rng: &mut impl Rng,
+    ) -> Result<Self, Vec<String>> {
+        let plan =
+            ExperimentPlan::generate(&parameters, shapes_to_use, randomization, protocol, rng)?;
+        Self::start(parameters, plan, None)
+    }
+
+    /// Runs an imported plan. Test count, practice count and seed in the parameters are replaced
+    /// by the plan's.
+    pub fn from_plan(parameters: Parameters, plan: ExperimentPlan) -> Result<Self, Vec<String>> {
+        let plan_name = Some(plan.name.clone());
+        Self::start(parameters, plan, plan_name)
+    }
+
+    fn start(
+        mut parameters: Parameters,
+        plan: ExperimentPlan,
+        plan_name: Option<String>,
+    ) -> Result<Self, Vec<String>> {
+        let n_tests = plan.list_of_shapes.len();
+        parameters.test_count.value = n_tests.to_string();
+        parameters.practice_count.value = plan.practice_shapes.len().to_string();
+        parameters.seed.value = plan.seed.map(|seed| seed.to_string()).unwrap_or_default();
+        // The entered parameters hold where the staircase starts.
+        if let Some(staircase) = &plan.staircase {
+            parameters = staircase.apply(&parameters, staircase.start);
+        }
+
+        let checked = session_conditions(&parameters, plan.conditions.as_ref(), n_tests);
+        let mut problems = match &checked {
+            Err(found) => found.clone(),
+            Ok(_) => Vec::new(),
+        };
+        if let Some(staircase) = &plan.staircase {
+            problems.extend(staircase.check());
+            if plan.conditions.is_some() {
+                problems.push("Plan has both conditions and a staircase.".to_string());
+            }
+        }
+        if n_tests == 0 {
+            problems.push("Plan has no trials.".to_string());
+        }
+        for (idx, shape) in plan.list_of_shapes.iter().enumerate() {
+            if !plan.shapes_selection.iter().any(|answer| answer.name == shape.name) {
+                problems.push(format!(
+                    "Plan trial {} ({}) is not among its answers.",
+                    idx + 1,
+                    shape.name
+                ));
+            }
+        }
+        for (idx, shape) in plan.practice_shapes.iter().enumerate() {
+            if !plan.shapes_selection.iter().any(|answer| answer.name == shape.name) {
+                problems.push(format!(
+                    "Plan practice trial {} ({}) is not among its answers.",
+                    idx + 1,
+                    shape.name
+                ));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+        let (condition_parameters, motions) = checked?;
+
+        Ok(Self {
+            stage       : ExperimentStage::BeginTiming,
+            practice    : ExperimentInstance::practice(
+                plan.shapes_selection.clone(),
+                plan.practice_shapes,
+            ),
+            instance    : ExperimentInstance {
+                randomization   : plan.randomization,
+                plan            : plan_name,
+                conditions      : plan.conditions,
+                staircase       : plan.staircase,
+                shapes_selection: plan.shapes_selection,
+                list_of_shapes  : plan.list_of_shapes,
+                list_of_guesses : Vec::with_capacity(n_tests),
+                list_of_retries : vec![0; n_tests],
+                list_of_time    : Vec::with_capacity(n_tests),
+                experiment_index: 0,
+            },
+            parameters,
+            condition_parameters,
+            motions,
+            confirmed_setup: None,
+            time_start  : None,
         })
-        .fold(Vec::new(), |mut accu, (real, guess, r_time, retries)| {
-            accu.push(ResultItem {
-                time       : *r_time,
-                true_shape : *real,
-                guess_shape: *guess,
-                retries    : *retries,
-                is_correct : {
-                    if real == guess {
-                        total_correct += 1;
-                        true
-                    } else {
-                        false
+    }
+
+    /// Continues after the answered trials, practice ones first. With every trial answered,
+    /// or a staircase's reversals reached, there is nothing left to run, the session only
+    /// holds the results then.
+    /// Conditions set up by hand are asked for again, they may have changed meanwhile.
+    pub fn resume(
+        parameters: Parameters,
+        mut practice: Option<ExperimentInstance>,
+        mut instance: ExperimentInstance,
+    ) -> Result<Self, String> {
+        let (condition_parameters, motions) = session_conditions(
+            &parameters,
+            instance.conditions.as_ref(),
+            instance.list_of_shapes.len(),
+        )
+        .map_err(|problems| problems.join(" "))?;
+        if let Some(practice) = practice.as_mut() {
+            practice.experiment_index = practice
+                .list_of_guesses
+                .len()
+                .min(practice.list_of_shapes.len().saturating_sub(1));
+        }
+        if instance.is_staircase_done() {
+            instance.drop_unanswered();
+        }
+        let answered = instance.list_of_guesses.len();
+        let stage = if answered < instance.list_of_shapes.len() {
+            instance.experiment_index = answered;
+            ExperimentStage::BeginTiming
+        } else {
+            instance.experiment_index = answered.saturating_sub(1);
+            ExperimentStage::NotInExperiment
+        };
+        Ok(Self {
+            stage,
+            instance,
+            practice,
+            parameters,
+            condition_parameters,
+            motions,
+            confirmed_setup: None,
+            time_start  : None,
+        })
+    }
+
+    /// Whether the current trial is a practice one, also while its feedback is shown.
+    pub fn is_practice(&self) -> bool {
+        self.stage == ExperimentStage::PracticeFeedback
+            || self
+                .practice
+                .as_ref()
+                .is_some_and(|practice| {
+                    practice.list_of_guesses.len() < practice.list_of_shapes.len()
+                })
+    }
+
+    /// The practice trials until every one is answered, then the recorded ones.
+    fn active_instance(&self) -> &ExperimentInstance {
+        match &self.practice {
+            Some(practice) if practice.list_of_guesses.len() < practice.list_of_shapes.len() => {
+                practice
+            }
+            _ => &self.instance,
+        }
+    }
+
+    fn active_instance_mut(&mut self) -> &mut ExperimentInstance {
+        match self.practice.as_mut() {
+            Some(practice) if practice.list_of_guesses.len() < practice.list_of_shapes.len() => {
+                practice
+            }
+            _ => &mut self.instance,
+        }
+    }
+
+    pub fn current_shape(&self) -> &ShapeDefinition {
+        let instance = self.active_instance();
+        &instance.list_of_shapes[instance.experiment_index]
+    }
+
+    /// Shape of the practice trial just answered and whether the answer was right,
+    /// None unless its feedback is shown.
+    pub fn practice_feedback(&self) -> Option<(&ShapeDefinition, bool)> {
+        if self.stage != ExperimentStage::PracticeFeedback {
+            return None;
+        }
+        let practice = self.practice.as_ref()?;
+        let index = practice.list_of_guesses.len().checked_sub(1)?;
+        let shape = &practice.list_of_shapes[index];
+        Some((shape, practice.list_of_guesses[index] == shape.name))
+    }
+
+    /// Index into condition_parameters, 0 without a protocol.
+    /// Practice runs under the condition of the first recorded trial.
+    fn condition_index(&self) -> usize {
+        let trial = if self.is_practice() {
+            0
+        } else {
+            self.instance.experiment_index
+        };
+        self.instance
+            .conditions
+            .as_ref()
+            .and_then(|schedule| schedule.list_of_conditions.get(trial))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Condition of the current trial, None without a protocol.
+    pub fn current_condition(&self) -> Option<&Condition> {
+        let schedule = self.instance.conditions.as_ref()?;
+        schedule.conditions.get(self.condition_index())
+    }
+
+    /// Level the staircase is at for the current trial, None without a staircase.
+    /// Practice runs at the start level.
+    pub fn staircase_level(&self) -> Option<u32> {
+        let track = self.instance.staircase_track()?;
+        if self.is_practice() {
+            return track.levels.first().copied();
+        }
+        track.levels.last().copied()
+    }
+
+    /// Parameters of the current trial, the condition's with the staircase level on top.
+    fn trial_parameters(&self) -> Parameters {
+        let parameters = &self.condition_parameters[self.condition_index()];
+        match (&self.instance.staircase, self.staircase_level()) {
+            (Some(staircase), Some(level)) => staircase.apply(parameters, level),
+            _ => parameters.clone(),
+        }
+    }
+
+    /// What the current trial runs under, the condition or the staircase level.
+    /// None without a protocol.
+    pub fn current_setup(&self) -> Option<Condition> {
+        match (&self.instance.staircase, self.staircase_level()) {
+            (Some(staircase), Some(level)) => Some(Condition {
+                name     : "Staircase・階段法".to_string(),
+                overrides: vec![ParameterItem {
+                    parameter_type: staircase.parameter.clone(),
+                    value         : level.to_string(),
+                }],
+            }),
+            _ => self.current_condition().cloned(),
+        }
+    }
+
+    /// Speed and acceleration of the current trial.
+    pub fn current_motion(&self) -> (f32, f32) {
+        motion_parameters(&self.trial_parameters()).unwrap_or(self.motions[self.condition_index()])
+    }
+
+    /// Every speed and acceleration the session may draw with, a staircase on speed or
+    /// acceleration included at both ends of its range.
+    pub fn possible_motions(&self) -> Vec<(f32, f32)> {
+        let mut motions = self.motions.clone();
+        if let Some(staircase) = &self.instance.staircase {
+            for level in [staircase.min, staircase.max] {
+                let parameters = staircase.apply(&self.parameters, level);
+                if let Ok(motion) = motion_parameters(&parameters) {
+                    motions.push(motion);
+                }
+            }
+        }
+        motions
+    }
+
+    /// Whether the current trial changes a parameter the app does not drive, compared to the
+    /// setup confirmed last. The first such setup always needs confirming.
+    fn needs_condition_setup(&self) -> bool {
+        let Some(setup) = self.current_setup() else {
+            return false;
+        };
+        let current = self.trial_parameters();
+        match &self.confirmed_setup {
+            Some(confirmed) => current
+                .into_iter()
+                .zip(confirmed.into_iter())
+                .any(|(item, confirmed_item)| {
+                    !Condition::is_driven(&item.parameter_type)
+                        && item.value != confirmed_item.value
+                }),
+            None => setup
+                .overrides
+                .iter()
+                .any(|item| !Condition::is_driven(&item.parameter_type)),
+        }
+    }
+
+    /// Under a staircase the most trials it may take, it stops on its reversals.
+    pub fn trial_count(&self) -> usize {
+        self.instance.list_of_shapes.len()
+    }
+
+    pub fn answered_count(&self) -> usize {
+        self.instance.list_of_guesses.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.answered_count() >= self.trial_count()
+    }
+
+    /// BeginTiming starts the trial, unless its condition has to be set up first.
+    /// Preparation asks for the shape to be drawn. PracticeFeedback goes on to the next trial.
+    pub fn advance(&mut self) -> ExperimentAction {
+        match self.stage {
+            ExperimentStage::PracticeFeedback => {
+                self.stage = ExperimentStage::BeginTiming;
+                self.advance()
+            }
+            ExperimentStage::BeginTiming => {
+                self.time_start = None;
+                if self.needs_condition_setup() {
+                    if let Some(condition) = self.current_setup() {
+                        self.stage = ExperimentStage::ConditionSetup;
+                        return ExperimentAction::AwaitConditionSetup(condition);
                     }
-                },
-            });
-            accu
-        });
+                }
+                self.stage = ExperimentStage::Preparation;
+                ExperimentAction::Advance
+            }
+            ExperimentStage::Preparation => ExperimentAction::Draw(self.current_shape().clone()),
+            _ => ExperimentAction::Ignored,
+        }
+    }
 
-    let _ = analysis_vec
-        .iter_mut()
-        .map(|analysis| analysis.calc_avg_time())
-        .collect::<Vec<_>>();
+    /// The answer timer starts with the first drawing of a trial and keeps running over retries.
+    pub fn drawing_finished(&mut self, now_ms: u128) -> ExperimentAction {
+        if self.stage != ExperimentStage::Preparation {
+            return ExperimentAction::Ignored;
+        }
+        self.time_start.get_or_insert(now_ms);
+        self.stage = ExperimentStage::Answering;
+        ExperimentAction::AwaitAnswer
+    }
+
+    /// A practice answer is followed by its feedback, see practice_feedback.
+    pub fn answer(&mut self, guess: String, now_ms: u128) -> ExperimentAction {
+        if self.stage != ExperimentStage::Answering {
+            return ExperimentAction::Ignored;
+        }
+        let time_start = self.time_start.unwrap_or(now_ms);
+        if self.is_practice() {
+            let practice = self.active_instance_mut();
+            let is_correct = practice.list_of_shapes[practice.experiment_index].name == guess;
+            practice.list_of_guesses.push(guess);
+            practice.list_of_time.push(now_ms.saturating_sub(time_start));
+            if practice.experiment_index + 1 < practice.list_of_shapes.len() {
+                practice.experiment_index += 1;
+            }
+            self.stage = ExperimentStage::PracticeFeedback;
+            return ExperimentAction::PracticeFeedback(is_correct);
+        }
+        self.instance.list_of_guesses.push(guess);
+        self.instance
+            .list_of_time
+            .push(now_ms.saturating_sub(time_start));
+
+        if self.instance.experiment_index + 1 >= self.trial_count()
+            || self.instance.is_staircase_done()
+        {
+            self.instance.drop_unanswered();
+            self.stage = ExperimentStage::NotInExperiment;
+            ExperimentAction::Finished
+        } else {
+            self.instance.experiment_index += 1;
+            self.stage = ExperimentStage::BeginTiming;
+            ExperimentAction::Advance
+        }
+    }
+
+    /// The experimenter has set up the current condition, the trial goes on.
+    pub fn confirm_condition(&mut self) -> ExperimentAction {
+        if self.stage != ExperimentStage::ConditionSetup {
+            return ExperimentAction::Ignored;
+        }
+        self.confirmed_setup = Some(self.trial_parameters());
+        self.stage = ExperimentStage::Preparation;
+        ExperimentAction::Advance
+    }
+
+    /// Draws the same shape again.
+    pub fn retry(&mut self) -> ExperimentAction {
+        if self.stage != ExperimentStage::Answering {
+            return ExperimentAction::Ignored;
+        }
+        let instance = self.active_instance_mut();
+        instance.list_of_retries[instance.experiment_index] += 1;
+        self.stage = ExperimentStage::Preparation;
+        ExperimentAction::Advance
+    }
+
+    pub fn results(&self) -> ResultExports {
+        let mut results = build_results(&self.parameters, &self.instance);
+        results.practice = self
+            .practice
+            .as_ref()
+            .map(result_items)
+            .unwrap_or_default();
+        results
+    }
+
+    /// Results of the trials answered so far, marked aborted with the reason unless every
+    /// trial was answered. None before the first recorded answer, there is nothing to save then.
+    pub fn aborted_results(&self, reason: String) -> Option<ResultExports> {
+        if self.answered_count() == 0 {
+            return None;
+        }
+        let mut results = self.results();
+        if results.status != CompletionStatus::Complete {
+            results.status = CompletionStatus::Aborted(reason);
+        }
+        Some(results)
+    }
+}
+
+/// Every required parameter left empty.
+fn missing_parameters(parameters: &Parameters) -> Vec<String> {
+    parameters
+        .into_iter()
+        .filter(|item| !item.parameter_type.is_optional() && item.value.is_empty())
+        .map(|item| format!("{} is empty", item.parameter_type.show_title(false)))
+        .collect()
+}
+
+fn parse_parameter(item_value: &str, what: &str) -> Result<u32, String> {
+    item_value
+        .parse::<u32>()
+        .map_err(|_| format!("{} {} not parseable as u32.", what, item_value))
+}
+
+/// Speed and acceleration the shapes are drawn with.
+fn motion_parameters(parameters: &Parameters) -> Result<(f32, f32), String> {
+    Ok((
+        parse_parameter(&parameters.speed.value, "Speed")? as f32,
+        parse_parameter(&parameters.acceleration.value, "Acceleration")? as f32,
+    ))
+}
+
+/// Scores the answered trials, shapes are compared by name, overall and per condition.
+/// Trials without an answer are left out and the results marked aborted, see aborted_results.
+/// Practice trials are not in the instance, see ExperimentSession::results.
+pub fn build_results(parameters: &Parameters, instance: &ExperimentInstance) -> ResultExports {
+    let shape_names = instance
+        .shapes_selection
+        .iter()
+        .map(|shape| shape.name.clone())
+        .collect::<Vec<String>>();
+    let result_items = result_items(instance);
 
-    let final_result = ResultExports {
-        parameters: appv.active_experiment_parameters.clone(),
-        avg_time: (total_time / counts as u128),
+    let (counts, avg_time, total_correct, analysis_vec) =
+        score_trials(&shape_names, result_items.iter());
+
+    let condition_analyses = match &instance.conditions {
+        None => Vec::new(),
+        Some(schedule) => schedule
+            .conditions
+            .iter()
+            .map(|condition| {
+                let (trial_count, avg_time, total_correct, analyses) = score_trials(
+                    &shape_names,
+                    result_items
+                        .iter()
+                        .filter(|item| item.condition.as_ref() == Some(&condition.name)),
+                );
+                ConditionAnalysis {
+                    condition          : condition.name.clone(),
+                    parameters         : condition.apply(parameters).0,
+                    trial_count,
+                    avg_time,
+                    avg_correct_answers: total_correct,
+                    analyses,
+                }
+            })
+            .collect(),
+    };
+
+    ResultExports {
+        parameters: parameters.clone(),
+        avg_time,
         avg_correct_answers: total_correct,
-        analyses: analysis_vec.clone(),
+        analyses: analysis_vec,
         results: result_items,
-    };
+        randomization: instance.randomization,
+        plan: instance.plan.clone(),
+        protocol: instance
+            .conditions
+            .as_ref()
+            .map(|schedule| schedule.protocol.clone()),
+        condition_analyses,
+        practice: Vec::new(),
+        staircase: instance.staircase.as_ref().zip(instance.staircase_track()).map(
+            |(staircase, track)| StaircaseResult {
+                staircase      : staircase.clone(),
+                threshold      : staircase.threshold(&track),
+                is_converged   : track.reversal_levels.len() >= staircase.reversals as usize,
+                reversal_levels: track.reversal_levels,
+            },
+        ),
+        status: if (counts as usize) < instance.list_of_shapes.len() {
+            CompletionStatus::Aborted("Stopped before the last trial.".to_string())
+        } else {
+            CompletionStatus::Complete
+        },
+    }
+}
+
+/// Every answered trial, tagged with its condition and staircase level.
+fn result_items(instance: &ExperimentInstance) -> Vec<ResultItem> {
+    let condition_names = instance
+        .conditions
+        .as_ref()
+        .map(|schedule| {
+            schedule
+                .list_of_conditions
+                .iter()
+                .map(|idx| schedule.conditions.get(*idx).map(|condition| condition.name.clone()))
+                .collect::<Vec<Option<String>>>()
+        })
+        .unwrap_or_default();
+    let levels = instance
+        .staircase_track()
+        .map(|track| track.levels)
+        .unwrap_or_default();
+
+    instance
+        .list_of_shapes
+        .iter()
+        .map(|shape| &shape.name)
+        .zip(instance.list_of_guesses.iter())
+        .zip(instance.list_of_time.iter())
+        .zip(instance.list_of_retries.iter())
+        .enumerate()
+        .map(|(idx, (((real, guess), r_time), retries))| ResultItem {
+            time       : *r_time,
+            true_shape : real.clone(),
+            guess_shape: guess.clone(),
+            retries    : *retries,
+            is_correct : real == guess,
+            condition  : condition_names.get(idx).cloned().flatten(),
+            level      : levels.get(idx).copied(),
+        })
+        .collect()
+}
+
+/// Trial count, average time, correct answer count and per shape analyses of the trials.
+fn score_trials<'a>(
+    shape_names: &[String],
+    result_items: impl Iterator<Item = &'a ResultItem>,
+) -> (u32, u128, u32, Vec<ShapeAnalysis>) {
+    let mut analysis_vec = shape_names
+        .iter()
+        .map(|name| ShapeAnalysis::new(name.clone(), shape_names.to_vec()))
+        .collect::<Vec<ShapeAnalysis>>();
+    let mut counts = 0;
+    let mut total_time = 0;
+    let mut total_correct = 0;
 
-    let final_string = serde_json::to_string_pretty(&final_result).unwrap();
+    for item in result_items {
+        for shape_analysis in analysis_vec
+            .iter_mut()
+            .filter(|shape_analysis| shape_analysis.main_shape == item.true_shape)
+        {
+            classify_answer(&item.guess_shape, shape_analysis, item.time);
+        }
+        counts += 1;
+        total_time += item.time;
+        if item.is_correct {
+            total_correct += 1;
+        }
+    }
 
-    let save_file_name = format!(
-        "N{}_T{}_V{}_A{}_S{}_MT{}_MF{}_CT{}_CF{}",
+    for analysis in analysis_vec.iter_mut() {
+        analysis.calc_avg_time();
+    }
+    (counts, total_time / counts.max(1) as u128, total_correct, analysis_vec)
+}
+
+/// File name without extension, made from the parameters.
+pub fn result_file_stem(final_result: &ResultExports) -> String {
+    format!(
+        "N{}_T{}_V{}_A{}_S{}_MT{}_MF{}_CT{}_CF{}{}{}",
         final_result.parameters.subject_name.value,
         final_result.parameters.test_count.value,
         final_result.parameters.voltage.value,
@@ -116,40 +1177,57 @@ pub async fn process_results<'a>(
         final_result.parameters.modulation_frequency.value,
         final_result.parameters.carrier_type.value,
         final_result.parameters.carrier_frequency.value,
-    );
+        final_result
+            .protocol
+            .as_ref()
+            .map(|protocol| format!("_P{}", protocol))
+            .unwrap_or_default(),
+        match final_result.status {
+            CompletionStatus::Complete => "",
+            CompletionStatus::Aborted(_) => "_partial",
+        },
+    )
+}
 
-    let results_dir = &appv.active_config.results_path;
+/// Writes the results into the results folder, adding _D1, _D2... instead of overwriting.
+pub async fn save_results(
+    results_dir: String,
+    final_result: ResultExports,
+) -> (Option<ResultExports>, Option<String>) {
+    let final_string = match serde_json::to_string_pretty(&final_result) {
+        Ok(final_string) => final_string,
+        Err(errmsg) => return (None, Some(format!("{}", errmsg))),
+    };
 
-    let save_file_path = format!("{}/{}", results_dir, save_file_name);
+    let save_file_path = format!("{}/{}", results_dir, result_file_stem(&final_result));
 
     let mut final_path = save_file_path.clone();
     let mut ndup: u32 = 1;
 
     while tokio::fs::try_exists(format!("{}.json", final_path))
         .await
-        .unwrap()
+        .unwrap_or(false)
     {
         final_path = format!("{}_D{}", save_file_path, ndup);
         ndup += 1;
     }
 
-    match tokio::fs::write(final_path, final_string).await {
+    match tokio::fs::write(format!("{}.json", final_path), final_string).await {
         Ok(_) => (Some(final_result), None),
         Err(errmsg) => (None, Some(format!("{}", errmsg))),
     }
 }
 
-pub fn classify_answer(guess: Shapes, analysis: &mut ShapeAnalysis, guesstime: u128) {
-    if guess == analysis.main_shape {
+pub fn classify_answer(guess: &String, analysis: &mut ShapeAnalysis, guesstime: u128) {
+    if *guess == analysis.main_shape {
         analysis.main_shape_count += 1;
     } else {
         let _ = analysis
             .wrong_shapes
             .iter_mut()
-            .filter(|(shape, _)| *shape == guess)
+            .filter(|(shape, _)| shape == guess)
             .map(|(_, shape_count)| *shape_count += 1)
             .collect::<Vec<_>>();
     }
     analysis.time += guesstime;
 }
-
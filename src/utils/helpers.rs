@@ -1,6 +1,4 @@
-use crate::{Config, LogType};
-
-use super::structs::LogMessage;
+use super::structs::{Config, LogMessage, LogType, ShapeDefinition};
 
 const ERRBUFSIZE: usize = 20;
 
@@ -24,6 +22,17 @@ pub async fn save_str_to_json(
     }
 }
 
+/// Writes the configuration back to .config.json, so settings changed in the GUI survive a restart.
+pub async fn save_config(config: Config) -> Option<String> {
+    match serde_json::to_string_pretty(&config) {
+        Ok(config_text) => match tokio::fs::write("./.config.json", config_text).await {
+            Ok(_) => None,
+            Err(errmsg) => Some(format!("{}", errmsg)),
+        },
+        Err(errmsg) => Some(format!("{}", errmsg)),
+    }
+}
+
 /// Used to update available list of contents of given directory. Format (Vec<String>, Error)
 pub async fn update_dir_lists(file_path: String) -> (Option<Vec<String>>, Option<String>) {
     match tokio::fs::read_dir(file_path).await {
@@ -38,6 +47,52 @@ pub async fn update_dir_lists(file_path: String) -> (Option<Vec<String>>, Option
     }
 }
 
+/// Loads every shape definition in the shape library directory, sorted by file name.
+/// Files that fail to parse are skipped and reported in the error, the rest is still returned.
+/// Shapes are told apart by name, so a name already loaded from an earlier file is skipped too.
+pub async fn load_shape_library(
+    shapes_path: String,
+) -> (Option<Vec<ShapeDefinition>>, Option<String>) {
+    let mut shape_paths = match update_dir_lists(shapes_path).await {
+        (Some(shape_paths), _) => shape_paths,
+        (None, errmsg) => return (None, errmsg),
+    };
+    shape_paths.sort();
+
+    let mut shapes: Vec<ShapeDefinition> = Vec::new();
+    let mut errors = Vec::new();
+    for shape_path in shape_paths.into_iter().filter(|path| path.ends_with(".json")) {
+        match tokio::fs::read_to_string(&shape_path).await {
+            Ok(contents) => match serde_json::from_str::<ShapeDefinition>(&contents) {
+                Ok(mut shape) => {
+                    if let Err(errmsg) = shape.expand_generator() {
+                        errors.push(format!("{}: {}", shape_path, errmsg));
+                    } else if shape.vertices.is_empty() {
+                        errors.push(format!("{} has no vertices", shape_path));
+                    } else if shapes.iter().any(|loaded| loaded.name == shape.name) {
+                        errors.push(format!(
+                            "{}: shape name {} is already taken",
+                            shape_path, shape.name
+                        ));
+                    } else {
+                        shapes.push(shape);
+                    }
+                }
+                Err(errmsg) => errors.push(format!("{}: {}", shape_path, errmsg)),
+            },
+            Err(errmsg) => errors.push(format!("{}: {}", shape_path, errmsg)),
+        }
+    }
+    (
+        Some(shapes),
+        if errors.is_empty() {
+            None
+        } else {
+            Some(errors.join(", "))
+        },
+    )
+}
+
 /// Function for adding an error message to an error array (Used for displaying error history in GUI, not terminal)
 pub fn append_log(error_array: &mut Vec<LogMessage>, log_type: LogType, log_message: String) {
     if error_array.is_empty() {
@@ -61,7 +116,8 @@ pub fn append_log(error_array: &mut Vec<LogMessage>, log_type: LogType, log_mess
 }
 
 /// Loads check if config exists and attempt to create the folders in the config
-pub fn configure_startup() {
+/// Returns the configuration, the GUI reads it again on its own.
+pub fn configure_startup() -> Config {
     // Load config or create one.
     let configuration = match std::fs::read_to_string("./.config.json") {
         Ok(contents) => match serde_json::from_str(&contents) {
@@ -104,11 +160,39 @@ pub fn configure_startup() {
         &configuration.font_path,
         &configuration.results_path,
         &configuration.sequences_path,
+        &configuration.shapes_path,
+        &configuration.plans_path,
+        &configuration.protocols_path,
     ];
 
     for folder_path in paths {
         create_dir_or_die(&folder_path);
     }
+
+    seed_shape_library(&configuration.shapes_path);
+    configuration
+}
+
+/// Writes the built-in shapes into an empty shape library, so a fresh install has something to draw.
+fn seed_shape_library(shapes_path: &String) {
+    let is_empty = match std::fs::read_dir(shapes_path) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(_) => false,
+    };
+    if !is_empty {
+        return;
+    }
+    for shape in ShapeDefinition::builtin() {
+        let shape_file = format!("{}/{}.json", shapes_path, shape.name);
+        match serde_json::to_string_pretty(&shape) {
+            Ok(shape_text) => {
+                if let Err(errmsg) = std::fs::write(&shape_file, shape_text) {
+                    log::error!("Cannot write shape {}. {}", shape_file, errmsg);
+                }
+            }
+            Err(errmsg) => log::error!("Cannot serialize shape {}. {}", shape.name, errmsg),
+        }
+    }
 }
 
 /// Does what it says on the tin
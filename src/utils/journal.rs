@@ -0,0 +1,195 @@
+use tokio::io::AsyncWriteExt;
+
+use super::structs::{ExperimentInstance, ExperimentSession, ExperimentStage, JournalEntry};
+
+/// Session journal in the results folder, one JSON line per entry.
+/// Not a .json file, so it stays out of the results list.
+pub const JOURNAL_FILE_NAME: &str = "session.journal.jsonl";
+
+pub fn journal_path(results_dir: &str) -> String {
+    format!("{}/{}", results_dir, JOURNAL_FILE_NAME)
+}
+
+impl ExperimentSession {
+    pub fn journal_header(&self) -> JournalEntry {
+        JournalEntry::Begin {
+            randomization   : self.instance.randomization,
+            plan            : self.instance.plan.clone(),
+            conditions      : self.instance.conditions.clone(),
+            staircase       : self.instance.staircase.clone(),
+            parameters      : self.parameters.clone(),
+            shapes_selection: self.instance.shapes_selection.clone(),
+            list_of_shapes  : self.instance.list_of_shapes.clone(),
+            practice_shapes : self
+                .practice
+                .as_ref()
+                .map(|practice| practice.list_of_shapes.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Entry of the trial answered last, None before the first answer.
+    pub fn last_trial_entry(&self) -> Option<JournalEntry> {
+        if self.stage == ExperimentStage::PracticeFeedback {
+            let practice = self.practice.as_ref()?;
+            let index = practice.list_of_guesses.len().checked_sub(1)?;
+            return Some(JournalEntry::Practice {
+                guess  : practice.list_of_guesses[index].clone(),
+                time   : practice.list_of_time[index],
+                retries: practice.list_of_retries[index],
+            });
+        }
+        let index = self.answered_count().checked_sub(1)?;
+        Some(JournalEntry::Trial {
+            guess  : self.instance.list_of_guesses[index].clone(),
+            time   : self.instance.list_of_time[index],
+            retries: self.instance.list_of_retries[index],
+        })
+    }
+
+    /// Rebuilds the session from the journal, see resume. Practice entries all come first.
+    pub fn from_journal(entries: Vec<JournalEntry>) -> Result<Self, String> {
+        let mut entries = entries.into_iter();
+        let Some(JournalEntry::Begin {
+            randomization,
+            plan,
+            conditions,
+            staircase,
+            parameters,
+            shapes_selection,
+            list_of_shapes,
+            practice_shapes,
+        }) = entries.next()
+        else {
+            return Err("Journal does not start with a session header.".to_string());
+        };
+
+        let mut practice = ExperimentInstance::practice(shapes_selection.clone(), practice_shapes);
+        let n_tests = list_of_shapes.len();
+        let mut instance = ExperimentInstance {
+            randomization,
+            plan,
+            conditions,
+            staircase,
+            shapes_selection,
+            list_of_shapes,
+            list_of_guesses : Vec::with_capacity(n_tests),
+            list_of_retries : vec![0; n_tests],
+            list_of_time    : Vec::with_capacity(n_tests),
+            experiment_index: 0,
+        };
+        for (idx, entry) in entries.enumerate() {
+            let answered = instance.list_of_guesses.len();
+            let is_practice_done = practice.as_ref().is_none_or(|practice| {
+                practice.list_of_guesses.len() >= practice.list_of_shapes.len()
+            });
+            match (entry, practice.as_mut()) {
+                (
+                    JournalEntry::Practice {
+                        guess,
+                        time,
+                        retries,
+                    },
+                    Some(practice),
+                ) if !is_practice_done => {
+                    let practice_answered = practice.list_of_guesses.len();
+                    practice.list_of_guesses.push(guess);
+                    practice.list_of_time.push(time);
+                    practice.list_of_retries[practice_answered] = retries;
+                }
+                (
+                    JournalEntry::Trial {
+                        guess,
+                        time,
+                        retries,
+                    },
+                    _,
+                ) if is_practice_done && answered < n_tests => {
+                    instance.list_of_guesses.push(guess);
+                    instance.list_of_time.push(time);
+                    instance.list_of_retries[answered] = retries;
+                }
+                _ => return Err(format!("Journal entry {} does not fit the session.", idx + 2)),
+            }
+        }
+        Self::resume(parameters, practice, instance)
+    }
+}
+
+/// A cut off last line, left by a crash while writing, is skipped.
+pub fn parse_journal(contents: &str) -> Result<Vec<JournalEntry>, String> {
+    let lines = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<&str>>();
+    let mut entries = Vec::with_capacity(lines.len());
+    for (idx, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) if idx + 1 == lines.len() => {
+                log::warn!("Journal ends in a partial line, skipped.")
+            }
+            Err(errmsg) => return Err(format!("Journal line {} unreadable: {}", idx + 1, errmsg)),
+        }
+    }
+    Ok(entries)
+}
+
+/// Starts a new journal, replacing the previous one.
+pub async fn journal_begin(results_dir: String, header: JournalEntry) -> Option<String> {
+    write_journal_entry(results_dir, header, false).await
+}
+
+pub async fn journal_append(results_dir: String, entry: JournalEntry) -> Option<String> {
+    write_journal_entry(results_dir, entry, true).await
+}
+
+/// Synced line by line, so a crash keeps every trial answered so far.
+async fn write_journal_entry(
+    results_dir: String,
+    entry: JournalEntry,
+    is_append: bool,
+) -> Option<String> {
+    let line = match serde_json::to_string(&entry) {
+        Ok(line) => line,
+        Err(errmsg) => return Some(format!("{}", errmsg)),
+    };
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(is_append)
+        .truncate(!is_append)
+        .open(journal_path(&results_dir))
+        .await;
+    let mut file = match file {
+        Ok(file) => file,
+        Err(errmsg) => return Some(format!("{}", errmsg)),
+    };
+    let written = async {
+        file.write_all(format!("{}\n", line).as_bytes()).await?;
+        file.sync_data().await
+    }
+    .await;
+    written.err().map(|errmsg| format!("{}", errmsg))
+}
+
+/// Unfinished session left in the results folder, None if there is no journal.
+pub async fn load_journal(results_dir: String) -> (Option<ExperimentSession>, Option<String>) {
+    match tokio::fs::read_to_string(journal_path(&results_dir)).await {
+        Ok(contents) => match parse_journal(&contents).and_then(ExperimentSession::from_journal) {
+            Ok(session) => (Some(session), None),
+            Err(errmsg) => (None, Some(errmsg)),
+        },
+        Err(errmsg) if errmsg.kind() == std::io::ErrorKind::NotFound => (None, None),
+        Err(errmsg) => (None, Some(format!("{}", errmsg))),
+    }
+}
+
+/// Called once the session's results are saved or discarded.
+pub async fn clear_journal(results_dir: String) -> Option<String> {
+    match tokio::fs::remove_file(journal_path(&results_dir)).await {
+        Ok(_) => None,
+        Err(errmsg) if errmsg.kind() == std::io::ErrorKind::NotFound => None,
+        Err(errmsg) => Some(format!("{}", errmsg)),
+    }
+}
@@ -1,5 +1,10 @@
 pub mod dobot;
 pub mod experiment;
 pub mod helpers;
+pub mod journal;
+pub mod preview;
+pub mod sequencer;
+pub mod shapegen;
+pub mod simulator;
 pub mod structs;
 pub mod styling;
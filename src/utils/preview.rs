@@ -0,0 +1,267 @@
+use iced::{
+    mouse,
+    widget::canvas::{self, Frame, Geometry, Path, Stroke, Text},
+    Color, Point, Rectangle, Renderer, Size, Theme,
+};
+
+use super::structs::{
+    DrawingCalibration, Position, SafetyEnvelope, SequenceStep, ShapeDefinition, ShapePlane,
+};
+
+/// Empty space kept around the trajectory. [mm]
+const PREVIEW_MARGIN: f32 = 15.0;
+/// Smallest area shown, so a single point or a short line doesn't fill the whole canvas. [mm]
+const PREVIEW_MIN_SPAN: f32 = 60.0;
+
+/// 2D plot of a trajectory in one plane, with the safety envelope behind it.
+/// Points are in travel order, labels are the numbers written next to them.
+pub struct TrajectoryPreview {
+    pub path    : Vec<[f32; 3]>,
+    pub labels  : Vec<(usize, [f32; 3])>,
+    pub plane   : ShapePlane,
+    pub envelope: SafetyEnvelope,
+}
+
+impl TrajectoryPreview {
+    /// Move steps of a sequence, numbered by their step index.
+    pub fn from_steps(steps: &[SequenceStep], plane: ShapePlane, envelope: SafetyEnvelope) -> Self {
+        let labels = steps
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, step)| match step {
+                SequenceStep::Move(position) => Some((idx, position_xyz(position))),
+                _ => None,
+            })
+            .collect::<Vec<(usize, [f32; 3])>>();
+        Self {
+            path: labels.iter().map(|(_, xyz)| *xyz).collect(),
+            labels,
+            plane,
+            envelope,
+        }
+    }
+
+    /// Shape as drawn by draw_shape: closed shapes start and end on their last vertex.
+    pub fn from_shape(
+        shape: &ShapeDefinition,
+        calibration: Option<&DrawingCalibration>,
+        plane: ShapePlane,
+        envelope: SafetyEnvelope,
+    ) -> Self {
+        let labels = shape
+            .motion_data(1.0, 1.0, calibration)
+            .iter()
+            .map(position_xyz)
+            .enumerate()
+            .collect::<Vec<(usize, [f32; 3])>>();
+        let mut path = labels.iter().map(|(_, xyz)| *xyz).collect::<Vec<[f32; 3]>>();
+        if !shape.is_open {
+            if let Some(last_vertex) = path.last().cloned() {
+                path.insert(0, last_vertex);
+            }
+        }
+        Self {
+            path,
+            labels,
+            plane,
+            envelope,
+        }
+    }
+}
+
+fn position_xyz(position: &Position) -> [f32; 3] {
+    [position.x.in_float, position.y.in_float, position.z.in_float]
+}
+
+/// (u, v) of a world point in the plane, the same axes ShapePlane maps vertices onto.
+fn project(plane: ShapePlane, [x, y, z]: [f32; 3]) -> [f32; 2] {
+    match plane {
+        ShapePlane::YZ => [y, z],
+        ShapePlane::XY => [x, y],
+        ShapePlane::XZ => [x, z],
+    }
+}
+
+/// Maps plane coordinates [mm] onto the canvas, keeping the aspect ratio and v pointing up.
+struct Viewport {
+    u_center: f32,
+    v_center: f32,
+    scale   : f32,
+    size    : Size,
+}
+
+impl Viewport {
+    fn fit(points: &[[f32; 2]], size: Size) -> Self {
+        let (mut u_min, mut u_max, mut v_min, mut v_max) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+        for [u, v] in points {
+            u_min = u_min.min(*u);
+            u_max = u_max.max(*u);
+            v_min = v_min.min(*v);
+            v_max = v_max.max(*v);
+        }
+        if points.is_empty() {
+            (u_min, u_max, v_min, v_max) = (0.0, 0.0, 0.0, 0.0);
+        }
+        let u_span = (u_max - u_min + 2.0 * PREVIEW_MARGIN).max(PREVIEW_MIN_SPAN);
+        let v_span = (v_max - v_min + 2.0 * PREVIEW_MARGIN).max(PREVIEW_MIN_SPAN);
+        Self {
+            u_center: (u_min + u_max) / 2.0,
+            v_center: (v_min + v_max) / 2.0,
+            scale   : (size.width / u_span).min(size.height / v_span),
+            size,
+        }
+    }
+
+    fn to_canvas(&self, [u, v]: [f32; 2]) -> Point {
+        Point::new(
+            self.size.width / 2.0 + (u - self.u_center) * self.scale,
+            self.size.height / 2.0 - (v - self.v_center) * self.scale,
+        )
+    }
+
+    /// Plane coordinates of the canvas corners, as (u_min, u_max, v_min, v_max).
+    fn visible(&self) -> (f32, f32, f32, f32) {
+        let half_u = self.size.width / 2.0 / self.scale;
+        let half_v = self.size.height / 2.0 / self.scale;
+        (
+            self.u_center - half_u,
+            self.u_center + half_u,
+            self.v_center - half_v,
+            self.v_center + half_v,
+        )
+    }
+}
+
+impl<Message> canvas::Program<Message> for TrajectoryPreview {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let palette = theme.extended_palette();
+        let mut frame = Frame::new(renderer, bounds.size());
+        let points = self
+            .path
+            .iter()
+            .map(|xyz| project(self.plane, *xyz))
+            .collect::<Vec<[f32; 2]>>();
+        let viewport = Viewport::fit(&points, bounds.size());
+
+        frame.with_clip(Rectangle::with_size(bounds.size()), |frame| {
+            draw_envelope(frame, &viewport, self.plane, &self.envelope, &self.path);
+
+            if points.len() > 1 {
+                let trajectory = Path::new(|builder| {
+                    builder.move_to(viewport.to_canvas(points[0]));
+                    for point in &points[1..] {
+                        builder.line_to(viewport.to_canvas(*point));
+                    }
+                });
+                frame.stroke(
+                    &trajectory,
+                    Stroke::default()
+                        .with_width(2.0)
+                        .with_color(palette.primary.strong.color),
+                );
+            }
+
+            for (idx, xyz) in &self.labels {
+                let point = viewport.to_canvas(project(self.plane, *xyz));
+                frame.fill(&Path::circle(point, 3.0), palette.background.strong.text);
+                frame.fill_text(Text {
+                    content : idx.to_string(),
+                    position: Point::new(point.x + 5.0, point.y - 16.0),
+                    color   : palette.background.base.text,
+                    size    : 12.0.into(),
+                    ..Text::default()
+                });
+            }
+
+            if let (Some(start), Some(end)) = (points.first(), points.last()) {
+                frame.stroke(
+                    &Path::circle(viewport.to_canvas(*start), 7.0),
+                    Stroke::default()
+                        .with_width(2.0)
+                        .with_color(palette.success.base.color),
+                );
+                let end = viewport.to_canvas(*end);
+                frame.stroke(
+                    &Path::rectangle(Point::new(end.x - 5.0, end.y - 5.0), Size::new(10.0, 10.0)),
+                    Stroke::default()
+                        .with_width(2.0)
+                        .with_color(palette.danger.base.color),
+                );
+            }
+        });
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Envelope limits as they cut through the plane.
+/// Vertical planes are cut at the mean x (YZ) or y (XZ) of the trajectory.
+fn draw_envelope(
+    frame: &mut Frame,
+    viewport: &Viewport,
+    plane: ShapePlane,
+    envelope: &SafetyEnvelope,
+    path: &[[f32; 3]],
+) {
+    let bound_stroke = Stroke::default()
+        .with_width(1.0)
+        .with_color(Color::from_rgba8(200, 60, 60, 0.8));
+    let (u_min, u_max, v_min, v_max) = viewport.visible();
+    let mean = |axis: usize| {
+        if path.is_empty() {
+            0.0
+        } else {
+            path.iter().map(|xyz| xyz[axis]).sum::<f32>() / path.len() as f32
+        }
+    };
+    let u_line = |frame: &mut Frame, u: f32| {
+        frame.stroke(
+            &Path::line(viewport.to_canvas([u, v_min]), viewport.to_canvas([u, v_max])),
+            bound_stroke,
+        )
+    };
+    let v_line = |frame: &mut Frame, v: f32| {
+        frame.stroke(
+            &Path::line(viewport.to_canvas([u_min, v]), viewport.to_canvas([u_max, v])),
+            bound_stroke,
+        )
+    };
+    // Half chord of a reach circle at the given distance from the base axis, None if it misses.
+    let half_chord = |radius: f32, distance: f32| {
+        let squared = radius.powi(2) - distance.powi(2);
+        if squared > 0.0 {
+            Some(squared.sqrt())
+        } else {
+            None
+        }
+    };
+
+    match plane {
+        ShapePlane::XY => {
+            let center = viewport.to_canvas([0.0, 0.0]);
+            for radius in [envelope.min_radius, envelope.max_radius] {
+                frame.stroke(&Path::circle(center, radius * viewport.scale), bound_stroke);
+            }
+        }
+        ShapePlane::YZ | ShapePlane::XZ => {
+            let distance = if plane == ShapePlane::YZ { mean(0) } else { mean(1) };
+            v_line(frame, envelope.z_floor);
+            v_line(frame, envelope.z_ceiling);
+            for radius in [envelope.min_radius, envelope.max_radius] {
+                if let Some(half_chord) = half_chord(radius, distance) {
+                    u_line(frame, -half_chord);
+                    u_line(frame, half_chord);
+                }
+            }
+        }
+    }
+}
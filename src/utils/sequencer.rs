@@ -0,0 +1,166 @@
+use super::structs::{Coordinate, NamedSequence, Position, RobotPose, SequenceStep};
+
+/// Sequencer tab without the window: the coordinate input, the step queue and its repeat settings.
+/// Inputs are kept as typed so half written numbers stay editable, see also GeneratorInputs.
+/// Errors are returned as messages for the log, the state is left unchanged on error.
+#[derive(Clone, Debug)]
+pub struct SequencerModel {
+    pub input       : Position,
+    pub steps       : Vec<SequenceStep>,
+    pub name_input  : String,
+    pub repeat_input: String,
+    pub dwell_input : String,
+    pub wait_input  : String,
+    pub is_looping  : bool,
+}
+
+impl SequencerModel {
+    pub fn new() -> Self {
+        Self {
+            input       : Position::default(),
+            steps       : Vec::new(),
+            name_input  : String::new(),
+            repeat_input: String::from("1"),
+            dwell_input : String::from("0"),
+            wait_input  : String::from("500"),
+            is_looping  : false,
+        }
+    }
+
+    /// Empty input is kept as 0.0 so the field can be cleared while typing.
+    pub fn set_coordinate(&mut self, coord: Coordinate, input_val: String) -> Result<(), String> {
+        let value = if input_val.is_empty() {
+            0.0
+        } else {
+            input_val
+                .parse::<f32>()
+                .map_err(|_| format!("Input {} not parseable to f32", coord))?
+        };
+        let item = match coord {
+            Coordinate::X => &mut self.input.x,
+            Coordinate::Y => &mut self.input.y,
+            Coordinate::Z => &mut self.input.z,
+            Coordinate::R => &mut self.input.r,
+            Coordinate::V => &mut self.input.v,
+            Coordinate::A => &mut self.input.a,
+        };
+        item.update_s_f32(input_val, value);
+        Ok(())
+    }
+
+    pub fn set_repeat_input(&mut self, intext: String) -> Result<(), String> {
+        match intext.parse::<u32>() {
+            Ok(0) => Err("Repeat count must be at least 1.".to_string()),
+            Ok(_) => {
+                self.repeat_input = intext;
+                Ok(())
+            }
+            Err(_) if intext.is_empty() => {
+                self.repeat_input = intext;
+                Ok(())
+            }
+            Err(_) => Err("Repeat count not parseable as u32.".to_string()),
+        }
+    }
+
+    pub fn set_dwell_input(&mut self, intext: String) -> Result<(), String> {
+        if intext.is_empty() || intext.parse::<u64>().is_ok() {
+            self.dwell_input = intext;
+            Ok(())
+        } else {
+            Err("Dwell not parseable as u64.".to_string())
+        }
+    }
+
+    pub fn set_wait_input(&mut self, intext: String) -> Result<(), String> {
+        if intext.is_empty() || intext.parse::<u64>().is_ok() {
+            self.wait_input = intext;
+            Ok(())
+        } else {
+            Err("Wait not parseable as u64.".to_string())
+        }
+    }
+
+    pub fn is_input_incomplete(&self) -> bool {
+        self.input.into_iter().any(|item| item.in_string.is_empty())
+    }
+
+    /// Queues the coordinate input as a move.
+    pub fn add_input_move(&mut self) -> Result<(), String> {
+        if self.is_input_incomplete() {
+            return Err("Cannot add to queue, some input(s) are empty".to_string());
+        }
+        self.steps.push(SequenceStep::Move(self.input.clone()));
+        Ok(())
+    }
+
+    /// Puts the pose into the X, Y, Z, R inputs, V, A and the mode are kept.
+    pub fn fill_input_from_pose(&mut self, pose: RobotPose) {
+        for (item, value) in [
+            (&mut self.input.x, pose.x),
+            (&mut self.input.y, pose.y),
+            (&mut self.input.z, pose.z),
+            (&mut self.input.r, pose.r),
+        ] {
+            // Rounded so the input text and the value sent agree.
+            let value = (value * 100.0).round() / 100.0;
+            item.update_s_f32(value.to_string(), value);
+        }
+    }
+
+    /// Copies a queued move back into the input, other steps have nothing to paste.
+    pub fn paste_step(&mut self, index: usize) {
+        if let Some(SequenceStep::Move(position)) = self.steps.get(index) {
+            self.input = position.clone();
+        }
+    }
+
+    pub fn remove_step(&mut self, index: usize) {
+        if index < self.steps.len() {
+            let _ = self.steps.remove(index);
+        }
+    }
+
+    pub fn swap_steps(&mut self, index: usize, target_index: usize) {
+        if index < self.steps.len() && target_index < self.steps.len() {
+            self.steps.swap(index, target_index);
+        }
+    }
+
+    /// Moves the step at index so it ends up in front of what is at insert_index now.
+    pub fn move_step(&mut self, index: usize, insert_index: usize) {
+        if index >= self.steps.len()
+            || insert_index > self.steps.len()
+            || insert_index == index
+            || insert_index == index + 1
+        {
+            return;
+        }
+        let item = self.steps.remove(index);
+        let insert_idx = if index < insert_index {
+            insert_index - 1
+        } else {
+            insert_index
+        };
+        self.steps.insert(insert_idx, item);
+    }
+
+    /// Queue plus its repeat settings. Empty inputs fall back to a single run without dwell.
+    pub fn named_sequence(&self) -> NamedSequence {
+        NamedSequence {
+            name            : self.name_input.clone(),
+            steps           : self.steps.clone(),
+            repeat_count    : self.repeat_input.parse::<u32>().unwrap_or(1),
+            is_infinite_loop: self.is_looping,
+            dwell_ms        : self.dwell_input.parse::<u64>().unwrap_or(0),
+        }
+    }
+
+    /// Replaces the queue and repeat settings with a saved sequence, the input is kept.
+    pub fn load(&mut self, named_sequence: NamedSequence) {
+        self.steps = named_sequence.steps;
+        self.repeat_input = named_sequence.repeat_count.to_string();
+        self.dwell_input = named_sequence.dwell_ms.to_string();
+        self.is_looping = named_sequence.is_infinite_loop;
+    }
+}
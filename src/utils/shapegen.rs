@@ -0,0 +1,497 @@
+use serde::{Deserialize, Serialize};
+
+use super::structs::{ShapeDefinition, ShapePlane};
+
+/// Letters that can be traced without lifting, in a box 0.6 wide and 1.0 high around the centre.
+const LETTER_STROKES: [(char, &[[f32; 2]]); 11] = [
+    ('C', &[[0.3, 0.5], [-0.3, 0.5], [-0.3, -0.5], [0.3, -0.5]]),
+    ('I', &[[0.0, 0.5], [0.0, -0.5]]),
+    ('J', &[[0.3, 0.5], [0.3, -0.5], [-0.3, -0.5], [-0.3, -0.2]]),
+    ('L', &[[-0.3, 0.5], [-0.3, -0.5], [0.3, -0.5]]),
+    ('M', &[[-0.3, -0.5], [-0.3, 0.5], [0.0, 0.0], [0.3, 0.5], [0.3, -0.5]]),
+    ('N', &[[-0.3, -0.5], [-0.3, 0.5], [0.3, -0.5], [0.3, 0.5]]),
+    ('S', &[[0.3, 0.5], [-0.3, 0.5], [-0.3, 0.0], [0.3, 0.0], [0.3, -0.5], [-0.3, -0.5]]),
+    ('U', &[[-0.3, 0.5], [-0.3, -0.5], [0.3, -0.5], [0.3, 0.5]]),
+    ('V', &[[-0.3, 0.5], [0.0, -0.5], [0.3, 0.5]]),
+    ('W', &[[-0.3, 0.5], [-0.15, -0.5], [0.0, 0.0], [0.15, -0.5], [0.3, 0.5]]),
+    ('Z', &[[-0.3, 0.5], [0.3, 0.5], [-0.3, -0.5], [0.3, -0.5]]),
+];
+
+/// Most corners a generated shape gets, each is a robot move. A degree apart on a full circle.
+const MAX_SEGMENTS: u32 = 360;
+
+/// Shape described by parameters instead of a vertex list, centred on the shape's origin.
+/// Angles are counter clockwise from the +u axis. [mm, deg]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ShapeGenerator {
+    /// Regular n-gon, the first corner points up before rotation.
+    Polygon { sides: u32, radius: f32, rotation: f32 },
+    Circle { radius: f32, segments: u32 },
+    Arc {
+        radius     : f32,
+        start_angle: f32,
+        sweep_angle: f32,
+        segments   : u32,
+    },
+    /// Straight stroke through the centre.
+    Line { length: f32, angle: f32 },
+    /// Single stroke capital letter, see LETTER_STROKES.
+    Letter { letter: char, height: f32 },
+}
+
+impl ShapeGenerator {
+    pub fn vertices(&self) -> Result<Vec<[f32; 2]>, String> {
+        match *self {
+            ShapeGenerator::Polygon {
+                sides,
+                radius,
+                rotation,
+            } => {
+                if !(3..=MAX_SEGMENTS).contains(&sides) {
+                    return Err(format!(
+                        "Polygon needs 3 to {} sides, got {}.",
+                        MAX_SEGMENTS, sides
+                    ));
+                }
+                check_size("Radius", radius)?;
+                check_finite("Rotation", rotation)?;
+                Ok(points_on_circle(radius, rotation + 90.0, 360.0, sides, false))
+            }
+            ShapeGenerator::Circle { radius, segments } => {
+                if !(3..=MAX_SEGMENTS).contains(&segments) {
+                    return Err(format!(
+                        "Circle needs 3 to {} segments, got {}.",
+                        MAX_SEGMENTS, segments
+                    ));
+                }
+                check_size("Radius", radius)?;
+                Ok(points_on_circle(radius, 0.0, 360.0, segments, false))
+            }
+            ShapeGenerator::Arc {
+                radius,
+                start_angle,
+                sweep_angle,
+                segments,
+            } => {
+                if !(1..=MAX_SEGMENTS).contains(&segments) {
+                    return Err(format!(
+                        "Arc needs 1 to {} segments, got {}.",
+                        MAX_SEGMENTS, segments
+                    ));
+                }
+                check_finite("Start angle", start_angle)?;
+                check_finite("Sweep angle", sweep_angle)?;
+                if sweep_angle == 0.0 || sweep_angle.abs() > 360.0 {
+                    return Err(format!("Arc sweep {} not in 0..360 deg.", sweep_angle));
+                }
+                check_size("Radius", radius)?;
+                Ok(points_on_circle(radius, start_angle, sweep_angle, segments, true))
+            }
+            ShapeGenerator::Line { length, angle } => {
+                check_size("Length", length)?;
+                check_finite("Angle", angle)?;
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let half = length / 2.0;
+                Ok(vec![[-half * cos, -half * sin], [half * cos, half * sin]])
+            }
+            ShapeGenerator::Letter { letter, height } => {
+                check_size("Height", height)?;
+                let letter = letter.to_ascii_uppercase();
+                match LETTER_STROKES.iter().find(|(stroke_letter, _)| *stroke_letter == letter) {
+                    Some((_, strokes)) => Ok(strokes
+                        .iter()
+                        .map(|[u, v]| [u * height, v * height])
+                        .collect()),
+                    None => Err(format!(
+                        "No stroke for letter {}, available: {}.",
+                        letter,
+                        LETTER_STROKES
+                            .iter()
+                            .map(|(stroke_letter, _)| stroke_letter.to_string())
+                            .collect::<Vec<String>>()
+                            .join(" ")
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Open paths start drawing at their first vertex instead of closing the loop.
+    pub fn is_open(&self) -> bool {
+        match self {
+            ShapeGenerator::Polygon { .. } | ShapeGenerator::Circle { .. } => false,
+            ShapeGenerator::Arc { .. }
+            | ShapeGenerator::Line { .. }
+            | ShapeGenerator::Letter { .. } => true,
+        }
+    }
+}
+
+/// NaN fails every comparison, so it has to be caught before the range checks.
+fn check_finite(what: &str, value: f32) -> Result<(), String> {
+    if value.is_finite() {
+        Ok(())
+    } else {
+        Err(format!("{} {} must be a finite number.", what, value))
+    }
+}
+
+fn check_size(what: &str, size: f32) -> Result<(), String> {
+    check_finite(what, size)?;
+    if size > 0.0 {
+        Ok(())
+    } else {
+        Err(format!("{} {} must be positive.", what, size))
+    }
+}
+
+/// Evenly spaced points over the sweep. Open sweeps include both ends, closed ones stop one short.
+fn points_on_circle(
+    radius: f32,
+    start_angle: f32,
+    sweep_angle: f32,
+    segments: u32,
+    is_open: bool,
+) -> Vec<[f32; 2]> {
+    let point_count = if is_open { segments + 1 } else { segments };
+    (0..point_count)
+        .map(|idx| {
+            let angle = start_angle + sweep_angle * idx as f32 / segments as f32;
+            let (sin, cos) = angle.to_radians().sin_cos();
+            // Rounded so saved shape files stay readable.
+            [
+                (radius * cos * 100.0).round() / 100.0,
+                (radius * sin * 100.0).round() / 100.0,
+            ]
+        })
+        .collect()
+}
+
+impl ShapeDefinition {
+    /// Fills the vertices from the generator, if the shape has one.
+    pub fn expand_generator(&mut self) -> Result<(), String> {
+        if let Some(generator) = &self.generator {
+            self.vertices = generator.vertices()?;
+            self.is_open = generator.is_open();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorKind {
+    Polygon,
+    Circle,
+    Arc,
+    Line,
+    Letter,
+}
+
+impl GeneratorKind {
+    pub const ALL: [GeneratorKind; 5] = [
+        GeneratorKind::Polygon,
+        GeneratorKind::Circle,
+        GeneratorKind::Arc,
+        GeneratorKind::Line,
+        GeneratorKind::Letter,
+    ];
+
+    /// Inputs used by this kind, with their labels.
+    pub fn fields(&self) -> Vec<(GeneratorField, &'static str)> {
+        match self {
+            GeneratorKind::Polygon => vec![
+                (GeneratorField::Count, "Sides"),
+                (GeneratorField::Size, "Radius [mm]"),
+                (GeneratorField::Angle, "Rotation [deg]"),
+            ],
+            GeneratorKind::Circle => vec![
+                (GeneratorField::Size, "Radius [mm]"),
+                (GeneratorField::Count, "Segments"),
+            ],
+            GeneratorKind::Arc => vec![
+                (GeneratorField::Size, "Radius [mm]"),
+                (GeneratorField::Angle, "Start [deg]"),
+                (GeneratorField::Sweep, "Sweep [deg]"),
+                (GeneratorField::Count, "Segments"),
+            ],
+            GeneratorKind::Line => vec![
+                (GeneratorField::Size, "Length [mm]"),
+                (GeneratorField::Angle, "Angle [deg]"),
+            ],
+            GeneratorKind::Letter => vec![
+                (GeneratorField::Letter, "Letter"),
+                (GeneratorField::Size, "Height [mm]"),
+            ],
+        }
+    }
+}
+
+impl std::fmt::Display for GeneratorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeneratorKind::Polygon => write!(f, "Polygon・多角形"),
+            GeneratorKind::Circle  => write!(f, "Circle・円"),
+            GeneratorKind::Arc     => write!(f, "Arc・円弧"),
+            GeneratorKind::Line    => write!(f, "Line・直線"),
+            GeneratorKind::Letter  => write!(f, "Letter・文字"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorField {
+    Name,
+    Count,
+    Size,
+    Angle,
+    Sweep,
+    Letter,
+}
+
+/// Generator form in the experiment setup, kept as typed so half written numbers stay editable.
+#[derive(Debug, Clone)]
+pub struct GeneratorInputs {
+    pub kind  : GeneratorKind,
+    pub name  : String,
+    pub count : String,
+    pub size  : String,
+    pub angle : String,
+    pub sweep : String,
+    pub letter: String,
+}
+
+impl GeneratorInputs {
+    pub fn new() -> Self {
+        Self {
+            kind  : GeneratorKind::Polygon,
+            name  : String::new(),
+            count : String::from("3"),
+            size  : String::from("20"),
+            angle : String::from("0"),
+            sweep : String::from("180"),
+            letter: String::from("L"),
+        }
+    }
+
+    pub fn get(&self, field: GeneratorField) -> &String {
+        match field {
+            GeneratorField::Name   => &self.name,
+            GeneratorField::Count  => &self.count,
+            GeneratorField::Size   => &self.size,
+            GeneratorField::Angle  => &self.angle,
+            GeneratorField::Sweep  => &self.sweep,
+            GeneratorField::Letter => &self.letter,
+        }
+    }
+
+    pub fn set(&mut self, field: GeneratorField, value: String) {
+        match field {
+            GeneratorField::Name   => self.name = value,
+            GeneratorField::Count  => self.count = value,
+            GeneratorField::Size   => self.size = value,
+            GeneratorField::Angle  => self.angle = value,
+            GeneratorField::Sweep  => self.sweep = value,
+            GeneratorField::Letter => self.letter = value,
+        }
+    }
+
+    pub fn generator(&self) -> Result<ShapeGenerator, String> {
+        let count = || {
+            self.count
+                .parse::<u32>()
+                .map_err(|_| format!("Count {} not parseable as u32.", self.count))
+        };
+        let float = |value: &String, what: &str| {
+            value
+                .parse::<f32>()
+                .map_err(|_| format!("{} {} not parseable as f32.", what, value))
+        };
+        Ok(match self.kind {
+            GeneratorKind::Polygon => ShapeGenerator::Polygon {
+                sides   : count()?,
+                radius  : float(&self.size, "Radius")?,
+                rotation: float(&self.angle, "Rotation")?,
+            },
+            GeneratorKind::Circle => ShapeGenerator::Circle {
+                radius  : float(&self.size, "Radius")?,
+                segments: count()?,
+            },
+            GeneratorKind::Arc => ShapeGenerator::Arc {
+                radius     : float(&self.size, "Radius")?,
+                start_angle: float(&self.angle, "Start")?,
+                sweep_angle: float(&self.sweep, "Sweep")?,
+                segments   : count()?,
+            },
+            GeneratorKind::Line => ShapeGenerator::Line {
+                length: float(&self.size, "Length")?,
+                angle : float(&self.angle, "Angle")?,
+            },
+            GeneratorKind::Letter => ShapeGenerator::Letter {
+                letter: match self.letter.chars().collect::<Vec<char>>().as_slice() {
+                    [letter] => *letter,
+                    _ => return Err("Letter must be a single character.".to_string()),
+                },
+                height: float(&self.size, "Height")?,
+            },
+        })
+    }
+
+    /// Shape as it would be saved, drawn in the default plane around the default origin.
+    pub fn build(&self) -> Result<ShapeDefinition, String> {
+        if self.name.is_empty() {
+            return Err("Name is empty.".to_string());
+        }
+        let mut shape = ShapeDefinition {
+            name     : self.name.clone(),
+            symbol   : self.name.clone(),
+            label    : String::new(),
+            vertices : Vec::new(),
+            is_open  : false,
+            plane    : ShapePlane::default(),
+            origin   : ShapeDefinition::default_origin(),
+            generator: Some(self.generator()?),
+        };
+        shape.expand_generator()?;
+        Ok(shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::structs::SequenceStep;
+
+    fn generated(generator: ShapeGenerator) -> ShapeDefinition {
+        let mut shape: ShapeDefinition =
+            serde_json::from_str(r#"{ "name": "generated", "symbol": "G" }"#).unwrap();
+        shape.generator = Some(generator);
+        shape.expand_generator().unwrap();
+        shape
+    }
+
+    /// Vertices of the drawing moves, rest pose left out.
+    fn traced(shape: &ShapeDefinition) -> Vec<[f32; 2]> {
+        let steps = shape.drawing_steps(100.0, 100.0, None, [200.0, 0.0, 0.0, 0.0]);
+        steps[..steps.len() - 1]
+            .iter()
+            .filter_map(|step| match step {
+                SequenceStep::Move(position) => Some([position.y.in_float, position.z.in_float]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn polygon_is_closed_and_points_up() {
+        let hexagon = generated(ShapeGenerator::Polygon {
+            sides   : 6,
+            radius  : 20.0,
+            rotation: 0.0,
+        });
+        assert!(!hexagon.is_open);
+        assert_eq!(hexagon.vertices.len(), 6);
+        assert_eq!(hexagon.vertices[0], [0.0, 20.0]);
+        for [u, v] in &hexagon.vertices {
+            assert!(((u * u + v * v).sqrt() - 20.0).abs() < 0.01);
+        }
+        // Starts at the last corner and comes back to it.
+        let traced = traced(&hexagon);
+        assert_eq!(traced.len(), 7);
+        assert_eq!(traced.first(), traced.last());
+
+        let rotated = ShapeGenerator::Polygon {
+            sides   : 4,
+            radius  : 10.0,
+            rotation: -90.0,
+        };
+        assert_eq!(rotated.vertices().unwrap()[0], [10.0, 0.0]);
+    }
+
+    #[test]
+    fn arc_is_open_and_includes_both_ends() {
+        let arc = generated(ShapeGenerator::Arc {
+            radius     : 10.0,
+            start_angle: 0.0,
+            sweep_angle: 90.0,
+            segments   : 3,
+        });
+        assert!(arc.is_open);
+        assert_eq!(arc.vertices.len(), 4);
+        assert_eq!(arc.vertices[0], [10.0, 0.0]);
+        assert_eq!(arc.vertices[3], [0.0, 10.0]);
+        // No closing stroke back to the start.
+        let traced = traced(&arc);
+        assert_eq!(traced, arc.vertices);
+
+        let circle = ShapeGenerator::Circle {
+            radius  : 10.0,
+            segments: 12,
+        };
+        assert_eq!(circle.vertices().unwrap().len(), 12);
+        assert!(!circle.is_open());
+        let line = ShapeGenerator::Line {
+            length: 30.0,
+            angle : 90.0,
+        };
+        assert_eq!(line.vertices().unwrap().len(), 2);
+        let letter = ShapeGenerator::Letter {
+            letter: 'z',
+            height: 10.0,
+        };
+        assert_eq!(letter.vertices().unwrap(), [[-3.0, 5.0], [3.0, 5.0], [-3.0, -5.0], [3.0, -5.0]]);
+    }
+
+    #[test]
+    fn invalid_inputs_are_rejected() {
+        let polygon = |sides, radius, rotation| ShapeGenerator::Polygon {
+            sides,
+            radius,
+            rotation,
+        };
+        let arc = |radius, sweep_angle, segments| ShapeGenerator::Arc {
+            radius,
+            start_angle: 0.0,
+            sweep_angle,
+            segments,
+        };
+        for generator in [
+            polygon(2, 10.0, 0.0),
+            polygon(361, 10.0, 0.0),
+            polygon(5, 0.0, 0.0),
+            polygon(5, -10.0, 0.0),
+            polygon(5, f32::NAN, 0.0),
+            polygon(5, f32::INFINITY, 0.0),
+            polygon(5, 10.0, f32::NAN),
+            ShapeGenerator::Circle {
+                radius  : 10.0,
+                segments: 2,
+            },
+            ShapeGenerator::Circle {
+                radius  : 10.0,
+                segments: u32::MAX,
+            },
+            arc(10.0, 0.0, 4),
+            arc(10.0, 400.0, 4),
+            arc(10.0, f32::NAN, 4),
+            arc(10.0, 90.0, 0),
+            arc(10.0, 90.0, 361),
+            arc(f32::NAN, 90.0, 4),
+            ShapeGenerator::Arc {
+                radius     : 10.0,
+                start_angle: f32::NAN,
+                sweep_angle: 90.0,
+                segments   : 4,
+            },
+            ShapeGenerator::Line {
+                length: 10.0,
+                angle : f32::NAN,
+            },
+            ShapeGenerator::Letter {
+                letter: 'Q',
+                height: 10.0,
+            },
+        ] {
+            assert!(generator.vertices().is_err(), "{:?} was accepted", generator);
+        }
+    }
+}
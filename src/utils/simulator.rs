@@ -0,0 +1,237 @@
+use iced::futures::future::BoxFuture;
+
+use super::{
+    dobot::RobotBackend,
+    structs::{DobotError, MotionMode, RobotPose},
+};
+
+/// Rough reachable cylinder of a Dobot Magician, measured from the base axis. [mm]
+const SIM_MIN_REACH: f32 = 110.0;
+const SIM_MAX_REACH: f32 = 320.0;
+const SIM_MIN_Z    : f32 = -135.0;
+const SIM_MAX_Z    : f32 = 160.0;
+const SIM_MAX_R    : f32 = 150.0;
+
+/// Link lengths for the joint angles, z = 0 at the shoulder. [mm]
+/// Only close to the real arm, good enough for jogging and the readout.
+const SIM_REAR_ARM   : f32 = 135.0;
+const SIM_FORE_ARM   : f32 = 147.0;
+const SIM_TOOL_OFFSET: f32 = 60.0;
+
+/// Default rest pose, see Config::rest_pose.
+const SIM_HOME: RobotPose = RobotPose {
+    x     : 200.0,
+    y     : 0.0,
+    z     : 0.0,
+    r     : 0.0,
+    joints: None,
+};
+
+/// In-process stand-in for the arm, used when unconnected mode is on.
+/// Keeps track of the pose and sleeps for as long as the real arm would roughly take,
+/// using a trapezoidal velocity profile from the last PTP coordinate params.
+pub struct SimulatedDobot {
+    pose        : RobotPose,
+    velocity    : f32,
+    acceleration: f32,
+    jump_height : f32,
+    jump_z_limit: f32,
+}
+
+impl SimulatedDobot {
+    pub fn new() -> Self {
+        Self {
+            pose        : RobotPose {
+                joints: inverse_kinematics(&SIM_HOME),
+                ..SIM_HOME
+            },
+            velocity    : 100.0,
+            acceleration: 100.0,
+            jump_height : 20.0,
+            jump_z_limit: SIM_MAX_Z,
+        }
+    }
+
+    /// MovJ and MovL are both timed as a straight line, jumps as lift, straight line, lower.
+    async fn travel_to(&mut self, target: RobotPose, mode: MotionMode) -> Result<(), DobotError> {
+        if let Some(errmsg) = check_reachable(&target) {
+            return Err(DobotError::Alarm(errmsg));
+        }
+        let distance = ((target.x - self.pose.x).powi(2)
+            + (target.y - self.pose.y).powi(2)
+            + (target.z - self.pose.z).powi(2))
+        .sqrt();
+        let duration = match mode {
+            MotionMode::MovJ | MotionMode::MovL => {
+                estimate_move_time(distance, self.velocity, self.acceleration)
+            }
+            MotionMode::Jump => {
+                // Like the arm, the lift stops at the z limit.
+                let lift = self
+                    .jump_height
+                    .min(self.jump_z_limit - self.pose.z.max(target.z))
+                    .max(0.0);
+                2.0 * estimate_move_time(lift, self.velocity, self.acceleration)
+                    + estimate_move_time(distance, self.velocity, self.acceleration)
+            }
+        };
+        log::info!(
+            "Simulated {} to ({}, {}, {}, {}) takes {:.2} s",
+            mode,
+            target.x,
+            target.y,
+            target.z,
+            target.r,
+            duration
+        );
+        tokio::time::sleep(tokio::time::Duration::from_secs_f32(duration)).await;
+        self.pose = RobotPose {
+            joints: inverse_kinematics(&target),
+            ..target
+        };
+        Ok(())
+    }
+}
+
+impl RobotBackend for SimulatedDobot {
+    fn move_to(&mut self, x: f32, y: f32, z: f32, r: f32, mode: MotionMode)
+        -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(self.travel_to(
+            RobotPose {
+                x,
+                y,
+                z,
+                r,
+                joints: None,
+            },
+            mode,
+        ))
+    }
+
+    fn move_joints(&mut self, joints: [f32; 4]) -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(self.travel_to(forward_kinematics(joints), MotionMode::MovJ))
+    }
+
+    fn set_home(&mut self) -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(self.travel_to(SIM_HOME, MotionMode::MovJ))
+    }
+
+    fn get_pose(&mut self) -> BoxFuture<'_, Result<RobotPose, DobotError>> {
+        let pose = self.pose;
+        Box::pin(async move { Ok(pose) })
+    }
+
+    fn set_ptp_coordinate_params(
+        &mut self,
+        v: f32,
+        a: f32,
+    ) -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(async move {
+            // Written so NaN is refused too, it would panic in the move timing.
+            if !(v > 0.0 && a > 0.0 && v.is_finite() && a.is_finite()) {
+                return Err(DobotError::CommandRejected(format!(
+                    "Velocity {} and acceleration {} must be positive.",
+                    v, a
+                )));
+            }
+            self.velocity = v;
+            self.acceleration = a;
+            Ok(())
+        })
+    }
+
+    fn set_ptp_jump_params(&mut self, jump_height: f32, z_limit: f32)
+        -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(async move {
+            if jump_height < 0.0 {
+                return Err(DobotError::CommandRejected(format!(
+                    "Jump height {} must not be negative.",
+                    jump_height
+                )));
+            }
+            self.jump_height = jump_height;
+            self.jump_z_limit = z_limit;
+            Ok(())
+        })
+    }
+
+    /// Nothing is queued in the simulator, cancelling the move future already stops it.
+    fn stop(&mut self) -> BoxFuture<'_, Result<(), DobotError>> {
+        Box::pin(async move { Ok(()) })
+    }
+}
+
+/// Returns why the pose can't be reached, None if it can.
+fn check_reachable(pose: &RobotPose) -> Option<String> {
+    let reach = (pose.x.powi(2) + pose.y.powi(2)).sqrt();
+    if !(SIM_MIN_REACH..=SIM_MAX_REACH).contains(&reach) {
+        Some(format!(
+            "Target out of reach, radius {:.1} mm not in {}..{} mm.",
+            reach, SIM_MIN_REACH, SIM_MAX_REACH
+        ))
+    } else if !(SIM_MIN_Z..=SIM_MAX_Z).contains(&pose.z) {
+        Some(format!(
+            "Target out of reach, z {:.1} mm not in {}..{} mm.",
+            pose.z, SIM_MIN_Z, SIM_MAX_Z
+        ))
+    } else if pose.r.abs() > SIM_MAX_R {
+        Some(format!(
+            "Target out of reach, r {:.1} deg over ±{} deg.",
+            pose.r, SIM_MAX_R
+        ))
+    } else {
+        None
+    }
+}
+
+/// Pose of the joint angles, J2 from vertical and J3 from horizontal like the real arm. [deg]
+/// Also where a joint jog ends up, for the safety envelope.
+pub fn forward_kinematics([j1, j2, j3, j4]: [f32; 4]) -> RobotPose {
+    let radial = SIM_REAR_ARM * j2.to_radians().sin()
+        + SIM_FORE_ARM * j3.to_radians().cos()
+        + SIM_TOOL_OFFSET;
+    let (sin_j1, cos_j1) = j1.to_radians().sin_cos();
+    RobotPose {
+        x     : radial * cos_j1,
+        y     : radial * sin_j1,
+        z     : SIM_REAR_ARM * j2.to_radians().cos() - SIM_FORE_ARM * j3.to_radians().sin(),
+        r     : j1 + j4,
+        joints: Some([j1, j2, j3, j4]),
+    }
+}
+
+/// Elbow up solution of forward_kinematics, None if the links can't reach.
+fn inverse_kinematics(pose: &RobotPose) -> Option<[f32; 4]> {
+    let j1 = pose.y.atan2(pose.x);
+    let u = (pose.x.powi(2) + pose.y.powi(2)).sqrt() - SIM_TOOL_OFFSET;
+    let v = pose.z;
+    let distance = (u.powi(2) + v.powi(2)).sqrt();
+    if distance > SIM_REAR_ARM + SIM_FORE_ARM || distance < (SIM_REAR_ARM - SIM_FORE_ARM).abs() {
+        return None;
+    }
+    let elbow = ((SIM_REAR_ARM.powi(2) + distance.powi(2) - SIM_FORE_ARM.powi(2))
+        / (2.0 * SIM_REAR_ARM * distance))
+        .acos();
+    let rear_angle = v.atan2(u) + elbow;
+    let fore_angle =
+        (v - SIM_REAR_ARM * rear_angle.sin()).atan2(u - SIM_REAR_ARM * rear_angle.cos());
+    let j1 = j1.to_degrees();
+    Some([
+        j1,
+        90.0 - rear_angle.to_degrees(),
+        -fore_angle.to_degrees(),
+        pose.r - j1,
+    ])
+}
+
+/// Time in seconds for a point to point move.
+/// Short moves never reach the cruise velocity and only accelerate then decelerate.
+fn estimate_move_time(distance: f32, velocity: f32, acceleration: f32) -> f32 {
+    if distance <= 0.0 {
+        0.0
+    } else if distance < velocity * velocity / acceleration {
+        2.0 * (distance / acceleration).sqrt()
+    } else {
+        distance / velocity + velocity / acceleration
+    }
+}
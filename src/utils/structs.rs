@@ -1,7 +1,143 @@
 use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
 
-pub struct DobotPath {
-    pub dobotpath: String,
+use super::shapegen::ShapeGenerator;
+
+/// Handle to the long-lived dobot connection actor (dobot.rs, dobot_connection_actor).
+/// Cloning is cheap, every clone talks to the same open serial port.
+#[derive(Clone)]
+pub struct DobotConnection {
+    pub sender     : mpsc::UnboundedSender<DobotCommand>,
+    pub stop_sender: mpsc::UnboundedSender<StopReply>,
+    /// Lift height sent before every MotionMode::Jump move, see Config::jump_height. [mm]
+    pub jump_height: f32,
+    /// Highest z a jump may lift to, the safety envelope's ceiling. [mm]
+    pub jump_z_limit: f32,
+}
+
+/// Receiving ends of DobotConnection, owned by the actor.
+pub struct DobotReceivers {
+    pub commands: mpsc::UnboundedReceiver<DobotCommand>,
+    pub stops   : mpsc::UnboundedReceiver<StopReply>,
+}
+
+/// Answer channel of a stop request.
+pub type StopReply = oneshot::Sender<Option<DobotError>>;
+
+/// Commands understood by the connection actor.
+pub enum DobotCommand {
+    /// (Re)connect to the given backend. An empty serial path disconnects.
+    Connect(BackendKind),
+    Execute(DobotOp, oneshot::Sender<Result<DobotReply, DobotError>>),
+}
+
+/// Single primitive dobot operation. Kept small so it can be retried after a reconnect.
+#[derive(Clone, Copy, Debug)]
+pub enum DobotOp {
+    SetHome,
+    SetParams(f32, f32),
+    MoveTo(f32, f32, f32, f32, MotionMode),
+    /// Lift height of jump moves and the highest z they may lift to. [mm]
+    SetJumpParams(f32, f32),
+    /// Joint interpolated move to J1 to J4. [deg]
+    MoveJoints([f32; 4]),
+    GetPose,
+}
+
+pub enum DobotReply {
+    Done,
+    Pose(RobotPose),
+}
+
+/// Which arm the connection actor talks to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackendKind {
+    Serial(String),
+    Simulated,
+}
+
+/// Backend independent pose. Joint angles are only reported by backends that know them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RobotPose {
+    pub x     : f32,
+    pub y     : f32,
+    pub z     : f32,
+    pub r     : f32,
+    pub joints: Option<[f32; 4]>,
+}
+
+/// Sent to a running playback, only acted on between waypoints.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlaybackControl {
+    Pause,
+    Resume,
+    Stop,
+}
+
+/// Progress reported by dobot.rs, play_sequence. Indices are waypoint indices.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlaybackEvent {
+    /// Starting iteration (number from 1, total or None when looping forever)
+    Iteration(u32, Option<u32>),
+    /// Moving towards (index, total)
+    Moving(usize, usize),
+    /// Reached (index, total)
+    Reached(usize, usize),
+    /// Paused before moving to index
+    Paused(usize),
+    Resumed,
+    Finished,
+    /// Stopped before moving to index
+    Stopped(usize),
+    Failed(usize, DobotError),
+}
+
+/// Everything that can go wrong while talking to the arm.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DobotError {
+    /// Serial port (dobot or relay) couldn't be opened, or the connection actor is gone.
+    PortOpen(String),
+    /// The arm answered but refused or failed the command.
+    CommandRejected(String),
+    /// No answer within COMMAND_TIMEOUT_MS, holds the command that timed out.
+    Timeout(String),
+    /// The arm raised an alarm, e.g. target out of reach.
+    Alarm(String),
+    PathNotSet,
+    /// Interrupted by an emergency stop.
+    Stopped,
+}
+
+impl std::fmt::Display for DobotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DobotError::PortOpen(errmsg)        => write!(f, "Port open failed: {}", errmsg),
+            DobotError::CommandRejected(errmsg) => write!(f, "Command rejected: {}", errmsg),
+            DobotError::Timeout(command)        => write!(f, "Timed out waiting for {}", command),
+            DobotError::Alarm(errmsg)           => write!(f, "Alarm: {}", errmsg),
+            DobotError::PathNotSet              => write!(f, "Dobot path not set."),
+            DobotError::Stopped                 => write!(f, "Stopped by emergency stop."),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Connected,
+    Faulted(String),
+}
+
+impl std::fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionState::Disconnected => write!(f, "○・未接続"),
+            ConnectionState::Connecting   => write!(f, "◌・接続中"),
+            ConnectionState::Connected    => write!(f, "●・接続済"),
+            ConnectionState::Faulted(_)   => write!(f, "✕・異常"),
+        }
+    }
 }
 
 pub struct RelayPath {
@@ -139,6 +275,99 @@ impl PositionItem {
     }
 }
 
+/// How the arm gets to a position.
+/// MovJ is the driver's default joint interpolated move, MovL a straight line in cartesian space,
+/// Jump lifts by Config::jump_height, travels, then lowers onto the target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MotionMode {
+    #[default]
+    MovJ,
+    MovL,
+    Jump,
+}
+
+impl MotionMode {
+    pub const ALL: [MotionMode; 3] = [MotionMode::MovJ, MotionMode::MovL, MotionMode::Jump];
+}
+
+impl std::fmt::Display for MotionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MotionMode::MovJ => write!(f, "MOVJ"),
+            MotionMode::MovL => write!(f, "MOVL"),
+            MotionMode::Jump => write!(f, "JUMP"),
+        }
+    }
+}
+
+/// Step sizes offered by the jog controls. [mm or deg]
+pub const JOG_STEPS: [f32; 3] = [0.1, 1.0, 10.0];
+
+/// What the jog controls move, the tool in cartesian space or single joints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JogSpace {
+    #[default]
+    Cartesian,
+    Joint,
+}
+
+impl JogSpace {
+    pub const ALL: [JogSpace; 2] = [JogSpace::Cartesian, JogSpace::Joint];
+}
+
+impl std::fmt::Display for JogSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JogSpace::Cartesian => write!(f, "Cartesian・直交"),
+            JogSpace::Joint     => write!(f, "Joint・関節"),
+        }
+    }
+}
+
+/// X, Y, Z, R in cartesian space, J1 to J4 in joint space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JogAxis {
+    X,
+    Y,
+    Z,
+    R,
+}
+
+impl JogAxis {
+    pub const ALL: [JogAxis; 4] = [JogAxis::X, JogAxis::Y, JogAxis::Z, JogAxis::R];
+
+    /// Index into [x, y, z, r] or [j1, j2, j3, j4].
+    pub fn index(&self) -> usize {
+        match self {
+            JogAxis::X => 0,
+            JogAxis::Y => 1,
+            JogAxis::Z => 2,
+            JogAxis::R => 3,
+        }
+    }
+
+    pub fn label(&self, space: JogSpace) -> &'static str {
+        match (space, self) {
+            (JogSpace::Cartesian, JogAxis::X) => "X",
+            (JogSpace::Cartesian, JogAxis::Y) => "Y",
+            (JogSpace::Cartesian, JogAxis::Z) => "Z",
+            (JogSpace::Cartesian, JogAxis::R) => "R",
+            (JogSpace::Joint, JogAxis::X)     => "J1",
+            (JogSpace::Joint, JogAxis::Y)     => "J2",
+            (JogSpace::Joint, JogAxis::Z)     => "J3",
+            (JogSpace::Joint, JogAxis::R)     => "J4",
+        }
+    }
+}
+
+/// Where a single jog step goes.
+#[derive(Clone, Debug)]
+pub enum JogTarget {
+    Pose(Position),
+    /// J1 to J4. [deg]
+    Joints([f32; 4]),
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Position {
     pub x: PositionItem,
@@ -147,6 +376,8 @@ pub struct Position {
     pub r: PositionItem,
     pub v: PositionItem,
     pub a: PositionItem,
+    #[serde(default)]
+    pub mode: MotionMode,
 }
 
 impl Position {
@@ -158,6 +389,7 @@ impl Position {
             r: PositionItem::new(),
             v: PositionItem::new(),
             a: PositionItem::new(),
+            mode: MotionMode::default(),
         }
     }
     pub fn position<T: Into<f32>>(x: T, y: T, z: T, r: T, v: T, a: T) -> Self {
@@ -168,6 +400,7 @@ impl Position {
             r: PositionItem::new_float_only(r.into()),
             v: PositionItem::new_float_only(v.into()),
             a: PositionItem::new_float_only(a.into()),
+            mode: MotionMode::default(),
         }
     }
     pub fn into_iter(&self) -> core::array::IntoIter<&PositionItem, 6> {
@@ -175,98 +408,362 @@ impl Position {
     }
 }
 
+/// One thing a sequence does. Move uses the velocity and acceleration stored in its position,
+/// so SetParams only matters for the steps that have none, i.e. Home.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct NamedSequence {
-    pub name: String,
-    pub sequences: Vec<Position>,
+pub enum SequenceStep {
+    Move(Position),
+    /// [ms]
+    Wait(u64),
+    /// true energizes the relay, false releases it.
+    Relay(bool),
+    Home,
+    /// Velocity and acceleration. [mm/s, mm/s^2]
+    SetParams(f32, f32),
+}
+
+impl std::fmt::Display for SequenceStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SequenceStep::Move(position) => write!(
+                f,
+                "{} X: {}, Y: {}, Z: {}, R: {}, V: {}, A: {}",
+                position.mode,
+                position.x.in_float,
+                position.y.in_float,
+                position.z.in_float,
+                position.r.in_float,
+                position.v.in_float,
+                position.a.in_float
+            ),
+            SequenceStep::Wait(wait_ms)      => write!(f, "Wait {} ms", wait_ms),
+            SequenceStep::Relay(true)        => write!(f, "Relay ON"),
+            SequenceStep::Relay(false)       => write!(f, "Relay OFF"),
+            SequenceStep::Home               => write!(f, "Home"),
+            SequenceStep::SetParams(v, a)    => write!(f, "Set params V: {}, A: {}", v, a),
+        }
+    }
+}
+
+/// Files saved before steps existed hold bare positions, those are read as moves.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredStep {
+    Step(SequenceStep),
+    Position(Position),
 }
 
-/// Shapes is very specific to my use case.
-/// This is probably not useful for a general purpose sequencing.
-/// However! You can re-implement this with your desired known sequence of position.
-/// Just need to make sure to update the shapes in the enum and iter, position sequences, display text accoridingly.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub enum Shapes {
-    Triangle,
-    Square,
-    Pentagon,
-    Hexagon,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NamedSequence {
+    pub name: String,
+    #[serde(alias = "sequences", deserialize_with = "NamedSequence::deserialize_steps")]
+    pub steps: Vec<SequenceStep>,
+    /// How many times the sequence is played, ignored when is_infinite_loop is set.
+    #[serde(default = "NamedSequence::default_repeat_count")]
+    pub repeat_count: u32,
+    #[serde(default)]
+    pub is_infinite_loop: bool,
+    /// Wait between the end of one iteration and the start of the next. [ms]
+    #[serde(default)]
+    pub dwell_ms: u64,
 }
 
-impl Shapes {
-    pub fn create_vec_shape_bool() -> Vec<(Shapes, bool)> {
-        Shapes::into_iter()
-            .map(|shape| (shape, false))
-            .collect::<Vec<(Shapes, bool)>>()
+impl NamedSequence {
+    fn default_repeat_count() -> u32 {
+        1
     }
-    pub fn into_iter() -> core::array::IntoIter<Shapes, 4> {
-        [
-            Shapes::Triangle,
-            Shapes::Square,
-            Shapes::Pentagon,
-            Shapes::Hexagon,
-        ]
-        .into_iter()
+    fn deserialize_steps<'de, D>(deserializer: D) -> Result<Vec<SequenceStep>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(Vec::<StoredStep>::deserialize(deserializer)?
+            .into_iter()
+            .map(|stored_step| match stored_step {
+                StoredStep::Step(step) => step,
+                StoredStep::Position(position) => SequenceStep::Move(position),
+            })
+            .collect())
     }
-    pub fn motion_data(&self, velocity: f32, acceleration: f32) -> Vec<Position> {
+}
+
+/// Shape drawn in the experiment and named by the subject, one JSON file per shape in Config::shapes_path.
+/// Vertices are in drawing order, 2D in the plane and relative to the origin. [mm]
+/// Shapes with a generator get their vertices from it when loaded, see shapegen.rs.
+/// The shapes identify themselves by name, which is also what ends up in the result files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShapeDefinition {
+    pub name     : String,
+    pub symbol   : String,
+    /// Answer button text, the symbol is used when empty.
+    #[serde(default)]
+    pub label    : String,
+    #[serde(default)]
+    pub vertices : Vec<[f32; 2]>,
+    /// Open paths (lines, letters) are not closed back to the first vertex.
+    #[serde(default)]
+    pub is_open  : bool,
+    #[serde(default)]
+    pub plane    : ShapePlane,
+    #[serde(default = "ShapeDefinition::default_origin")]
+    pub origin   : [f32; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generator: Option<ShapeGenerator>,
+}
+
+/// Plane the vertices are drawn in, named by the two arm axes they map to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShapePlane {
+    /// Vertical plane facing the arm, vertex (u, v) is (y, z).
+    #[default]
+    YZ,
+    /// Table plane, vertex (u, v) is (x, y).
+    XY,
+    /// Vertical plane along the arm, vertex (u, v) is (x, z).
+    XZ,
+}
+
+impl ShapePlane {
+    pub const ALL: [ShapePlane; 3] = [ShapePlane::YZ, ShapePlane::XY, ShapePlane::XZ];
+
+    pub fn to_world(&self, origin: [f32; 3], vertex: [f32; 2]) -> [f32; 3] {
+        let [ox, oy, oz] = origin;
+        let [u, v] = vertex;
         match self {
-          /// Posititon needs to be in order.
-            Shapes::Triangle => vec![
-                Position::position(200.0, 15.0, -22.5, 0.0, velocity, acceleration),
-                Position::position(200.0, -15.0, -22.5, 0.0, velocity, acceleration),
-                Position::position(200.0, 0.0, 12.5, 0.0, velocity, acceleration),
-            ],
-            Shapes::Square => vec![
-                Position::position(200.0, 15.0, 12.5, 0.0, velocity, acceleration),
-                Position::position(200.0, 15.0, -22.5, 0.0, velocity, acceleration),
-                Position::position(200.0, -15.0, -22.5, 0.0, velocity, acceleration),
-                Position::position(200.0, -15.0, 12.5, 0.0, velocity, acceleration),
-            ],
-            Shapes::Pentagon => vec![
-                Position::position(200.0, 16.0, 0.0, 0.0, velocity, acceleration),
-                Position::position(200.0, 10.0, -19.0, 0.0, velocity, acceleration),
-                Position::position(200.0, -10.0, -19.0, 0.0, velocity, acceleration),
-                Position::position(200.0, -16.0, 0.0, 0.0, velocity, acceleration),
-                Position::position(200.0, 0.0, 12.0, 0.0, velocity, acceleration),
-            ],
-            Shapes::Hexagon => vec![
-                Position::position(200.0, 8.0, 10.0, 0.0, velocity, acceleration),
-                Position::position(200.0, 17.0, -5.0, 0.0, velocity, acceleration),
-                Position::position(200.0, 8.0, -20.0, 0.0, velocity, acceleration),
-                Position::position(200.0, -9.0, -20.0, 0.0, velocity, acceleration),
-                Position::position(200.0, -17.0, -5.0, 0.0, velocity, acceleration),
-                Position::position(200.0, -8.0, 10.0, 0.0, velocity, acceleration),
-            ],
+            ShapePlane::YZ => [ox, oy + u, oz + v],
+            ShapePlane::XY => [ox + u, oy + v, oz],
+            ShapePlane::XZ => [ox + u, oy, oz + v],
         }
     }
-    pub fn show_name_en(&self) -> String {
+}
+
+impl std::fmt::Display for ShapePlane {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Shapes::Triangle => String::from("Triangle"),
-            Shapes::Square   => String::from("Square"),
-            Shapes::Pentagon => String::from("Pentagon"),
-            Shapes::Hexagon  => String::from("Hexagon"),
+            ShapePlane::YZ => write!(f, "Y–Z"),
+            ShapePlane::XY => write!(f, "X–Y"),
+            ShapePlane::XZ => write!(f, "X–Z"),
         }
     }
-    pub fn show_name_symbol(&self) -> String {
-        match self {
-            Shapes::Triangle => String::from("T・▲・三"),
-            Shapes::Square   => String::from("S・■・四"),
-            Shapes::Pentagon => String::from("P・⬟・五"),
-            Shapes::Hexagon  => String::from("H・⬢・六"),
+}
+
+impl ShapeDefinition {
+    pub fn default_origin() -> [f32; 3] {
+        [200.0, 0.0, 0.0]
+    }
+    /// The four polygons this controller was written for, seeded into an empty shape library.
+    pub fn builtin() -> Vec<ShapeDefinition> {
+        let polygon = |name: &str, symbol: &str, label: &str, vertices: Vec<[f32; 2]>| {
+            ShapeDefinition {
+                name     : name.to_string(),
+                symbol   : symbol.to_string(),
+                label    : label.to_string(),
+                vertices,
+                is_open  : false,
+                plane    : ShapePlane::YZ,
+                origin   : ShapeDefinition::default_origin(),
+                generator: None,
+            }
+        };
+        vec![
+            polygon(
+                "Triangle",
+                "T・▲・三",
+                " ▲ 三角形 ▲ ",
+                vec![[15.0, -22.5], [-15.0, -22.5], [0.0, 12.5]],
+            ),
+            polygon(
+                "Square",
+                "S・■・四",
+                " ■ 正方形 ■ ",
+                vec![[15.0, 12.5], [15.0, -22.5], [-15.0, -22.5], [-15.0, 12.5]],
+            ),
+            polygon(
+                "Pentagon",
+                "P・⬟・五",
+                " ⬟ 五角形 ⬟ ",
+                vec![[16.0, 0.0], [10.0, -19.0], [-10.0, -19.0], [-16.0, 0.0], [0.0, 12.0]],
+            ),
+            polygon(
+                "Hexagon",
+                "H・⬢・六",
+                " ⬢ 六角形 ⬢ ",
+                vec![
+                    [8.0, 10.0],
+                    [17.0, -5.0],
+                    [8.0, -20.0],
+                    [-9.0, -20.0],
+                    [-17.0, -5.0],
+                    [-8.0, 10.0],
+                ],
+            ),
+        ]
+    }
+    /// Vertices in arm coordinates, moved onto the calibrated drawing frame if there is one.
+    pub fn motion_data(
+        &self,
+        velocity: f32,
+        acceleration: f32,
+        calibration: Option<&DrawingCalibration>,
+    ) -> Vec<Position> {
+        self.vertices
+            .iter()
+            .map(|vertex| {
+                let world = self.plane.to_world(self.origin, *vertex);
+                let [x, y, z] = calibration.map_or(world, |calibration| calibration.apply(world));
+                Position::position(x, y, z, 0.0, velocity, acceleration)
+            })
+            .collect()
+    }
+    /// The whole drawing protocol: start at the last corner, energize the relay, trace, release, move to the rest pose.
+    /// Open paths start at their first vertex instead, so no closing stroke is drawn.
+    pub fn drawing_steps(
+        &self,
+        velocity: f32,
+        acceleration: f32,
+        calibration: Option<&DrawingCalibration>,
+        rest_pose: [f32; 4],
+    ) -> Vec<SequenceStep> {
+        let mut motion_data = self.motion_data(velocity, acceleration, calibration);
+        let start_corner = if self.is_open && !motion_data.is_empty() {
+            Some(motion_data.remove(0))
+        } else {
+            motion_data.last().cloned()
+        };
+        let mut steps = vec![SequenceStep::Wait(2000)];
+        if let Some(start_corner) = start_corner {
+            steps.push(SequenceStep::Move(start_corner));
+        }
+        steps.extend([SequenceStep::Wait(500), SequenceStep::Relay(true)]);
+        steps.extend(motion_data.into_iter().map(SequenceStep::Move));
+        steps.extend([
+            SequenceStep::Wait(500),
+            SequenceStep::Relay(false),
+            SequenceStep::Wait(500),
+            SequenceStep::Move(rest_position(rest_pose, calibration)),
+        ]);
+        steps
+    }
+    pub fn answer_label(&self) -> String {
+        if self.label.is_empty() {
+            format!(" {} ", self.symbol)
+        } else {
+            self.label.clone()
         }
     }
 }
 
-// Redundant, but legacy.
-impl std::fmt::Display for Shapes {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Shapes::Triangle => write!(f, " ▲ 三角形 ▲ "),
-            Shapes::Square   => write!(f, " ■ 正方形 ■ "),
-            Shapes::Pentagon => write!(f, " ⬟ 五角形 ⬟ "),
-            Shapes::Hexagon  => write!(f, " ⬢ 六角形 ⬢ "),
-        }
+/// Rest pose as a position, moved along with the drawing frame.
+pub fn rest_position(rest_pose: [f32; 4], calibration: Option<&DrawingCalibration>) -> Position {
+    let [x, y, z, r] = rest_pose;
+    let [x, y, z] = calibration.map_or([x, y, z], |calibration| calibration.apply([x, y, z]));
+    Position::position(x, y, z, r, 200.0, 200.0)
+}
+
+/// Drawing frame measured on the subject from three captured points. [mm]
+/// Moves the default frame (Y–Z plane through ShapeDefinition::default_origin) onto it,
+/// so shape planes, origins and the rest pose keep their meaning relative to the subject.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DrawingCalibration {
+    pub origin: [f32; 3],
+    /// Unit vector the default +y is mapped to.
+    pub u_axis: [f32; 3],
+    /// Unit vector the default +z is mapped to, perpendicular to u_axis.
+    pub v_axis: [f32; 3],
+}
+
+impl DrawingCalibration {
+    /// origin is the new shape centre, u_point lies along the new +u, v_point anywhere on the +v side of the plane.
+    pub fn from_points(
+        origin: [f32; 3],
+        u_point: [f32; 3],
+        v_point: [f32; 3],
+    ) -> Result<Self, String> {
+        let u_axis = normalized(sub(u_point, origin))
+            .ok_or("Origin and U point are at the same place.".to_string())?;
+        let v_direction = sub(v_point, origin);
+        // Gram-Schmidt, keeps only the part of the V point perpendicular to u.
+        let v_axis = normalized(sub(v_direction, scale(u_axis, dot(v_direction, u_axis))))
+            .ok_or("V point lies on the line through origin and U point.".to_string())?;
+        Ok(Self {
+            origin,
+            u_axis,
+            v_axis,
+        })
     }
+
+    /// Where the default +x (the normal of the default plane) ends up.
+    pub fn normal(&self) -> [f32; 3] {
+        cross(self.u_axis, self.v_axis)
+    }
+
+    pub fn apply(&self, world: [f32; 3]) -> [f32; 3] {
+        let [dx, dy, dz] = sub(world, ShapeDefinition::default_origin());
+        let moved = [
+            scale(self.normal(), dx),
+            scale(self.u_axis, dy),
+            scale(self.v_axis, dz),
+        ]
+        .into_iter()
+        .fold(self.origin, add);
+        // Rounded so the logged and sent positions stay readable.
+        moved.map(|value| (value * 100.0).round() / 100.0)
+    }
+
+    /// Inverse of apply, for poses captured on the calibrated frame.
+    pub fn unapply(&self, world: [f32; 3]) -> [f32; 3] {
+        let moved = sub(world, self.origin);
+        let default = add(
+            ShapeDefinition::default_origin(),
+            [
+                dot(moved, self.normal()),
+                dot(moved, self.u_axis),
+                dot(moved, self.v_axis),
+            ],
+        );
+        default.map(|value| (value * 100.0).round() / 100.0)
+    }
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], factor: f32) -> [f32; 3] {
+    a.map(|value| value * factor)
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// None for vectors too short to give a direction. [mm]
+fn normalized(a: [f32; 3]) -> Option<[f32; 3]> {
+    let length = dot(a, a).sqrt();
+    if length < 1.0 {
+        None
+    } else {
+        Some(scale(a, 1.0 / length))
+    }
+}
+
+/// Symbol of the named shape, the name itself if it is no longer in the library.
+pub fn shape_symbol(shape_library: &[ShapeDefinition], name: &str) -> String {
+    shape_library
+        .iter()
+        .find(|shape| shape.name == name)
+        .map_or(name.to_string(), |shape| shape.symbol.clone())
 }
 
 /// CONFIG
@@ -275,8 +772,30 @@ pub struct Config {
     pub font_path     : String,
     pub results_path  : String,
     pub sequences_path: String,
+    #[serde(default = "Config::default_shapes_path")]
+    pub shapes_path   : String,
+    /// Exported and importable experiment plans.
+    #[serde(default = "Config::default_plans_path")]
+    pub plans_path    : String,
+    /// Multi-condition protocols, see ExperimentProtocol.
+    #[serde(default = "Config::default_protocols_path")]
+    pub protocols_path: String,
     pub dobot_path    : String,
     pub relay_path    : String,
+    /// Live pose refresh interval while connected, 0 turns polling off.
+    #[serde(default = "Config::default_pose_poll_interval_ms")]
+    pub pose_poll_interval_ms: u64,
+    #[serde(default = "SafetyEnvelope::default")]
+    pub safety_envelope: SafetyEnvelope,
+    /// Lift height of jump moves. [mm]
+    #[serde(default = "Config::default_jump_height")]
+    pub jump_height: f32,
+    /// Measured drawing frame, shapes are drawn in the default frame when None.
+    #[serde(default)]
+    pub calibration: Option<DrawingCalibration>,
+    /// Where the arm goes after drawing a shape, x, y, z, r in the default frame. [mm, deg]
+    #[serde(default = "Config::default_rest_pose")]
+    pub rest_pose: [f32; 4],
 }
 
 impl Config {
@@ -287,29 +806,518 @@ impl Config {
             font_path     : String::from("./fonts"),
             results_path  : String::from("./results"),
             sequences_path: String::from("./sequences"),
+            shapes_path   : Config::default_shapes_path(),
+            plans_path    : Config::default_plans_path(),
+            protocols_path: Config::default_protocols_path(),
+            pose_poll_interval_ms: Config::default_pose_poll_interval_ms(),
+            safety_envelope: SafetyEnvelope::default(),
+            jump_height   : Config::default_jump_height(),
+            calibration   : None,
+            rest_pose     : Config::default_rest_pose(),
+        }
+    }
+    fn default_pose_poll_interval_ms() -> u64 {
+        500
+    }
+    fn default_jump_height() -> f32 {
+        20.0
+    }
+    fn default_shapes_path() -> String {
+        String::from("./shapes")
+    }
+    fn default_plans_path() -> String {
+        String::from("./plans")
+    }
+    fn default_protocols_path() -> String {
+        String::from("./protocols")
+    }
+    fn default_rest_pose() -> [f32; 4] {
+        [200.0, 0.0, 0.0, 0.0]
+    }
+}
+
+/// Region the arm is allowed to move in, checked before any position is sent.
+/// Reach is the horizontal distance from the base axis, sqrt(x^2 + y^2). [mm, deg, mm/s, mm/s^2]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SafetyEnvelope {
+    pub min_radius      : f32,
+    pub max_radius      : f32,
+    pub z_floor         : f32,
+    pub z_ceiling       : f32,
+    pub r_min           : f32,
+    pub r_max           : f32,
+    pub max_velocity    : f32,
+    pub max_acceleration: f32,
+}
+
+impl SafetyEnvelope {
+    pub fn default() -> Self {
+        Self {
+            min_radius      : 120.0,
+            max_radius      : 315.0,
+            z_floor         : -100.0,
+            z_ceiling       : 150.0,
+            r_min           : -150.0,
+            r_max           : 150.0,
+            max_velocity    : 300.0,
+            max_acceleration: 300.0,
+        }
+    }
+
+    /// Same envelope with the reach and height limits pulled in by margin. [mm]
+    pub fn shrunk(&self, margin: f32) -> Self {
+        Self {
+            min_radius: self.min_radius + margin,
+            max_radius: self.max_radius - margin,
+            z_floor   : self.z_floor + margin,
+            z_ceiling : self.z_ceiling - margin,
+            ..self.clone()
+        }
+    }
+
+    /// Every way the position leaves the envelope, empty if it is safe.
+    /// A jump passes jump_height above its target, that peak is held against the ceiling too.
+    pub fn check(&self, position: &Position, jump_height: f32) -> Vec<String> {
+        // NaN passes every comparison below, so anything not finite is refused up front.
+        let non_finite = not_finite(&[
+            ("x", position.x.in_float),
+            ("y", position.y.in_float),
+            ("z", position.z.in_float),
+            ("r", position.r.in_float),
+            ("v", position.v.in_float),
+            ("a", position.a.in_float),
+        ]);
+        if !non_finite.is_empty() {
+            return non_finite;
         }
+        let mut violations = Vec::new();
+        let radius = (position.x.in_float.powi(2) + position.y.in_float.powi(2)).sqrt();
+        if radius < self.min_radius {
+            violations.push(format!("reach {:.1} < min {}", radius, self.min_radius));
+        }
+        if radius > self.max_radius {
+            violations.push(format!("reach {:.1} > max {}", radius, self.max_radius));
+        }
+        if position.z.in_float < self.z_floor {
+            violations.push(format!("z {} < floor {}", position.z.in_float, self.z_floor));
+        }
+        if position.z.in_float > self.z_ceiling {
+            violations.push(format!("z {} > ceiling {}", position.z.in_float, self.z_ceiling));
+        } else if position.mode == MotionMode::Jump
+            && position.z.in_float + jump_height > self.z_ceiling
+        {
+            violations.push(format!(
+                "jump peak z {} > ceiling {}",
+                position.z.in_float + jump_height,
+                self.z_ceiling
+            ));
+        }
+        if position.r.in_float < self.r_min || position.r.in_float > self.r_max {
+            violations.push(format!(
+                "r {} not in {}..{}",
+                position.r.in_float, self.r_min, self.r_max
+            ));
+        }
+        violations.extend(self.check_params(position.v.in_float, position.a.in_float));
+        violations
+    }
+
+    pub fn check_params(&self, velocity: f32, acceleration: f32) -> Vec<String> {
+        let non_finite = not_finite(&[("v", velocity), ("a", acceleration)]);
+        if !non_finite.is_empty() {
+            return non_finite;
+        }
+        let mut violations = Vec::new();
+        if velocity <= 0.0 || velocity > self.max_velocity {
+            violations.push(format!("v {} not in 0..{}", velocity, self.max_velocity));
+        }
+        if acceleration <= 0.0 || acceleration > self.max_acceleration {
+            violations.push(format!(
+                "a {} not in 0..{}",
+                acceleration, self.max_acceleration
+            ));
+        }
+        violations
+    }
+
+    /// Only moves and params can leave the envelope, the other steps always pass.
+    pub fn check_step(&self, step: &SequenceStep, jump_height: f32) -> Vec<String> {
+        match step {
+            SequenceStep::Move(position) => self.check(position, jump_height),
+            SequenceStep::SetParams(velocity, acceleration) => {
+                self.check_params(*velocity, *acceleration)
+            }
+            SequenceStep::Wait(_) | SequenceStep::Relay(_) | SequenceStep::Home => Vec::new(),
+        }
+    }
+
+    /// Violations of a whole sequence as (waypoint index, joined reasons).
+    pub fn check_sequence(&self, sequence: &[Position], jump_height: f32) -> Vec<(usize, String)> {
+        sequence
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, position)| {
+                let violations = self.check(position, jump_height);
+                if violations.is_empty() {
+                    None
+                } else {
+                    Some((idx, violations.join(", ")))
+                }
+            })
+            .collect()
+    }
+
+    /// Same as check_sequence, indexed by step.
+    pub fn check_steps(&self, steps: &[SequenceStep], jump_height: f32) -> Vec<(usize, String)> {
+        steps
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, step)| {
+                let violations = self.check_step(step, jump_height);
+                if violations.is_empty() {
+                    None
+                } else {
+                    Some((idx, violations.join(", ")))
+                }
+            })
+            .collect()
     }
 }
 
+fn not_finite(values: &[(&str, f32)]) -> Vec<String> {
+    values
+        .iter()
+        .filter(|(_, value)| !value.is_finite())
+        .map(|(what, value)| format!("{} {} is not a finite number", what, value))
+        .collect()
+}
+
 /// SHAPES EXPERIMENT
 #[derive(Clone, Debug, PartialEq)]
 pub enum ExperimentStage {
     NotInExperiment,
     BeginTiming,
+    /// Waiting for the experimenter to set up a condition by hand, see confirm_condition.
+    ConditionSetup,
     Preparation,
     Answering,
+    /// Showing whether the practice trial just answered was right, see practice_feedback.
+    PracticeFeedback,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct ExperimentInstance {
-    pub shapes_selection: Vec<Shapes>,
-    pub list_of_shapes  : Vec<Shapes>,
-    pub list_of_guesses : Vec<Shapes>,
+    /// How list_of_shapes was drawn from shapes_selection.
+    pub randomization   : Randomization,
+    /// Name of the imported plan the trials come from.
+    pub plan            : Option<String>,
+    /// Condition of every trial, None without a protocol.
+    pub conditions      : Option<ConditionSchedule>,
+    /// Varies a parameter by the answers, list_of_shapes is only the most it may run then.
+    pub staircase       : Option<Staircase>,
+    pub shapes_selection: Vec<ShapeDefinition>,
+    pub list_of_shapes  : Vec<ShapeDefinition>,
+    /// Names of the guessed shapes.
+    pub list_of_guesses : Vec<String>,
     pub list_of_retries : Vec<u32>,
     pub list_of_time    : Vec<u128>,
     pub experiment_index: usize,
 }
 
+/// Experiment in progress, the transitions are in experiment.rs.
+/// Times are ms on the caller's clock, so the flow runs the same without a window.
+#[derive(Clone, Debug)]
+pub struct ExperimentSession {
+    pub stage       : ExperimentStage,
+    pub instance    : ExperimentInstance,
+    /// Practice trials run before the recorded ones, None without practice.
+    pub practice    : Option<ExperimentInstance>,
+    /// As entered, the conditions override them.
+    pub parameters  : Parameters,
+    /// Parameters of every condition in schedule order, only the entered ones without a protocol.
+    pub condition_parameters: Vec<Parameters>,
+    /// Speed and acceleration of every entry of condition_parameters.
+    pub motions     : Vec<(f32, f32)>,
+    /// Parameters of the trial the experimenter set up last, None until the first one.
+    pub confirmed_setup: Option<Parameters>,
+    /// Answer timer start of the current trial, None until its first drawing finishes.
+    pub time_start  : Option<u128>,
+}
+
+/// What the caller has to do after an ExperimentSession transition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExperimentAction {
+    /// Call advance again, after a short pause so the GUI can redraw.
+    Advance,
+    /// Draw the shape, then call drawing_finished. A failed drawing aborts the experiment.
+    Draw(ShapeDefinition),
+    /// Wait for the subject's answer or a retry.
+    AwaitAnswer,
+    /// Wait until the experimenter has set up the condition, then call confirm_condition.
+    AwaitConditionSetup(Condition),
+    /// Practice answer given, with whether it was right. Show it, then call advance.
+    PracticeFeedback(bool),
+    /// Every trial is answered, the results are ready.
+    Finished,
+    /// Not possible in the current stage, nothing changed.
+    Ignored,
+}
+
+/// How the trial order is drawn, see create_experiment_instance.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RandomizationStrategy {
+    /// Every trial picks any shape, so the counts per shape vary.
+    #[default]
+    Uniform,
+    /// Blocks holding every shape once in shuffled order, the last block may be cut short.
+    BalancedBlocks,
+    /// One row of a balanced Latin square, repeated. Same order for the same row.
+    LatinSquare,
+}
+
+impl RandomizationStrategy {
+    pub const ALL: [RandomizationStrategy; 3] = [
+        RandomizationStrategy::Uniform,
+        RandomizationStrategy::BalancedBlocks,
+        RandomizationStrategy::LatinSquare,
+    ];
+}
+
+impl std::fmt::Display for RandomizationStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RandomizationStrategy::Uniform        => write!(f, "Uniform・一様"),
+            RandomizationStrategy::BalancedBlocks => write!(f, "Balanced blocks・均等ブロック"),
+            RandomizationStrategy::LatinSquare    => write!(f, "Latin square・ラテン方格"),
+        }
+    }
+}
+
+/// Trial order settings, saved with the results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Randomization {
+    pub strategy           : RandomizationStrategy,
+    /// Latin square row, counted from 0. Give every subject the next row.
+    #[serde(default)]
+    pub latin_square_row   : usize,
+    /// No shape twice in a row, as long as more than one shape is selected.
+    #[serde(default)]
+    pub no_immediate_repeat: bool,
+}
+
+impl std::fmt::Display for Randomization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.strategy)?;
+        if self.strategy == RandomizationStrategy::LatinSquare {
+            write!(f, ", row {}", self.latin_square_row)?;
+        }
+        if self.no_immediate_repeat {
+            write!(f, ", no repeats")?;
+        }
+        Ok(())
+    }
+}
+
+/// Trial order fixed ahead of the session, exported to and imported from Config::plans_path.
+/// Holds the whole shapes, so the plan runs the same with another shape library.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExperimentPlan {
+    pub name            : String,
+    /// Seed the order was drawn with, None for plans written by hand.
+    #[serde(default)]
+    pub seed            : Option<u64>,
+    #[serde(default)]
+    pub randomization   : Randomization,
+    /// Shapes offered as answers.
+    pub shapes_selection: Vec<ShapeDefinition>,
+    /// Shape of every trial, in order.
+    pub list_of_shapes  : Vec<ShapeDefinition>,
+    /// Condition of every trial, plans without a protocol run the entered parameters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions      : Option<ConditionSchedule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staircase       : Option<Staircase>,
+    /// Shape of every practice trial, run before the recorded ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub practice_shapes : Vec<ShapeDefinition>,
+}
+
+/// Parameter overrides of one condition of a protocol.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Condition {
+    /// Tags the trials run under it, unique within the protocol.
+    pub name     : String,
+    /// Replace the entered parameters. Subject name, test count and seed stay per session.
+    #[serde(default)]
+    pub overrides: Vec<ParameterItem>,
+}
+
+impl Condition {
+    /// The app only drives speed and acceleration, the rest is set up by hand.
+    pub fn is_driven(parameter_type: &ParameterType) -> bool {
+        matches!(parameter_type, ParameterType::Speed | ParameterType::Acceleration)
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        let overrides = self
+            .overrides
+            .iter()
+            .map(|item| {
+                format!(
+                    "{} {}{}",
+                    item.parameter_type.show_title_en(),
+                    item.value,
+                    item.parameter_type.show_unit().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<String>>();
+        if !overrides.is_empty() {
+            write!(f, " ({})", overrides.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// How the trials of the conditions are ordered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConditionScheduling {
+    /// All trials of a condition, then the next one, in protocol order.
+    #[default]
+    Blocked,
+    /// Conditions shuffled across the trials.
+    Interleaved,
+}
+
+impl std::fmt::Display for ConditionScheduling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConditionScheduling::Blocked     => write!(f, "Blocked・ブロック"),
+            ConditionScheduling::Interleaved => write!(f, "Interleaved・交互"),
+        }
+    }
+}
+
+/// Conditions swept within one session or a staircase, loaded from Config::protocols_path.
+/// The test count is split evenly across the conditions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExperimentProtocol {
+    pub name      : String,
+    #[serde(default)]
+    pub scheduling: ConditionScheduling,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Instead of conditions. The test count is the most trials it may run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staircase : Option<Staircase>,
+}
+
+/// Down after this many correct answers in a row, up after every wrong one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StaircaseRule {
+    /// Converges on 70.7 % correct.
+    #[default]
+    OneUpTwoDown,
+    /// Converges on 79.4 % correct.
+    OneUpThreeDown,
+}
+
+impl std::fmt::Display for StaircaseRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StaircaseRule::OneUpTwoDown   => write!(f, "1-up/2-down"),
+            StaircaseRule::OneUpThreeDown => write!(f, "1-up/3-down"),
+        }
+    }
+}
+
+/// Adaptive procedure varying one parameter by the answers, see Staircase::track.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Staircase {
+    pub parameter        : ParameterType,
+    #[serde(default)]
+    pub rule             : StaircaseRule,
+    pub start            : u32,
+    /// Added after a wrong answer, taken off after the correct ones.
+    /// Negative when larger values are harder, e.g. for the speed.
+    pub step             : i32,
+    pub min              : u32,
+    pub max              : u32,
+    /// The run stops after this many reversals.
+    pub reversals        : u32,
+    /// First reversals left out of the threshold, they are still far from it.
+    #[serde(default)]
+    pub discard_reversals: u32,
+}
+
+/// Levels of a staircase run, see Staircase::track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaircaseTrack {
+    /// Level of every answered trial and of the next one.
+    pub levels         : Vec<u32>,
+    /// Level at every change of direction.
+    pub reversal_levels: Vec<u32>,
+}
+
+/// Outcome of a staircase run, the level of every trial is in its ResultItem.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StaircaseResult {
+    pub staircase      : Staircase,
+    pub reversal_levels: Vec<u32>,
+    /// Mean of the reversal levels after the discarded ones, None without any.
+    pub threshold      : Option<f32>,
+    /// Whether it stopped on its reversals rather than on the test count.
+    pub is_converged   : bool,
+}
+
+/// Which condition every trial runs under, fixed with the trial order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConditionSchedule {
+    pub protocol          : String,
+    #[serde(default)]
+    pub scheduling        : ConditionScheduling,
+    pub conditions        : Vec<Condition>,
+    /// Index into conditions for every trial, in order.
+    pub list_of_conditions: Vec<usize>,
+}
+
+/// One line of the session journal, see journal.rs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum JournalEntry {
+    /// Written when the experiment begins, the trial order is fixed from here on.
+    Begin {
+        #[serde(default)]
+        randomization   : Randomization,
+        #[serde(default)]
+        plan            : Option<String>,
+        #[serde(default)]
+        conditions      : Option<ConditionSchedule>,
+        #[serde(default)]
+        staircase       : Option<Staircase>,
+        parameters      : Parameters,
+        shapes_selection: Vec<ShapeDefinition>,
+        list_of_shapes  : Vec<ShapeDefinition>,
+        #[serde(default)]
+        practice_shapes : Vec<ShapeDefinition>,
+    },
+    /// One answered practice trial, all of them come before the first Trial.
+    Practice {
+        guess  : String,
+        /// [ms]
+        time   : u128,
+        retries: u32,
+    },
+    /// One answered trial, in trial order.
+    Trial {
+        guess  : String,
+        /// [ms]
+        time   : u128,
+        retries: u32,
+    },
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ResultExports {
     pub parameters         : Parameters,
@@ -317,36 +1325,83 @@ pub struct ResultExports {
     pub avg_correct_answers: u32,
     pub analyses           : Vec<ShapeAnalysis>,
     pub results            : Vec<ResultItem>,
+    /// Results saved before the files had it are complete.
+    #[serde(default)]
+    pub status             : CompletionStatus,
+    /// Older files are uniform, the only strategy there was.
+    #[serde(default)]
+    pub randomization      : Randomization,
+    /// Imported plan the session ran, see ExperimentPlan.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan               : Option<String>,
+    /// Protocol the conditions come from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol           : Option<String>,
+    /// Results broken down per condition, in protocol order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub condition_analyses : Vec<ConditionAnalysis>,
+    /// Practice trials for reference, left out of every score above.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub practice           : Vec<ResultItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staircase          : Option<StaircaseResult>,
+}
+
+/// How the session ended. Aborted results only hold the trials answered before.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum CompletionStatus {
+    #[default]
+    Complete,
+    /// With the reason, e.g. the arm error or the emergency stop.
+    Aborted(String),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ResultItem {
     pub time       : u128,
-    pub true_shape : Shapes,
-    pub guess_shape: Shapes,
+    pub true_shape : String,
+    pub guess_shape: String,
     pub retries    : u32,
     pub is_correct : bool,
+    /// Name of the condition the trial ran under.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition  : Option<String>,
+    /// Staircase level the trial ran at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level      : Option<u32>,
+}
+
+/// Scores of the trials run under one condition.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConditionAnalysis {
+    pub condition          : String,
+    /// The entered parameters with the condition's overrides.
+    pub parameters         : Parameters,
+    pub trial_count        : u32,
+    pub avg_time           : u128,
+    pub avg_correct_answers: u32,
+    pub analyses           : Vec<ShapeAnalysis>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ShapeAnalysis {
     pub time            : u128,
     pub av_time         : u128,
-    pub main_shape      : Shapes,
+    pub main_shape      : String,
     pub main_shape_count: u32,
-    pub wrong_shapes    : Vec<(Shapes, u32)>,
+    pub wrong_shapes    : Vec<(String, u32)>,
 }
 
 impl ShapeAnalysis {
-    pub fn new(main_shape: Shapes, used_shapes: Vec<Shapes>) -> Self {
+    pub fn new(main_shape: String, used_shapes: Vec<String>) -> Self {
         Self {
-            main_shape,
-            main_shape_count: 0,
             wrong_shapes: used_shapes
                 .iter()
                 .filter(|shape| **shape != main_shape)
                 .map(|shape| (shape.clone(), 0))
-                .collect::<Vec<(Shapes, u32)>>(),
+                .collect::<Vec<(String, u32)>>(),
+            main_shape,
+            main_shape_count: 0,
             time: 0,
             av_time: 0,
         }
@@ -376,6 +1431,7 @@ impl ShapeAnalysis {
 pub enum ParameterType {
     SubjectName,
     TestCount,
+    PracticeCount,
     Voltage,
     Acceleration,
     Speed,
@@ -383,6 +1439,7 @@ pub enum ParameterType {
     ModulationFrequency,
     CarrierType,
     CarrierFrequency,
+    Seed,
 }
 
 impl ParameterType {
@@ -402,6 +1459,13 @@ impl ParameterType {
                     "実験回数・Test Count"
                 }
             }),
+            ParameterType::PracticeCount => String::from({
+                if no_english {
+                    "練習回数"
+                } else {
+                    "練習回数・Practice Count"
+                }
+            }),
             ParameterType::Voltage => String::from({
                 if no_english {
                     "駆動電圧"
@@ -451,6 +1515,13 @@ impl ParameterType {
                     "搬送波周波数・Carrier Frequency"
                 }
             }),
+            ParameterType::Seed => String::from({
+                if no_english {
+                    "乱数シード"
+                } else {
+                    "乱数シード・Seed"
+                }
+            }),
         }
     }
 
@@ -458,6 +1529,7 @@ impl ParameterType {
         match self {
             ParameterType::SubjectName         => None,
             ParameterType::TestCount           => None,
+            ParameterType::PracticeCount       => None,
             ParameterType::Voltage             => Some(String::from(" [Vpp]")),
             ParameterType::Acceleration        => Some(String::from(" [cm / s^2]")),
             ParameterType::Speed               => Some(String::from(" [cm / s]")),
@@ -465,6 +1537,7 @@ impl ParameterType {
             ParameterType::ModulationFrequency => Some(String::from(" [Hz]")),
             ParameterType::CarrierType         => None,
             ParameterType::CarrierFrequency    => Some(String::from(" [Hz]")),
+            ParameterType::Seed                => None,
         }
     }
 
@@ -472,6 +1545,7 @@ impl ParameterType {
         match self {
             ParameterType::SubjectName         => String::from("Subject name"),
             ParameterType::TestCount           => String::from("Test count"),
+            ParameterType::PracticeCount       => String::from("Practice count (optional)"),
             ParameterType::Voltage             => String::from("Voltage"),
             ParameterType::Acceleration        => String::from("Acceleration"),
             ParameterType::Speed               => String::from("Speed"),
@@ -479,11 +1553,17 @@ impl ParameterType {
             ParameterType::ModulationFrequency => String::from("Modulation frequency"),
             ParameterType::CarrierType         => String::from("Carrier type"),
             ParameterType::CarrierFrequency    => String::from("Carrier frequency"),
+            ParameterType::Seed                => String::from("Seed (optional)"),
         }
     }
+
+    /// May be left empty, the others are needed to start.
+    pub fn is_optional(&self) -> bool {
+        matches!(self, ParameterType::PracticeCount | ParameterType::Seed)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ParameterItem {
     pub parameter_type: ParameterType,
     pub value         : String,
@@ -502,6 +1582,9 @@ impl ParameterItem {
 pub struct Parameters {
     pub subject_name        : ParameterItem,
     pub test_count          : ParameterItem,
+    /// Practice trials before the recorded ones, none when empty.
+    #[serde(default = "Parameters::default_practice_count")]
+    pub practice_count      : ParameterItem,
     pub voltage             : ParameterItem,
     pub acceleration        : ParameterItem,
     pub speed               : ParameterItem,
@@ -509,6 +1592,9 @@ pub struct Parameters {
     pub modulation_frequency: ParameterItem,
     pub carrier_type        : ParameterItem,
     pub carrier_frequency   : ParameterItem,
+    /// Trial order seed, the only optional parameter. Results record the one actually used.
+    #[serde(default = "Parameters::default_seed")]
+    pub seed                : ParameterItem,
 }
 
 impl Parameters {
@@ -516,6 +1602,7 @@ impl Parameters {
         Self {
             subject_name        : ParameterItem::new(ParameterType::SubjectName),
             test_count          : ParameterItem::new(ParameterType::TestCount),
+            practice_count      : Parameters::default_practice_count(),
             voltage             : ParameterItem::new(ParameterType::Voltage),
             acceleration        : ParameterItem::new(ParameterType::Acceleration),
             speed               : ParameterItem::new(ParameterType::Speed),
@@ -523,13 +1610,23 @@ impl Parameters {
             modulation_frequency: ParameterItem::new(ParameterType::ModulationFrequency),
             carrier_type        : ParameterItem::new(ParameterType::CarrierType),
             carrier_frequency   : ParameterItem::new(ParameterType::CarrierFrequency),
+            seed                : Parameters::default_seed(),
         }
     }
 
-    pub fn into_iter(&self) -> core::array::IntoIter<&ParameterItem, 9> {
+    fn default_seed() -> ParameterItem {
+        ParameterItem::new(ParameterType::Seed)
+    }
+
+    fn default_practice_count() -> ParameterItem {
+        ParameterItem::new(ParameterType::PracticeCount)
+    }
+
+    pub fn into_iter(&self) -> core::array::IntoIter<&ParameterItem, 11> {
         [
             &self.subject_name,
             &self.test_count,
+            &self.practice_count,
             &self.voltage,
             &self.acceleration,
             &self.speed,
@@ -537,6 +1634,7 @@ impl Parameters {
             &self.modulation_frequency,
             &self.carrier_type,
             &self.carrier_frequency,
+            &self.seed,
         ]
         .into_iter()
     }
@@ -545,6 +1643,7 @@ impl Parameters {
         vec![
             &mut self.subject_name,
             &mut self.test_count,
+            &mut self.practice_count,
             &mut self.voltage,
             &mut self.acceleration,
             &mut self.speed,
@@ -552,7 +1651,272 @@ impl Parameters {
             &mut self.modulation_frequency,
             &mut self.carrier_type,
             &mut self.carrier_frequency,
+            &mut self.seed,
         ]
         .into_iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JUMP_HEIGHT: f32 = 20.0;
+
+    fn position(x: f32, z: f32, mode: MotionMode) -> Position {
+        let mut position = Position::position(x, 0.0, z, 0.0, 100.0, 100.0);
+        position.mode = mode;
+        position
+    }
+
+    /// Reach 200 on the x axis at z 0, well inside the default envelope.
+    fn inside() -> Position {
+        position(200.0, 0.0, MotionMode::MovJ)
+    }
+
+    #[test]
+    fn every_limit_is_checked() {
+        let envelope = SafetyEnvelope::default();
+        assert!(envelope.check(&inside(), JUMP_HEIGHT).is_empty());
+        // x, y, z, r, v, a
+        for (outside, reason) in [
+            ([100.0, 0.0, 0.0, 0.0, 100.0, 100.0], "reach 100.0 < min"),
+            ([320.0, 0.0, 0.0, 0.0, 100.0, 100.0], "reach 320.0 > max"),
+            // Reach is horizontal, so y counts as much as x.
+            ([0.0, 320.0, 0.0, 0.0, 100.0, 100.0], "reach 320.0 > max"),
+            ([200.0, 0.0, -101.0, 0.0, 100.0, 100.0], "< floor"),
+            ([200.0, 0.0, 151.0, 0.0, 100.0, 100.0], "> ceiling"),
+            ([200.0, 0.0, 0.0, -151.0, 100.0, 100.0], "r -151"),
+            ([200.0, 0.0, 0.0, 151.0, 100.0, 100.0], "r 151"),
+            ([200.0, 0.0, 0.0, 0.0, 0.0, 100.0], "v 0"),
+            ([200.0, 0.0, 0.0, 0.0, 301.0, 100.0], "v 301"),
+            ([200.0, 0.0, 0.0, 0.0, 100.0, -1.0], "a -1"),
+            ([200.0, 0.0, 0.0, 0.0, 100.0, 301.0], "a 301"),
+        ] {
+            let [x, y, z, r, v, a] = outside;
+            let violations = envelope.check(&Position::position(x, y, z, r, v, a), JUMP_HEIGHT);
+            assert_eq!(violations.len(), 1, "{:?}", violations);
+            assert!(violations[0].contains(reason), "{} not in {}", reason, violations[0]);
+        }
+        // The limits themselves are inside.
+        for [x, z, r] in [[120.0, -100.0, -150.0], [315.0, 150.0, 150.0]] {
+            let position = Position::position(x, 0.0, z, r, 300.0, 300.0);
+            assert!(envelope.check(&position, JUMP_HEIGHT).is_empty());
+        }
+        let position = Position::position(0.0, 0.0, -200.0, 0.0, 100.0, 100.0);
+        assert_eq!(envelope.check(&position, JUMP_HEIGHT).len(), 2);
+        // NaN would pass every comparison.
+        for [x, y, z, r, v, a] in [
+            [f32::NAN, 0.0, 0.0, 0.0, 100.0, 100.0],
+            [200.0, f32::NAN, 0.0, 0.0, 100.0, 100.0],
+            [200.0, 0.0, f32::NAN, 0.0, 100.0, 100.0],
+            [200.0, 0.0, f32::INFINITY, 0.0, 100.0, 100.0],
+            [200.0, 0.0, 0.0, f32::NAN, 100.0, 100.0],
+            [200.0, 0.0, 0.0, 0.0, f32::NAN, 100.0],
+            [200.0, 0.0, 0.0, 0.0, 100.0, f32::NEG_INFINITY],
+        ] {
+            let violations = envelope.check(&Position::position(x, y, z, r, v, a), JUMP_HEIGHT);
+            assert_eq!(violations.len(), 1, "{:?}", violations);
+            assert!(violations[0].contains("not a finite number"), "{}", violations[0]);
+        }
+    }
+
+    #[test]
+    fn only_moves_and_params_are_checked() {
+        let envelope = SafetyEnvelope::default();
+        for step in [SequenceStep::Wait(500), SequenceStep::Relay(true), SequenceStep::Home] {
+            assert!(envelope.check_step(&step, JUMP_HEIGHT).is_empty());
+        }
+        assert!(envelope.check_step(&SequenceStep::SetParams(100.0, 100.0), JUMP_HEIGHT).is_empty());
+        assert_eq!(envelope.check_step(&SequenceStep::SetParams(0.0, 400.0), JUMP_HEIGHT).len(), 2);
+        let not_a_number = SequenceStep::SetParams(f32::NAN, 100.0);
+        assert_eq!(envelope.check_step(&not_a_number, JUMP_HEIGHT).len(), 1);
+        assert!(envelope.check_step(&SequenceStep::Move(inside()), JUMP_HEIGHT).is_empty());
+        assert_eq!(
+            envelope
+                .check_step(&SequenceStep::Move(position(200.0, 200.0, MotionMode::MovJ)), JUMP_HEIGHT)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn violations_are_indexed_by_waypoint() {
+        let envelope = SafetyEnvelope::default();
+        let too_far = position(400.0, 0.0, MotionMode::MovJ);
+        let mut too_low_and_slow = position(200.0, -200.0, MotionMode::MovJ);
+        too_low_and_slow.v.in_float = 0.0;
+        let sequence = [inside(), too_far.clone(), inside(), too_low_and_slow.clone()];
+        let violations = envelope.check_sequence(&sequence, JUMP_HEIGHT);
+        assert_eq!(violations.iter().map(|(idx, _)| *idx).collect::<Vec<usize>>(), [1, 3]);
+        assert!(violations[1].1.contains("floor") && violations[1].1.contains(", "));
+        assert!(envelope.check_sequence(&[inside(), inside()], JUMP_HEIGHT).is_empty());
+
+        let steps = [
+            SequenceStep::Wait(500),
+            SequenceStep::Move(too_far),
+            SequenceStep::Relay(true),
+            SequenceStep::SetParams(500.0, 100.0),
+            SequenceStep::Move(inside()),
+            SequenceStep::Move(too_low_and_slow),
+        ];
+        let violations = envelope.check_steps(&steps, JUMP_HEIGHT);
+        assert_eq!(violations.iter().map(|(idx, _)| *idx).collect::<Vec<usize>>(), [1, 3, 5]);
+    }
+
+    #[test]
+    fn jump_peak_stays_under_the_ceiling() {
+        let envelope = SafetyEnvelope::default();
+        // 140 is under the ceiling of 150, but the jump passes 160.
+        assert!(envelope.check(&position(200.0, 140.0, MotionMode::MovJ), JUMP_HEIGHT).is_empty());
+        assert!(envelope.check(&position(200.0, 140.0, MotionMode::MovL), JUMP_HEIGHT).is_empty());
+        let violations = envelope.check(&position(200.0, 140.0, MotionMode::Jump), JUMP_HEIGHT);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("jump peak"));
+        assert!(envelope.check(&position(200.0, 130.0, MotionMode::Jump), JUMP_HEIGHT).is_empty());
+        assert!(envelope.check(&position(200.0, 140.0, MotionMode::Jump), 5.0).is_empty());
+    }
+
+    #[test]
+    fn shrunk_envelope_keeps_a_margin() {
+        let shrunk = SafetyEnvelope::default().shrunk(10.0);
+        assert!(shrunk.check(&position(300.0, 0.0, MotionMode::MovJ), JUMP_HEIGHT).is_empty());
+        for (x, z) in [(310.0, 0.0), (125.0, 0.0), (200.0, -95.0), (200.0, 145.0)] {
+            assert_eq!(shrunk.check(&position(x, z, MotionMode::MovJ), JUMP_HEIGHT).len(), 1);
+        }
+        // Rotation and speed are exact, those limits stay.
+        let at_the_limits = Position::position(200.0, 0.0, 0.0, 150.0, 300.0, 300.0);
+        assert!(shrunk.check(&at_the_limits, JUMP_HEIGHT).is_empty());
+    }
+
+    fn item(value: f32) -> String {
+        format!(r#"{{ "in_string": "{}", "in_float": {} }}"#, value, value)
+    }
+
+    /// A position the way the first version saved it, without a motion mode.
+    fn stored_position(x: f32, z: f32) -> String {
+        format!(
+            r#"{{ "x": {}, "y": {}, "z": {}, "r": {}, "v": {}, "a": {} }}"#,
+            item(x),
+            item(0.0),
+            item(z),
+            item(0.0),
+            item(100.0),
+            item(50.0)
+        )
+    }
+
+    #[test]
+    fn position_lists_load_as_moves() {
+        let contents = format!(
+            r#"{{ "name": "old", "sequences": [{}, {}] }}"#,
+            stored_position(200.0, 10.0),
+            stored_position(250.0, -20.0)
+        );
+        let sequence: NamedSequence = serde_json::from_str(&contents).unwrap();
+        assert_eq!(sequence.name, "old");
+        assert_eq!(sequence.steps.len(), 2);
+        let SequenceStep::Move(second) = &sequence.steps[1] else {
+            panic!("expected a move");
+        };
+        assert_eq!((second.x.in_float, second.z.in_float), (250.0, -20.0));
+        assert_eq!((second.v.in_float, second.a.in_float), (100.0, 50.0));
+        for step in &sequence.steps {
+            assert!(matches!(step, SequenceStep::Move(position) if position.mode == MotionMode::MovJ));
+        }
+        // Defaults for what the old files did not have, so they play once like before.
+        assert_eq!(sequence.repeat_count, 1);
+        assert!(!sequence.is_infinite_loop);
+        assert_eq!(sequence.dwell_ms, 0);
+
+        // Saved again, it is written in the current format and reads back the same.
+        let saved = serde_json::to_string(&sequence).unwrap();
+        assert!(saved.contains(r#""steps""#) && !saved.contains(r#""sequences""#));
+        let reloaded: NamedSequence = serde_json::from_str(&saved).unwrap();
+        assert_eq!(reloaded.steps.len(), 2);
+        assert!(matches!(reloaded.steps[0], SequenceStep::Move(_)));
+    }
+
+    fn xyzr(position: &Position) -> [f32; 4] {
+        [position.x.in_float, position.y.in_float, position.z.in_float, position.r.in_float]
+    }
+
+    /// Shape centre at (180, 20, 30), +u pointing up and +v to -y, so the normal stays +x.
+    /// The points are further out than a unit and v is skewed towards u, both get taken out.
+    fn calibration() -> DrawingCalibration {
+        DrawingCalibration::from_points([180.0, 20.0, 30.0], [180.0, 20.0, 80.0], [180.0, -40.0, 60.0])
+            .unwrap()
+    }
+
+    fn square() -> ShapeDefinition {
+        ShapeDefinition::builtin().swap_remove(1)
+    }
+
+    #[test]
+    fn calibration_moves_the_default_frame() {
+        let calibration = calibration();
+        assert_eq!(calibration.u_axis, [0.0, 0.0, 1.0]);
+        assert_eq!(calibration.v_axis, [0.0, -1.0, 0.0]);
+        assert_eq!(calibration.normal(), [1.0, 0.0, 0.0]);
+
+        assert_eq!(calibration.apply(ShapeDefinition::default_origin()), [180.0, 20.0, 30.0]);
+        // dy along u, dz along v, dx along the normal.
+        assert_eq!(calibration.apply([200.0, 5.0, 3.0]), [180.0, 17.0, 35.0]);
+        assert_eq!(calibration.apply([210.0, 0.0, 0.0]), [190.0, 20.0, 30.0]);
+        assert_eq!(calibration.unapply([180.0, 17.0, 35.0]), [200.0, 5.0, 3.0]);
+
+        let unmoved =
+            DrawingCalibration::from_points([200.0, 0.0, 0.0], [200.0, 1.0, 0.0], [200.0, 0.0, 1.0])
+                .unwrap();
+        assert_eq!(unmoved.apply([210.0, -15.0, 12.5]), [210.0, -15.0, 12.5]);
+    }
+
+    #[test]
+    fn calibration_needs_three_distinct_points() {
+        let origin = [200.0, 0.0, 0.0];
+        assert!(DrawingCalibration::from_points(origin, origin, [200.0, 0.0, 10.0]).is_err());
+        assert!(DrawingCalibration::from_points(origin, [200.0, 10.0, 0.0], [200.0, 30.0, 0.0]).is_err());
+    }
+
+    #[test]
+    fn shapes_are_drawn_on_the_calibrated_frame() {
+        let square = square();
+        let default = square.motion_data(100.0, 100.0, None);
+        assert_eq!(xyzr(&default[0]), [200.0, 15.0, 12.5, 0.0]);
+
+        let moved = square.motion_data(100.0, 100.0, Some(&calibration()));
+        assert_eq!(moved.len(), 4);
+        assert_eq!(xyzr(&moved[0]), [180.0, 7.5, 45.0, 0.0]);
+        assert_eq!(xyzr(&moved[2]), [180.0, 42.5, 15.0, 0.0]);
+        assert_eq!((moved[0].v.in_float, moved[0].a.in_float), (100.0, 100.0));
+    }
+
+    #[test]
+    fn drawing_ends_at_the_rest_pose() {
+        let square = square();
+        let rest_pose = [210.0, 0.0, 10.0, 5.0];
+        let steps = square.drawing_steps(100.0, 100.0, None, rest_pose);
+        let Some(SequenceStep::Move(rest)) = steps.last() else {
+            panic!("expected the rest move last");
+        };
+        assert_eq!(xyzr(rest), rest_pose);
+        assert!(matches!(steps[steps.len() - 3], SequenceStep::Relay(false)));
+
+        // Closed shapes start at their last corner, the pen goes down before the first one.
+        let Some(SequenceStep::Move(start)) = steps.get(1) else {
+            panic!("expected the start corner");
+        };
+        assert_eq!(xyzr(start), [200.0, -15.0, 12.5, 0.0]);
+        assert!(matches!(steps[3], SequenceStep::Relay(true)));
+        assert_eq!(steps.iter().filter(|step| matches!(step, SequenceStep::Move(_))).count(), 6);
+
+        // The rest pose moves along with the drawing frame.
+        let calibration = calibration();
+        let steps = square.drawing_steps(100.0, 100.0, Some(&calibration), rest_pose);
+        let Some(SequenceStep::Move(rest)) = steps.last() else {
+            panic!("expected the rest move last");
+        };
+        assert_eq!(xyzr(rest), [190.0, 10.0, 30.0, 5.0]);
+        assert_eq!(xyzr(rest), xyzr(&rest_position(rest_pose, Some(&calibration))));
+    }
+}
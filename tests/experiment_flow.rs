@@ -0,0 +1,608 @@
+use iced_dobot_controller::utils::{
+    experiment::{build_results, create_experiment_instance, latin_square_row},
+    journal::parse_journal,
+    sequencer::SequencerModel,
+    structs::{
+        CompletionStatus, ExperimentAction, ExperimentPlan, ExperimentProtocol, ExperimentSession, ExperimentStage,
+        JournalEntry, ParameterType, Parameters, Randomization, RandomizationStrategy, SequenceStep, ShapeDefinition,
+    },
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+fn shape(name: &str) -> ShapeDefinition {
+    serde_json::from_str(&format!(
+        r#"{{ "name": "{}", "symbol": "{}", "vertices": [[0, 0], [10, 0], [0, 10]] }}"#,
+        name, name
+    ))
+    .unwrap()
+}
+
+fn parameters(test_count: &str) -> Parameters {
+    let mut parameters = Parameters::new();
+    for (param_type, value) in [
+        (ParameterType::SubjectName, "test"),
+        (ParameterType::TestCount, test_count),
+        (ParameterType::Voltage, "5"),
+        (ParameterType::Acceleration, "100"),
+        (ParameterType::Speed, "100"),
+        (ParameterType::ModulationType, "none"),
+        (ParameterType::ModulationFrequency, "0"),
+        (ParameterType::CarrierType, "sine"),
+        (ParameterType::CarrierFrequency, "200"),
+    ] {
+        parameters.update(param_type, value.to_string()).unwrap();
+    }
+    parameters
+}
+
+fn begin(test_count: &str) -> ExperimentSession {
+    ExperimentSession::begin(
+        parameters(test_count),
+        &[(shape("circle"), true), (shape("square"), true), (shape("star"), false)],
+        Randomization::default(),
+        None,
+        &mut StdRng::seed_from_u64(7),
+    )
+    .unwrap()
+}
+
+/// Runs one trial up to the answer buttons, returns the shape that was drawn.
+fn draw_trial(session: &mut ExperimentSession, now_ms: u128) -> String {
+    assert_eq!(session.advance(), ExperimentAction::Advance);
+    let ExperimentAction::Draw(drawn) = session.advance() else {
+        panic!("expected a drawing");
+    };
+    assert_eq!(session.drawing_finished(now_ms), ExperimentAction::AwaitAnswer);
+    drawn.name
+}
+
+#[test]
+fn full_run_answers_every_trial() {
+    let mut session = begin("3");
+    assert_eq!(session.trial_count(), 3);
+    assert_eq!(session.current_motion(), (100.0, 100.0));
+
+    for trial in 0..3 {
+        let drawn = draw_trial(&mut session, 1000);
+        assert_ne!(drawn, "star", "unselected shapes are never drawn");
+        let expected = if trial < 2 {
+            ExperimentAction::Advance
+        } else {
+            ExperimentAction::Finished
+        };
+        assert_eq!(session.answer(drawn, 1250), expected);
+    }
+
+    let results = session.results();
+    assert_eq!(results.status, CompletionStatus::Complete);
+    assert_eq!(results.results.len(), 3);
+    assert_eq!(results.avg_correct_answers, 3);
+    assert_eq!(results.avg_time, 250);
+    assert!(results.results.iter().all(|item| item.is_correct));
+}
+
+#[test]
+fn retry_counts_and_keeps_the_timer() {
+    let mut session = begin("1");
+    let drawn = draw_trial(&mut session, 1000);
+
+    assert_eq!(session.retry(), ExperimentAction::Advance);
+    assert_eq!(session.stage, ExperimentStage::Preparation);
+    assert_eq!(
+        session.advance(),
+        ExperimentAction::Draw(session.current_shape().clone())
+    );
+    assert_eq!(session.drawing_finished(3000), ExperimentAction::AwaitAnswer);
+    assert_eq!(session.answer(drawn, 4000), ExperimentAction::Finished);
+
+    let results = session.results();
+    assert_eq!(results.results[0].retries, 1);
+    assert_eq!(results.results[0].time, 3000);
+}
+
+#[test]
+fn out_of_stage_messages_are_ignored() {
+    let mut session = begin("2");
+    assert_eq!(session.answer("circle".to_string(), 0), ExperimentAction::Ignored);
+    assert_eq!(session.retry(), ExperimentAction::Ignored);
+    assert_eq!(session.drawing_finished(0), ExperimentAction::Ignored);
+    assert_eq!(session.stage, ExperimentStage::BeginTiming);
+}
+
+#[test]
+fn begin_reports_every_problem() {
+    let problems = ExperimentSession::begin(
+        Parameters::new(),
+        &[(shape("circle"), false)],
+        Randomization::default(),
+        None,
+        &mut StdRng::seed_from_u64(7),
+    )
+    .unwrap_err();
+    assert_eq!(problems.len(), 10);
+    assert_eq!(problems.last().unwrap(), "Please select some shapes!");
+
+    assert!(ExperimentSession::begin(
+        parameters("0"),
+        &[(shape("circle"), true)],
+        Randomization::default(),
+        None,
+        &mut StdRng::seed_from_u64(7),
+    )
+    .is_err());
+}
+
+#[test]
+fn wrong_answers_land_in_the_analysis() {
+    let mut session = begin("1");
+    let drawn = draw_trial(&mut session, 0);
+    let guess = if drawn == "circle" { "square" } else { "circle" };
+    session.answer(guess.to_string(), 500);
+
+    let results = build_results(&session.parameters, &session.instance);
+    assert_eq!(results.avg_correct_answers, 0);
+    let analysis = results
+        .analyses
+        .iter()
+        .find(|analysis| analysis.main_shape == drawn)
+        .unwrap();
+    assert_eq!(analysis.main_shape_count, 0);
+    assert!(analysis
+        .wrong_shapes
+        .iter()
+        .any(|(name, count)| name == guess && *count == 1));
+}
+
+#[test]
+fn journal_resumes_at_the_next_trial() {
+    let mut session = begin("3");
+    let mut journal = vec![serde_json::to_string(&session.journal_header()).unwrap()];
+    for _ in 0..2 {
+        let drawn = draw_trial(&mut session, 0);
+        session.answer(drawn, 100);
+        journal.push(serde_json::to_string(&session.last_trial_entry().unwrap()).unwrap());
+    }
+    // Crash while writing the third line.
+    journal.push("{\"Trial\":{\"gue".to_string());
+
+    let resumed =
+        ExperimentSession::from_journal(parse_journal(&journal.join("\n")).unwrap()).unwrap();
+    assert_eq!(resumed.answered_count(), 2);
+    assert_eq!(resumed.instance.experiment_index, 2);
+    assert_eq!(resumed.stage, ExperimentStage::BeginTiming);
+    assert_eq!(resumed.current_shape(), session.current_shape());
+
+    let partial = resumed.aborted_results("crash".to_string()).unwrap();
+    assert_eq!(partial.status, CompletionStatus::Aborted("crash".to_string()));
+    assert_eq!(partial.results.len(), 2);
+}
+
+#[test]
+fn balanced_blocks_show_every_shape_equally_often() {
+    let shapes = vec![shape("circle"), shape("square"), shape("star")];
+    let randomization = Randomization {
+        strategy           : RandomizationStrategy::BalancedBlocks,
+        latin_square_row   : 0,
+        no_immediate_repeat: true,
+    };
+    for seed in 0..20 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let instance = create_experiment_instance(shapes.clone(), 12, randomization, &mut rng);
+        for name in ["circle", "square", "star"] {
+            let count = instance
+                .list_of_shapes
+                .iter()
+                .filter(|shape| shape.name == name)
+                .count();
+            assert_eq!(count, 4);
+        }
+        assert!(instance
+            .list_of_shapes
+            .windows(2)
+            .all(|pair| pair[0].name != pair[1].name));
+    }
+}
+
+#[test]
+fn uniform_without_repeats_never_repeats() {
+    let shapes = vec![shape("circle"), shape("square")];
+    let randomization = Randomization {
+        no_immediate_repeat: true,
+        ..Randomization::default()
+    };
+    let instance =
+        create_experiment_instance(shapes, 50, randomization, &mut StdRng::seed_from_u64(3));
+    assert!(instance
+        .list_of_shapes
+        .windows(2)
+        .all(|pair| pair[0].name != pair[1].name));
+}
+
+#[test]
+fn latin_square_rows_are_balanced() {
+    for n_shapes in [3, 4, 5] {
+        let n_rows = if n_shapes % 2 == 0 { n_shapes } else { 2 * n_shapes };
+        let rows = (0..n_rows)
+            .map(|row| latin_square_row(n_shapes, row))
+            .collect::<Vec<Vec<usize>>>();
+        // Every shape in every position equally often, every ordered pair adjacent equally often.
+        let mut firsts = vec![0; n_shapes];
+        let mut pairs = vec![vec![0; n_shapes]; n_shapes];
+        for row in &rows {
+            firsts[row[0]] += 1;
+            for pair in row.windows(2) {
+                pairs[pair[0]][pair[1]] += 1;
+            }
+        }
+        assert!(firsts.iter().all(|count| *count == n_rows / n_shapes));
+        let expected = n_rows / n_shapes;
+        for (a, row) in pairs.iter().enumerate() {
+            for (b, count) in row.iter().enumerate() {
+                assert_eq!(*count, if a == b { 0 } else { expected }, "{} shapes", n_shapes);
+            }
+        }
+    }
+    assert_eq!(latin_square_row(4, 1), latin_square_row(4, 5));
+}
+
+#[test]
+fn seed_reproduces_the_trial_order() {
+    let shapes = [(shape("circle"), true), (shape("square"), true), (shape("star"), true)];
+    let mut seeded = parameters("20");
+    seeded.update(ParameterType::Seed, "1234".to_string()).unwrap();
+    let plan_a = ExperimentPlan::generate(
+        &seeded,
+        &shapes,
+        Randomization::default(),
+        None,
+        &mut StdRng::seed_from_u64(1),
+    )
+    .unwrap();
+    let plan_b = ExperimentPlan::generate(
+        &seeded,
+        &shapes,
+        Randomization::default(),
+        None,
+        &mut StdRng::seed_from_u64(2),
+    )
+    .unwrap();
+    assert_eq!(plan_a, plan_b);
+
+    // Without a seed one is drawn and recorded, drawing again with it gives the same order.
+    let unseeded = begin("20");
+    let recorded_seed = unseeded.results().parameters.seed.value;
+    assert!(!recorded_seed.is_empty());
+    let mut reseeded = parameters("20");
+    reseeded.update(ParameterType::Seed, recorded_seed).unwrap();
+    let again = ExperimentSession::begin(
+        reseeded,
+        &[(shape("circle"), true), (shape("square"), true), (shape("star"), false)],
+        Randomization::default(),
+        None,
+        &mut StdRng::seed_from_u64(99),
+    )
+    .unwrap();
+    assert_eq!(again.instance.list_of_shapes, unseeded.instance.list_of_shapes);
+}
+
+#[test]
+fn imported_plan_runs_as_written() {
+    let plan: ExperimentPlan = serde_json::from_str(&format!(
+        r#"{{ "name": "audit", "shapes_selection": [{}, {}], "list_of_shapes": [{}, {}, {}] }}"#,
+        serde_json::to_string(&shape("circle")).unwrap(),
+        serde_json::to_string(&shape("square")).unwrap(),
+        serde_json::to_string(&shape("square")).unwrap(),
+        serde_json::to_string(&shape("circle")).unwrap(),
+        serde_json::to_string(&shape("square")).unwrap(),
+    ))
+    .unwrap();
+    let session = ExperimentSession::from_plan(parameters("50"), plan.clone()).unwrap();
+    assert_eq!(session.trial_count(), 3);
+    assert_eq!(session.parameters.test_count.value, "3");
+    assert_eq!(session.instance.list_of_shapes, plan.list_of_shapes);
+    assert_eq!(session.results().plan.as_deref(), Some("audit"));
+
+    let mut broken = plan;
+    broken.shapes_selection.pop();
+    assert!(ExperimentSession::from_plan(parameters("3"), broken).is_err());
+}
+
+fn protocol(scheduling: &str, overridden: &str, values: [&str; 2]) -> ExperimentProtocol {
+    serde_json::from_str(&format!(
+        r#"{{ "name": "sweep", "scheduling": "{}", "conditions": [
+            {{ "name": "low", "overrides": [{{ "parameter_type": "{}", "value": "{}" }}] }},
+            {{ "name": "high", "overrides": [{{ "parameter_type": "{}", "value": "{}" }}] }}
+        ] }}"#,
+        scheduling, overridden, values[0], overridden, values[1]
+    ))
+    .unwrap()
+}
+
+fn begin_protocol(test_count: &str, protocol: &ExperimentProtocol) -> ExperimentSession {
+    ExperimentSession::begin(
+        parameters(test_count),
+        &[(shape("circle"), true), (shape("square"), true)],
+        Randomization::default(),
+        Some(protocol),
+        &mut StdRng::seed_from_u64(7),
+    )
+    .unwrap()
+}
+
+#[test]
+fn protocol_tags_trials_and_breaks_results_down() {
+    let mut session = begin_protocol("5", &protocol("Blocked", "Speed", ["20", "40"]));
+    assert_eq!(session.trial_count(), 5);
+
+    let mut speeds = Vec::new();
+    for trial in 0..5 {
+        let drawn = draw_trial(&mut session, 0);
+        speeds.push(session.current_motion().0);
+        let guess = if trial == 0 { "wrong".to_string() } else { drawn };
+        session.answer(guess, 100 * (trial + 1));
+    }
+    assert_eq!(speeds, [20.0, 20.0, 20.0, 40.0, 40.0]);
+
+    let results = session.results();
+    assert_eq!(results.protocol.as_deref(), Some("sweep"));
+    let conditions = results
+        .results
+        .iter()
+        .map(|item| item.condition.clone().unwrap())
+        .collect::<Vec<String>>();
+    assert_eq!(conditions, ["low", "low", "low", "high", "high"]);
+    let low = &results.condition_analyses[0];
+    let high = &results.condition_analyses[1];
+    assert_eq!((low.condition.as_str(), low.trial_count, low.avg_correct_answers), ("low", 3, 2));
+    assert_eq!((high.condition.as_str(), high.trial_count, high.avg_correct_answers), ("high", 2, 2));
+    assert_eq!(low.parameters.speed.value, "20");
+    assert_eq!(high.avg_time, 450);
+    assert_eq!(results.avg_correct_answers, 4);
+}
+
+#[test]
+fn hand_set_conditions_wait_for_setup() {
+    let sweep = protocol("Interleaved", "Voltage", ["5", "10"]);
+    let mut session = begin_protocol("40", &sweep);
+    let schedule = session.instance.conditions.clone().unwrap();
+    let low_count = schedule.list_of_conditions.iter().filter(|idx| **idx == 0).count();
+    assert_eq!(low_count, 20);
+    assert_ne!(schedule.list_of_conditions[..20], [0; 20]);
+
+    // The entered voltage is 5, but the first condition still has to be set up by hand.
+    let mut setups = 0;
+    let mut journal = vec![session.journal_header()];
+    for trial in 0..40 {
+        let changes = trial == 0 || schedule.list_of_conditions[trial] != schedule.list_of_conditions[trial - 1];
+        match session.advance() {
+            ExperimentAction::AwaitConditionSetup(condition) => {
+                assert!(changes);
+                assert_eq!(condition, sweep.conditions[schedule.list_of_conditions[trial]]);
+                assert_eq!(session.stage, ExperimentStage::ConditionSetup);
+                assert_eq!(session.advance(), ExperimentAction::Ignored);
+                assert_eq!(session.confirm_condition(), ExperimentAction::Advance);
+                setups += 1;
+            }
+            action => {
+                assert!(!changes);
+                assert_eq!(action, ExperimentAction::Advance);
+            }
+        }
+        let ExperimentAction::Draw(drawn) = session.advance() else {
+            panic!("expected a drawing");
+        };
+        session.drawing_finished(0);
+        session.answer(drawn.name, 0);
+        journal.push(session.last_trial_entry().unwrap());
+        if trial == 9 {
+            let contents = journal
+                .iter()
+                .map(|entry| serde_json::to_string(entry).unwrap())
+                .collect::<Vec<String>>()
+                .join("\n");
+            let mut resumed = ExperimentSession::from_journal(parse_journal(&contents).unwrap()).unwrap();
+            assert_eq!(resumed.instance.conditions, session.instance.conditions);
+            assert!(matches!(resumed.advance(), ExperimentAction::AwaitConditionSetup(_)));
+        }
+    }
+    assert!(setups > 2);
+}
+
+#[test]
+fn protocol_problems_are_reported() {
+    let problems = ExperimentSession::begin(
+        parameters("1"),
+        &[(shape("circle"), true)],
+        Randomization::default(),
+        Some(&protocol("Blocked", "TestCount", ["3", "4"])),
+        &mut StdRng::seed_from_u64(7),
+    )
+    .unwrap_err();
+    assert_eq!(problems.len(), 2);
+
+    let mut unset_speed = parameters("4");
+    unset_speed.update(ParameterType::Speed, String::new()).unwrap();
+    assert!(ExperimentSession::begin(
+        unset_speed.clone(),
+        &[(shape("circle"), true)],
+        Randomization::default(),
+        Some(&protocol("Blocked", "Speed", ["20", "40"])),
+        &mut StdRng::seed_from_u64(7),
+    )
+    .is_ok());
+    assert!(ExperimentSession::begin(
+        unset_speed,
+        &[(shape("circle"), true)],
+        Randomization::default(),
+        Some(&protocol("Blocked", "Speed", ["20", ""])),
+        &mut StdRng::seed_from_u64(7),
+    )
+    .is_err());
+}
+
+#[test]
+fn practice_gives_feedback_and_stays_out_of_the_scores() {
+    let mut with_practice = parameters("3");
+    with_practice.update(ParameterType::PracticeCount, "2".to_string()).unwrap();
+    with_practice.update(ParameterType::Seed, "42".to_string()).unwrap();
+    let mut session = ExperimentSession::begin(
+        with_practice,
+        &[(shape("circle"), true), (shape("square"), true)],
+        Randomization::default(),
+        None,
+        &mut StdRng::seed_from_u64(7),
+    )
+    .unwrap();
+    let practice_shapes = session.practice.clone().unwrap().list_of_shapes;
+    let mut practice_names = practice_shapes.iter().map(|shape| shape.name.clone()).collect::<Vec<String>>();
+    practice_names.sort();
+    assert_eq!(practice_names, ["circle", "square"]);
+
+    let mut journal = vec![session.journal_header()];
+    assert!(session.is_practice());
+    draw_trial(&mut session, 0);
+    assert_eq!(session.answer("wrong".to_string(), 10), ExperimentAction::PracticeFeedback(false));
+    assert_eq!(session.practice_feedback(), Some((&practice_shapes[0], false)));
+    journal.push(session.last_trial_entry().unwrap());
+    assert!(matches!(journal[1], JournalEntry::Practice { .. }));
+
+    // A crash during practice resumes at the next practice trial.
+    let resumed = ExperimentSession::from_journal(journal.clone()).unwrap();
+    assert!(resumed.is_practice());
+    assert_eq!(resumed.current_shape(), &practice_shapes[1]);
+
+    let drawn = draw_trial(&mut session, 0);
+    assert_eq!(session.answer(drawn, 10), ExperimentAction::PracticeFeedback(true));
+    assert!(session.is_practice());
+    for _ in 0..3 {
+        let drawn = draw_trial(&mut session, 0);
+        assert!(!session.is_practice());
+        session.answer(drawn, 20);
+    }
+    assert_eq!(session.stage, ExperimentStage::NotInExperiment);
+
+    let results = session.results();
+    assert_eq!((results.results.len(), results.avg_correct_answers, results.avg_time), (3, 3, 20));
+    assert_eq!(results.practice.len(), 2);
+    assert!(!results.practice[0].is_correct && results.practice[1].is_correct);
+    assert_eq!(results.status, CompletionStatus::Complete);
+
+    // Practice is drawn after the recorded trials, so it does not change their order.
+    let without_practice = ExperimentSession::begin(
+        {
+            let mut seeded = parameters("3");
+            seeded.update(ParameterType::Seed, "42".to_string()).unwrap();
+            seeded
+        },
+        &[(shape("circle"), true), (shape("square"), true)],
+        Randomization::default(),
+        None,
+        &mut StdRng::seed_from_u64(7),
+    )
+    .unwrap();
+    assert_eq!(without_practice.instance.list_of_shapes, session.instance.list_of_shapes);
+    assert!(without_practice.practice.is_none());
+}
+
+fn staircase_protocol(conditions: &str) -> ExperimentProtocol {
+    serde_json::from_str(&format!(
+        r#"{{ "name": "threshold", "conditions": [{}], "staircase": {{
+            "parameter": "Voltage", "rule": "OneUpTwoDown", "start": 4, "step": 1,
+            "min": 1, "max": 10, "reversals": 3, "discard_reversals": 1
+        }} }}"#,
+        conditions
+    ))
+    .unwrap()
+}
+
+#[test]
+fn staircase_stops_on_its_reversals() {
+    let protocol = staircase_protocol("");
+    let mut session = begin_protocol("20", &protocol);
+    assert_eq!(session.parameters.voltage.value, "4");
+
+    // Two right in a row step down, a wrong one steps up, every level change is set up by hand.
+    let answers = [true, true, true, true, false, true, true, false];
+    let mut journal = vec![session.journal_header()];
+    let mut setup_levels = Vec::new();
+    for (trial, is_correct) in answers.into_iter().enumerate() {
+        let level = session.staircase_level().unwrap();
+        if let ExperimentAction::AwaitConditionSetup(setup) = session.advance() {
+            assert_eq!(setup.overrides[0].value, level.to_string());
+            session.confirm_condition();
+            setup_levels.push(level);
+        }
+        let ExperimentAction::Draw(drawn) = session.advance() else {
+            panic!("expected a drawing");
+        };
+        session.drawing_finished(0);
+        let guess = if is_correct { drawn.name } else { "wrong".to_string() };
+        let action = session.answer(guess, 10);
+        journal.push(session.last_trial_entry().unwrap());
+        if trial == 4 {
+            let resumed = ExperimentSession::from_journal(journal.clone()).unwrap();
+            assert_eq!(resumed.staircase_level(), Some(3));
+        }
+        if trial + 1 < answers.len() {
+            assert_eq!(action, ExperimentAction::Advance);
+        } else {
+            assert_eq!(action, ExperimentAction::Finished);
+        }
+    }
+    assert_eq!(setup_levels, [4, 3, 2, 3, 2]);
+    assert_eq!(session.trial_count(), 8);
+    assert!(ExperimentSession::from_journal(journal).unwrap().is_finished());
+
+    let results = session.results();
+    assert_eq!(results.status, CompletionStatus::Complete);
+    let levels = results.results.iter().map(|item| item.level.unwrap()).collect::<Vec<u32>>();
+    assert_eq!(levels, [4, 4, 3, 3, 2, 3, 3, 2]);
+    let staircase = results.staircase.unwrap();
+    assert_eq!(staircase.reversal_levels, [2, 3, 2]);
+    assert_eq!(staircase.threshold, Some(2.5));
+    assert!(staircase.is_converged);
+
+    // A staircase runs on its own, not within conditions.
+    assert!(ExperimentSession::begin(
+        parameters("20"),
+        &[(shape("circle"), true)],
+        Randomization::default(),
+        Some(&staircase_protocol(r#"{ "name": "fast", "overrides": [] }"#)),
+        &mut StdRng::seed_from_u64(7),
+    )
+    .is_err());
+}
+
+#[test]
+fn sequencer_reorders_and_exports() {
+    let mut sequencer = SequencerModel::new();
+    sequencer.steps = vec![
+        SequenceStep::Wait(1),
+        SequenceStep::Wait(2),
+        SequenceStep::Wait(3),
+    ];
+    let waits = |sequencer: &SequencerModel| {
+        sequencer
+            .steps
+            .iter()
+            .map(|step| match step {
+                SequenceStep::Wait(ms) => *ms,
+                _ => 0,
+            })
+            .collect::<Vec<u64>>()
+    };
+
+    sequencer.move_step(0, 3);
+    assert_eq!(waits(&sequencer), [2, 3, 1]);
+    sequencer.swap_steps(0, 2);
+    assert_eq!(waits(&sequencer), [1, 3, 2]);
+    sequencer.remove_step(5);
+    assert_eq!(sequencer.steps.len(), 3);
+
+    assert!(sequencer.set_repeat_input("0".to_string()).is_err());
+    sequencer.set_repeat_input("4".to_string()).unwrap();
+    sequencer.set_dwell_input(String::new()).unwrap();
+    let named_sequence = sequencer.named_sequence();
+    assert_eq!(named_sequence.repeat_count, 4);
+    assert_eq!(named_sequence.dwell_ms, 0);
+}